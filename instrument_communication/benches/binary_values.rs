@@ -0,0 +1,66 @@
+//! Decoding and reading of a 50 MB record, the largest response a VISA connection accepts.
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use instrument_communication::binary_block::{decode_values, encode, BlockValue, ByteOrder};
+use instrument_communication::communication::BinaryValues;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::thread;
+
+const RECORD_SIZE: usize = 50_000_000;
+
+fn decode<T: BlockValue>(c: &mut Criterion, name: &str) {
+    let data: Vec<u8> = (0..RECORD_SIZE).map(|i| i as u8).collect();
+    let mut group = c.benchmark_group(format!("decode_{name}"));
+    group.throughput(Throughput::Bytes(RECORD_SIZE as u64));
+    group.sample_size(10);
+    let swapped = match ByteOrder::NATIVE {
+        ByteOrder::BigEndian => ByteOrder::LittleEndian,
+        ByteOrder::LittleEndian => ByteOrder::BigEndian,
+    };
+    for (label, order) in [("native", ByteOrder::NATIVE), ("swapped", swapped)] {
+        group.bench_with_input(BenchmarkId::from_parameter(label), &order, |b, order| {
+            b.iter(|| decode_values::<T>(&data, *order).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn decode_all(c: &mut Criterion) {
+    decode::<i8>(c, "i8");
+    decode::<i16>(c, "i16");
+    decode::<f32>(c, "f32");
+    decode::<f64>(c, "f64");
+}
+
+/// Reads the record from a loopback server that answers every `CURV?` with the same block.
+fn query_over_tcp(c: &mut Criterion) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let mut response = encode(&vec![0x5Au8; RECORD_SIZE]);
+        response.push(b'\n');
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut command = [0u8; 64];
+        while stream.read(&mut command).is_ok_and(|n| n > 0) {
+            if stream.write_all(&response).is_err() {
+                break;
+            }
+        }
+    });
+    let mut conn = instrument_communication::connect(format!("127.0.0.1:{port}")).unwrap();
+    conn.set_timeout(std::time::Duration::from_secs(10))
+        .unwrap();
+    let mut group = c.benchmark_group("query_over_tcp");
+    group.throughput(Throughput::Bytes(RECORD_SIZE as u64));
+    group.sample_size(10);
+    group.bench_function("f32", |b| {
+        b.iter(|| {
+            conn.query_binary_values::<f32>("CURV?", ByteOrder::BigEndian)
+                .unwrap()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, decode_all, query_over_tcp);
+criterion_main!(benches);
@@ -0,0 +1,170 @@
+//! Aliases such as `DMM1` that stand for instrument addresses. They come from an aliases file
+//! with one `<alias> = <address>` per line or from the aliases a VISA binary has configured in
+//! its connection expert. [`InstAddr::new`] resolves names that aren't addresses through the
+//! installed aliases. They are loaded from the file in the `INSTRUMENT_ALIASES` environment
+//! variable unless [`Aliases::install`] replaces them.
+use super::error::AddressError;
+use super::{parse_address, InstAddr};
+use crate::connection::visa_conn::try_load_binary;
+use crate::err::Error;
+use lazy_static::lazy_static;
+use log::error;
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::RwLock;
+use visa::Binary;
+
+/// The environment variable with the path of the aliases file that is loaded by default.
+pub const ALIASES_ENV_VAR: &str = "INSTRUMENT_ALIASES";
+
+lazy_static! {
+    pub(crate) static ref ALIAS_REGEX: Regex = Regex::new(r"^[A-Za-z_][\w\-]*$").unwrap();
+    static ref INSTALLED: RwLock<Aliases> = RwLock::new(Aliases::from_env());
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Aliases {
+    /// The addresses by lowercase alias. Aliases are not case sensitive.
+    entries: BTreeMap<String, InstAddr>,
+    /// The binary whose aliases are used for names that are not in `entries`.
+    binary: Option<Binary>,
+}
+
+impl Aliases {
+    pub fn new() -> Aliases {
+        Aliases::default()
+    }
+
+    /// Reads an aliases file. Lines starting with `#` are comments.
+    pub fn load(path: impl AsRef<Path>) -> Result<Aliases, Error> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            Error::ParseFailed(
+                format!("Failed to read aliases file {}. {e}", path.display()).into(),
+            )
+        })?;
+        text.parse().map_err(|e| match e {
+            Error::ParseFailed(msg) => {
+                Error::ParseFailed(format!("{}:{msg}", path.display()).into())
+            }
+            e => e,
+        })
+    }
+
+    fn from_env() -> Aliases {
+        match std::env::var_os(ALIASES_ENV_VAR) {
+            Some(path) => Aliases::load(path).unwrap_or_else(|e| {
+                error!("{e}");
+                Aliases::default()
+            }),
+            None => Aliases::default(),
+        }
+    }
+
+    pub fn insert(&mut self, alias: &str, address: InstAddr) -> Result<(), AddressError> {
+        if !ALIAS_REGEX.is_match(alias) {
+            return Err(AddressError::InvalidAlias {
+                name: alias.to_owned(),
+                span: 0..alias.len(),
+            });
+        }
+        self.entries.insert(alias.to_ascii_lowercase(), address);
+        Ok(())
+    }
+
+    /// Resolves aliases that are not in the file through `viParseRsrcEx` of the binary.
+    pub fn with_binary(mut self, binary: Binary) -> Aliases {
+        self.binary = Some(binary);
+        self
+    }
+
+    /// The address of an alias. The entries of the file take precedence over the binary.
+    pub fn get(&self, alias: &str) -> Option<InstAddr> {
+        if let Some(address) = self.entries.get(&alias.to_ascii_lowercase()) {
+            return Some(address.clone());
+        }
+        let rm = try_load_binary(self.binary.clone()?).ok()?;
+        let parsed = rm.parse_resource(alias).ok()?;
+        parsed.alias?;
+        parse_address(&parsed.expanded_name).ok()
+    }
+
+    /// The aliases of the file with their addresses.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &InstAddr)> {
+        self.entries
+            .iter()
+            .map(|(alias, address)| (alias.as_str(), address))
+    }
+
+    /// Makes these the aliases that [`InstAddr::new`] resolves.
+    pub fn install(self) {
+        match INSTALLED.write() {
+            Ok(mut installed) => *installed = self,
+            Err(e) => *e.into_inner() = self,
+        }
+    }
+}
+
+impl FromStr for Aliases {
+    type Err = Error;
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut aliases = Aliases::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |msg: String| Error::ParseFailed(format!("{}: {msg}", number + 1).into());
+            let (alias, address) = line
+                .split_once('=')
+                .ok_or_else(|| invalid(format!("Expected <alias> = <address> but got {line}")))?;
+            let address = parse_address(address).map_err(|e| invalid(e.to_string()))?;
+            aliases
+                .insert(alias.trim(), address)
+                .map_err(|e| invalid(e.to_string()))?;
+        }
+        Ok(aliases)
+    }
+}
+
+/// The address of an alias through the installed aliases.
+pub(crate) fn resolve(alias: &str) -> Option<InstAddr> {
+    match INSTALLED.read() {
+        Ok(installed) => installed.get(alias),
+        Err(e) => e.into_inner().get(alias),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aliases_file_is_parsed() {
+        let aliases: Aliases = "# bench 3\n\nDMM1 = GPIB0::7::INSTR\n psu-1=10.0.0.2:5025 "
+            .parse()
+            .unwrap();
+        assert_eq!(
+            aliases.get("dmm1"),
+            Some("GPIB0::7::INSTR".parse().unwrap())
+        );
+        assert_eq!(aliases.get("PSU-1"), Some("10.0.0.2:5025".parse().unwrap()));
+        assert_eq!(aliases.get("scope1"), None);
+        assert_eq!(aliases.iter().count(), 2);
+    }
+
+    #[test]
+    fn invalid_lines_name_their_line_number() {
+        let err = "DMM1 = GPIB0::7::INSTR\nPSU1 = GPIB0::40::INSTR"
+            .parse::<Aliases>()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "2: GPIB primary address 40 out of range 0-30"
+        );
+        assert!("DMM 1 = GPIB0::7::INSTR".parse::<Aliases>().is_err());
+        assert!("DMM1 GPIB0::7::INSTR".parse::<Aliases>().is_err());
+    }
+}
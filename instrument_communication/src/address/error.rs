@@ -0,0 +1,331 @@
+//! Errors of address parsing. Every error carries the byte span of the offending part of the
+//! input and can suggest a fix so configuration tools can point at the wrong entry.
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::Range;
+
+pub(crate) const GPIB_FORMAT: &str = "GPIB[board]::primary[::secondary][::INSTR]";
+pub(crate) const USB_FORMAT: &str = "USB[board]::vendor::product::serial[::interface][::INSTR]";
+pub(crate) const SERIAL_FORMAT: &str = "ASRL<port>[::INSTR][::settings]";
+pub(crate) const TCPIP_FORMAT: &str =
+    "TCPIP[board]::host[::device][::INSTR] or TCPIP[board]::host::port::SOCKET";
+pub(crate) const SOCKET_FORMAT: &str = "host:port";
+pub(crate) const VISA_FORMAT: &str = "a VISA resource string such as GPIB0::7::INSTR";
+
+/// The part of an address a number belongs to.
+#[non_exhaustive]
+#[derive(Copy, Clone, PartialEq, Debug, Eq, Hash)]
+pub enum AddressField {
+    Board,
+    GpibPrimary,
+    GpibSecondary,
+    Port,
+    VendorId,
+    ProductId,
+    UsbInterface,
+    SerialPort,
+    /// The number of an `inst` or `hislip` LAN device name.
+    LanDevice,
+}
+
+impl fmt::Display for AddressField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AddressField::Board => "board number",
+            AddressField::GpibPrimary => "GPIB primary address",
+            AddressField::GpibSecondary => "GPIB secondary address",
+            AddressField::Port => "port",
+            AddressField::VendorId => "USB vendor ID",
+            AddressField::ProductId => "USB product ID",
+            AddressField::UsbInterface => "USB interface number",
+            AddressField::SerialPort => "serial port number",
+            AddressField::LanDevice => "LAN device number",
+        })
+    }
+}
+
+#[non_exhaustive]
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub enum AddressError {
+    /// The address doesn't have the structure of any supported address.
+    UnknownFormat {
+        expected: &'static str,
+        span: Range<usize>,
+    },
+    NotANumber {
+        field: AddressField,
+        value: String,
+        span: Range<usize>,
+    },
+    OutOfRange {
+        field: AddressField,
+        value: String,
+        min: u64,
+        max: u64,
+        span: Range<usize>,
+    },
+    /// The host is neither an IP address nor a valid hostname.
+    InvalidHost {
+        host: String,
+        span: Range<usize>,
+    },
+    /// The zone of an IPv6 address doesn't name a network interface.
+    UnknownInterface {
+        zone: String,
+        span: Range<usize>,
+    },
+    InvalidSerialSettings {
+        settings: String,
+        span: Range<usize>,
+    },
+    InvalidDeviceName {
+        name: String,
+        span: Range<usize>,
+    },
+    /// Aliases start with a letter or `_` and contain letters, digits, `_` and `-`.
+    InvalidAlias {
+        name: String,
+        span: Range<usize>,
+    },
+}
+
+impl AddressError {
+    /// The byte range of the input that caused the error.
+    pub fn span(&self) -> Range<usize> {
+        self.span_ref().clone()
+    }
+
+    /// A short hint on how to fix the address.
+    pub fn suggestion(&self) -> Cow<'static, str> {
+        match self {
+            AddressError::UnknownFormat { expected, .. } => {
+                format!("write the address as {expected}").into()
+            }
+            AddressError::NotANumber { field, .. } => format!("use a number for the {field}").into(),
+            AddressError::OutOfRange {
+                field, min, max, ..
+            } => format!("use a {field} between {min} and {max}").into(),
+            AddressError::InvalidHost { .. } => {
+                "use an IPv4 address, a hostname or an IPv6 address in brackets".into()
+            }
+            AddressError::UnknownInterface { .. } => {
+                "use the name or the index of a network interface".into()
+            }
+            AddressError::InvalidSerialSettings { .. } => {
+                "write the settings as <baud>,<data bits><parity><stop bits>[,<flow control>], e.g. 9600,8N1".into()
+            }
+            AddressError::InvalidDeviceName { .. } => {
+                "use inst<n>, gpib<board>,<primary>[,<secondary>] or hislip<n>[,<port>]".into()
+            }
+            AddressError::InvalidAlias { .. } => {
+                "start the alias with a letter and use letters, digits, _ and - only".into()
+            }
+        }
+    }
+
+    fn span_ref(&self) -> &Range<usize> {
+        match self {
+            AddressError::UnknownFormat { span, .. }
+            | AddressError::NotANumber { span, .. }
+            | AddressError::OutOfRange { span, .. }
+            | AddressError::InvalidHost { span, .. }
+            | AddressError::UnknownInterface { span, .. }
+            | AddressError::InvalidSerialSettings { span, .. }
+            | AddressError::InvalidDeviceName { span, .. }
+            | AddressError::InvalidAlias { span, .. } => span,
+        }
+    }
+
+    fn span_mut(&mut self) -> &mut Range<usize> {
+        match self {
+            AddressError::UnknownFormat { span, .. }
+            | AddressError::NotANumber { span, .. }
+            | AddressError::OutOfRange { span, .. }
+            | AddressError::InvalidHost { span, .. }
+            | AddressError::UnknownInterface { span, .. }
+            | AddressError::InvalidSerialSettings { span, .. }
+            | AddressError::InvalidDeviceName { span, .. }
+            | AddressError::InvalidAlias { span, .. } => span,
+        }
+    }
+
+    /// Moves the span of an error of a part that starts at `offset` in the whole address.
+    pub(crate) fn offset(mut self, offset: usize) -> AddressError {
+        let span = self.span_mut();
+        *span = span.start + offset..span.end + offset;
+        self
+    }
+
+    pub(crate) fn with_span(mut self, span: Range<usize>) -> AddressError {
+        *self.span_mut() = span;
+        self
+    }
+}
+
+impl fmt::Display for AddressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddressError::UnknownFormat { expected, .. } => {
+                write!(f, "Unrecognized address format, expected {expected}")
+            }
+            AddressError::NotANumber { field, value, .. } => {
+                write!(f, "{field} not a number: {value:?}")
+            }
+            AddressError::OutOfRange {
+                field,
+                value,
+                min,
+                max,
+                ..
+            } => write!(f, "{field} {value} out of range {min}-{max}"),
+            AddressError::InvalidHost { host, .. } => write!(f, "Invalid host {host}"),
+            AddressError::UnknownInterface { zone, .. } => {
+                write!(f, "Unknown network interface {zone}")
+            }
+            AddressError::InvalidSerialSettings { settings, .. } => {
+                write!(f, "Invalid serial settings {settings}")
+            }
+            AddressError::InvalidDeviceName { name, .. } => {
+                write!(f, "Invalid LAN device name {name}")
+            }
+            AddressError::InvalidAlias { name, .. } => write!(f, "Invalid alias {name}"),
+        }
+    }
+}
+
+impl std::error::Error for AddressError {}
+
+/// Parses the decimal number of a field and checks that it is in `min..=max`.
+pub(crate) fn parse_number<T>(
+    field: AddressField,
+    text: &str,
+    span: Range<usize>,
+    min: T,
+    max: T,
+) -> Result<T, AddressError>
+where
+    T: TryFrom<u64> + Into<u64>,
+{
+    if text.is_empty() || !text.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Err(AddressError::NotANumber {
+            field,
+            value: text.to_owned(),
+            span,
+        });
+    }
+    let (min, max) = (min.into(), max.into());
+    text.parse::<u64>()
+        .ok()
+        .filter(|number| (min..=max).contains(number))
+        .and_then(|number| T::try_from(number).ok())
+        .ok_or_else(|| AddressError::OutOfRange {
+            field,
+            value: text.to_owned(),
+            min,
+            max,
+            span,
+        })
+}
+
+/// Checks a number that is given to an address builder. Builders have no input text so the
+/// span of the error is empty.
+pub(crate) fn check_range(
+    field: AddressField,
+    value: impl Into<u64>,
+    min: u64,
+    max: u64,
+) -> Result<(), AddressError> {
+    let value = value.into();
+    if (min..=max).contains(&value) {
+        Ok(())
+    } else {
+        Err(AddressError::OutOfRange {
+            field,
+            value: value.to_string(),
+            min,
+            max,
+            span: 0..0,
+        })
+    }
+}
+
+/// Parses the board number in capture `index`. Addresses without one use board 0.
+pub(crate) fn parse_board(captures: &regex::Captures, index: usize) -> Result<u16, AddressError> {
+    match captures.get(index) {
+        Some(board) if !board.as_str().is_empty() => parse_number(
+            AddressField::Board,
+            board.as_str(),
+            board.range(),
+            0,
+            u16::MAX,
+        ),
+        _ => Ok(0),
+    }
+}
+
+/// An address without whitespace which remembers where every byte came from, so the spans of
+/// errors point into the original input.
+pub(crate) struct Compact {
+    pub text: String,
+    origins: Vec<usize>,
+}
+
+impl Compact {
+    pub fn new(input: &str) -> Compact {
+        let mut text = String::with_capacity(input.len());
+        let mut origins = Vec::with_capacity(input.len() + 1);
+        for (index, c) in input.char_indices().filter(|(_, c)| !c.is_whitespace()) {
+            text.push(c);
+            origins.extend(index..index + c.len_utf8());
+        }
+        origins.push(input.len());
+        Compact { text, origins }
+    }
+
+    /// Maps the span of an error in the compact text back to the input.
+    pub fn restore(&self, mut error: AddressError) -> AddressError {
+        let span = error.span_mut();
+        let start = self.origins[span.start.min(self.text.len())];
+        let end = match span.end.min(self.text.len()) {
+            end if end > span.start => self.origins[end - 1] + 1,
+            _ => start,
+        };
+        *span = start..end;
+        error
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address::InstAddr;
+    use test_case::test_case;
+
+    #[test_case("GPIB0::40::INSTR", "GPIB primary address 40 out of range 0-30", 7..9)]
+    #[test_case("GPIB0 :: 4 0::INSTR", "GPIB primary address 40 out of range 0-30", 9..12)]
+    #[test_case("GPIB0::7::31", "GPIB secondary address 31 out of range 0-30", 10..12)]
+    #[test_case("192.168.0.1:abc", "port not a number: \"abc\"", 12..15)]
+    #[test_case("192.168.0.1:70000", "port 70000 out of range 0-65535", 12..17)]
+    #[test_case("USB0::0x12345::0x0101::MY1::INSTR", "USB vendor ID 0x12345 out of range 0-65535", 6..13)]
+    #[test_case("ASRL0::INSTR", "serial port number 0 out of range 1-65535", 4..5)]
+    #[test_case("ASRL1::INSTR::9600,9N1", "Invalid serial settings 9600,9N1", 14..22)]
+    #[test_case("TCPIP0::e5810::gpib0,31::INSTR", "GPIB primary address 31 out of range 0-30", 21..23)]
+    #[test_case("TCPIP0::10.0.0.5::hislip0,65536::INSTR", "port 65536 out of range 0-65535", 26..31)]
+    #[test_case("TCPIP0::10.0.0.5::5025::SOCKETS", "Unrecognized address format, expected TCPIP[board]::host[::device][::INSTR] or TCPIP[board]::host::port::SOCKET", 0..31)]
+    #[test_case("host_name:5025", "Invalid host host_name", 0..9)]
+    #[test_case("scope-lab-3", "Unrecognized address format, expected host:port", 0..11)]
+    fn errors_point_at_the_wrong_part(address: &str, message: &str, span: Range<usize>) {
+        let err = address.parse::<InstAddr>().unwrap_err();
+        assert_eq!(err.to_string(), message);
+        assert_eq!(err.span(), span);
+    }
+
+    #[test]
+    fn suggestions_name_the_valid_range() {
+        let err = "GPIB0::40::INSTR".parse::<InstAddr>().unwrap_err();
+        assert_eq!(
+            err.suggestion(),
+            "use a GPIB primary address between 0 and 30"
+        );
+    }
+}
@@ -0,0 +1,107 @@
+//! The host grammar shared by socket addresses and the LAN resource strings of VISA. A host is
+//! an IPv4 address, a hostname or an IPv6 literal. Inside resource strings IPv6 literals are
+//! written in brackets, e.g. `TCPIP::[fe80::1%eth0]::5025::SOCKET`, and may carry a zone.
+//! Hostnames may contain non-ASCII letters. They are converted to punycode with IDNA.
+use super::error::AddressError;
+use super::socket::NetworkAddr;
+use lazy_static::lazy_static;
+use std::str::FromStr;
+
+/// A hostname label: letters and digits with hyphens inside.
+const LABEL: &str = r"[\p{L}\p{N}](?:[\p{L}\p{N}\-]*[\p{L}\p{N}])?";
+/// An IPv6 literal with an optional zone. The address itself is validated when it is parsed.
+const IPV6: &str = r"[0-9a-f:.]+(?:%[\w.\-]+)?";
+
+lazy_static! {
+    /// A hostname or an IPv4 address.
+    pub static ref HOSTNAME_PATTERN: String = format!(r"{LABEL}(?:\.{LABEL})*");
+    /// A host as it is written in a resource string.
+    pub static ref HOST_PATTERN: String = format!(r"(?:\[{IPV6}\]|{})", *HOSTNAME_PATTERN);
+}
+
+/// Converts an internationalized hostname to its ASCII form, e.g. `bücher.example` becomes
+/// `xn--bcher-kva.example`. ASCII hostnames are returned as they are.
+pub fn to_ascii_hostname(host: &str) -> Result<String, AddressError> {
+    if host.is_ascii() {
+        return Ok(host.to_owned());
+    }
+    idna::domain_to_ascii(host).map_err(|_| AddressError::InvalidHost {
+        host: host.to_owned(),
+        span: 0..host.len(),
+    })
+}
+
+/// The host of an address that is built from its parts. Addresses are lowercased when they are
+/// parsed so the host is lowercased the same way.
+pub(crate) fn resource_host(host: &str) -> Result<NetworkAddr, AddressError> {
+    NetworkAddr::from_str(&host.to_ascii_lowercase())
+}
+
+/// The scope ID of an IPv6 zone. A zone is an interface index or, on Linux, an interface name.
+pub fn scope_id(zone: &str) -> Result<u32, AddressError> {
+    if let Ok(index) = zone.parse() {
+        return Ok(index);
+    }
+    #[cfg(target_os = "linux")]
+    if let Ok(name) = std::ffi::CString::new(zone) {
+        let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        if index != 0 {
+            return Ok(index);
+        }
+    }
+    Err(AddressError::UnknownInterface {
+        zone: zone.to_owned(),
+        span: 0..zone.len(),
+    })
+}
+
+/// Splits a resource string on `::` except inside the brackets of an IPv6 literal.
+pub(crate) fn split_resource(resource: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut in_brackets = false;
+    let bytes = resource.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'[' => in_brackets = true,
+            b']' => in_brackets = false,
+            b':' if !in_brackets && bytes.get(i + 1) == Some(&b':') => {
+                parts.push(&resource[start..i]);
+                i += 2;
+                start = i;
+                continue;
+            }
+            _ => (),
+        }
+        i += 1;
+    }
+    parts.push(&resource[start..]);
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("tcpip0::[fe80::1%eth0]::inst0::instr", &["tcpip0", "[fe80::1%eth0]", "inst0", "instr"])]
+    #[test_case("tcpip0::192.168.0.1::5025::socket", &["tcpip0", "192.168.0.1", "5025", "socket"])]
+    fn test_split_resource(resource: &str, expected: &[&str]) {
+        assert_eq!(split_resource(resource), expected);
+    }
+
+    #[test_case("bücher.example", "xn--bcher-kva.example")]
+    #[test_case("scope-lab-3", "scope-lab-3")]
+    fn test_to_ascii_hostname(host: &str, expected: &str) {
+        assert_eq!(to_ascii_hostname(host).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_scope_id() {
+        assert_eq!(scope_id("3"), Ok(3));
+        assert!(scope_id("no-such-interface0").is_err());
+        #[cfg(target_os = "linux")]
+        assert!(scope_id("lo").is_ok());
+    }
+}
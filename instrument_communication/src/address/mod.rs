@@ -1,24 +1,33 @@
-use crate::connection::visa_conn;
+#[cfg(target_os = "linux")]
+use crate::connection::serial_conn;
+use crate::connection::{hislip_conn, visa_conn, vxi11_conn};
 use crate::{Error, InstConnection};
+use error::*;
 use hostname;
 use lazy_static::lazy_static;
 use regex::Regex;
 use socket::*;
 use std::borrow::Cow;
-use std::ffi::CString;
 use std::fmt;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, SocketAddrV6, ToSocketAddrs};
 use std::str::FromStr;
 use visa_gpib::*;
+use visa_hislip::*;
+use visa_serial::*;
 use visa_socket::*;
+use visa_usb::*;
 use visa_vxi::*;
 
+pub mod alias;
+pub mod error;
+pub mod host;
 pub mod socket;
 pub mod visa_gpib;
+pub mod visa_hislip;
+pub mod visa_serial;
 pub mod visa_socket;
+pub mod visa_usb;
 pub mod visa_vxi;
-// pub mod visa_hislip;
-// pub mod visa_usb;
 
 #[derive(Clone, PartialEq, Debug, Eq, Hash, PartialOrd, Ord)]
 #[non_exhaustive]
@@ -38,8 +47,11 @@ impl InstAddr {
     /// all types of instrument addresses if the address matches any of
     /// the regex patterns defined above it will assume that it will not
     /// match any other format. It will then attempt to parse it and return
-    /// a Result. Note this function doesn't handle UTF8 host names yet.
-    /// It is already being explored.
+    /// a Result. Internationalized host names are converted to punycode. The spans of errors
+    /// point into `address` as it was given, including its whitespace.
+    /// Names such as `DMM1` that aren't addresses are resolved through the installed
+    /// [`Aliases`](alias::Aliases). A name that isn't an alias either returns the error of
+    /// parsing it as an address.
     /// ```rust
     /// use instrument_communication::address::InstAddr;
     /// use std::str::FromStr;
@@ -52,33 +64,44 @@ impl InstAddr {
     /// assert_eq!(method2,method3);
     /// assert_eq!(method3,method4);
     /// ```
-    pub fn new(address: impl AsRef<str>) -> Result<Self, String> {
-        let address = address
-            .as_ref()
-            .split_whitespace()
-            .collect::<String>()
-            .to_ascii_lowercase();
-        if let Some(captures) = GPIB_ADDRESS_REGEX.captures(&address) {
-            parse_gpib(captures)
-        } else if let Some(captures) = VISASOCKET_ADDRESS_REGEX.captures(&address) {
-            parse_visa_socket(captures)
-        } else if let Some(captures) = VISAVXI11_ADDRESS_REGEX.captures(&address) {
-            parse_visa_vxi11(captures)
-        } else {
-            parse_socket(&address)
-        }
+    pub fn new(address: impl AsRef<str>) -> Result<Self, AddressError> {
+        let address = address.as_ref();
+        parse_address(address).or_else(|e| {
+            let name = address.trim();
+            if !alias::ALIAS_REGEX.is_match(name) {
+                return Err(e);
+            }
+            alias::resolve(name).ok_or(e)
+        })
     }
 
-    pub fn address(&self) -> Cow<str> {
+    pub fn address(&self) -> Cow<'_, str> {
         match self {
             InstAddr::Visa(addr) => (&addr.address).into(),
-            InstAddr::Socket(addr) => match addr {
-                Socket::V4(socket) => format!("{}:{}", socket.ip(), socket.port()).into(),
-                Socket::V6(socket) => format!("{}:{}", socket.ip(), socket.port()).into(),
-                Socket::Raw(socket) => format!("{}:{}", socket.host_name, socket.port).into(),
-            },
+            InstAddr::Socket(addr) => addr.to_string().into(),
+        }
+    }
+    /// The raw socket of a socket address. VISA socket addresses are converted, other VISA
+    /// addresses return `None`.
+    pub fn to_raw_socket(&self) -> Option<Socket> {
+        match self {
+            InstAddr::Socket(socket) => Some(socket.clone()),
+            InstAddr::Visa(addr) if addr.visa_type == VisaType::Socket => {
+                Socket::host(addr.host()?.to_string(), addr.port()?).ok()
+            }
+            InstAddr::Visa(_) => None,
         }
     }
+
+    /// True if both addresses reach the same instrument, e.g. `TCPIP0::10.0.0.2::5025::SOCKET`
+    /// and `10.0.0.2:5025`. Host names are compared without resolving them.
+    pub fn is_equivalent(&self, other: &InstAddr) -> bool {
+        match (self.to_raw_socket(), other.to_raw_socket()) {
+            (Some(socket), Some(other)) => socket == other,
+            _ => self == other,
+        }
+    }
+
     ///Consume the address and return a communication interface
     pub fn connect(self) -> Result<Box<dyn InstConnection>, Error> {
         match self {
@@ -96,8 +119,50 @@ pub enum VisaType {
     Serial,
     VXI,
 }
+/// Parses an address without resolving aliases. Spans of errors point into `address` as it was
+/// given, including its whitespace.
+pub(crate) fn parse_address(address: &str) -> Result<InstAddr, AddressError> {
+    let compact = Compact::new(address);
+    let original = &compact.text;
+    let address = original.to_ascii_lowercase();
+    let parsed = if let Some(captures) = GPIB_ADDRESS_REGEX.captures(&address) {
+        parse_gpib(captures)
+    } else if let Some(captures) = USB_ADDRESS_REGEX.captures(original) {
+        parse_usb(captures)
+    } else if let Some(captures) = SERIAL_ADDRESS_REGEX.captures(original) {
+        parse_serial(captures)
+    } else if let Some(captures) = VISASOCKET_ADDRESS_REGEX.captures(&address) {
+        parse_visa_socket(captures)
+    } else if let Some(captures) = VISAHISLIP_ADDRESS_REGEX.captures(&address) {
+        parse_visa_hislip(captures)
+    } else if let Some(captures) = VISAVXI11_ADDRESS_REGEX.captures(&address) {
+        parse_visa_vxi11(captures)
+    } else if let Some(expected) = expected_visa_format(&address) {
+        Err(AddressError::UnknownFormat {
+            expected,
+            span: 0..address.len(),
+        })
+    } else {
+        parse_socket(&address)
+    };
+    parsed.map_err(|e| compact.restore(e))
+}
+
+/// The format of a VISA address that starts with a known interface but doesn't match its
+/// pattern, e.g. `GPIB0::15::INSTRx`.
+fn expected_visa_format(address: &str) -> Option<&'static str> {
+    lazy_static! {
+        static ref VISA_PREFIX_REGEX: Regex = Regex::new(r"^(gpib|usb|tcpip)\d*::").unwrap();
+    }
+    match &VISA_PREFIX_REGEX.captures(address)?[1] {
+        "gpib" => Some(GPIB_FORMAT),
+        "usb" => Some(USB_FORMAT),
+        _ => Some(TCPIP_FORMAT),
+    }
+}
+
 impl FromStr for InstAddr {
-    type Err = String;
+    type Err = AddressError;
     fn from_str(address: &str) -> Result<Self, Self::Err> {
         InstAddr::new(address)
     }
@@ -109,20 +174,128 @@ pub struct VisaAddress {
     visa_type: VisaType,
 }
 impl VisaAddress {
+    /// Opens the address through VISA. LAN instruments are opened with the native VXI-11,
+    /// HiSLIP and TCP clients when no VISA binary can be loaded, and serial instruments with
+    /// termios on Linux.
     fn connect(self) -> Result<Box<dyn InstConnection>, Error> {
-        let connection = visa_conn::VisaConn::connect(self, None)?;
-        Ok(Box::new(connection) as Box<dyn InstConnection>)
+        match visa_conn::VisaConn::connect(self.clone(), None) {
+            Ok(connection) => Ok(Box::new(connection) as Box<dyn InstConnection>),
+            Err(Error::BinaryError(_) | Error::OpenSessionError(_))
+                if self.visa_type == VisaType::VXI =>
+            {
+                let connection = vxi11_conn::Vxi11Conn::connect(self)?;
+                Ok(Box::new(connection) as Box<dyn InstConnection>)
+            }
+            Err(Error::BinaryError(_) | Error::OpenSessionError(_))
+                if self.visa_type == VisaType::Hislip =>
+            {
+                let connection = hislip_conn::HislipConn::connect(self)?;
+                Ok(Box::new(connection) as Box<dyn InstConnection>)
+            }
+            Err(e @ (Error::BinaryError(_) | Error::OpenSessionError(_)))
+                if self.visa_type == VisaType::Socket =>
+            {
+                match InstAddr::Visa(self).to_raw_socket() {
+                    Some(socket) => socket.connect(),
+                    None => Err(e),
+                }
+            }
+            #[cfg(target_os = "linux")]
+            Err(Error::BinaryError(_) | Error::OpenSessionError(_))
+                if self.visa_type == VisaType::Serial =>
+            {
+                let connection = serial_conn::SerialConn::connect(self)?;
+                Ok(Box::new(connection) as Box<dyn InstConnection>)
+            }
+            Err(e) => Err(e),
+        }
     }
 
     pub fn get_type(&self) -> VisaType {
         self.visa_type
     }
+
+    /// The board number of the interface. Serial addresses have a port instead.
+    pub fn board(&self) -> Option<u16> {
+        if self.visa_type == VisaType::Serial {
+            return None;
+        }
+        let interface = self.parts()[0];
+        let number = interface.trim_start_matches(|c: char| c.is_ascii_alphabetic());
+        if number.is_empty() {
+            Some(0)
+        } else {
+            number.parse().ok()
+        }
+    }
+
+    /// The host of a LAN address.
+    pub fn host(&self) -> Option<NetworkAddr> {
+        match self.visa_type {
+            VisaType::Socket | VisaType::VXI | VisaType::Hislip => self.parts()[1].parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// The port of a socket address or the explicit port of a HiSLIP address.
+    pub fn port(&self) -> Option<u16> {
+        match self.visa_type {
+            VisaType::Socket => self.parts()[2].parse().ok(),
+            VisaType::Hislip => match self.lan_device_name()? {
+                LanDeviceName::Hislip { port, .. } => port,
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// The primary address of a GPIB instrument, also when it is behind a LAN gateway.
+    pub fn primary_address(&self) -> Option<u8> {
+        match self.lan_device_name() {
+            Some(LanDeviceName::Gpib { primary, .. }) => Some(primary),
+            _ => self.as_gpib().map(|gpib| gpib.primary),
+        }
+    }
+
+    pub fn secondary_address(&self) -> Option<u8> {
+        match self.lan_device_name() {
+            Some(LanDeviceName::Gpib { secondary, .. }) => secondary,
+            _ => self.as_gpib().and_then(|gpib| gpib.secondary),
+        }
+    }
+
+    /// The `::` separated parts of the resource string. IPv6 hosts stay in one part.
+    pub(crate) fn parts(&self) -> Vec<&str> {
+        host::split_resource(self.as_str())
+    }
+
+    /// The resource string that is passed to VISA. Serial settings are not part of it.
+    pub fn as_str(&self) -> &str {
+        match self.address.find("::instr::") {
+            Some(end) if self.visa_type == VisaType::Serial => &self.address[..end + 7],
+            _ => &self.address,
+        }
+    }
 }
-impl From<VisaAddress> for *const i8 {
-    fn from(val: VisaAddress) -> Self {
-        CString::new(val.address)
-            .unwrap_or(CString::default())
-            .as_ptr()
+
+/// The canonical resource string, e.g. `gpib0::7::instr`. Serial addresses include their
+/// settings.
+impl fmt::Display for VisaAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.address)
+    }
+}
+
+impl FromStr for VisaAddress {
+    type Err = AddressError;
+    fn from_str(address: &str) -> Result<Self, Self::Err> {
+        match InstAddr::new(address)? {
+            InstAddr::Visa(address) => Ok(address),
+            InstAddr::Socket(_) => Err(AddressError::UnknownFormat {
+                expected: VISA_FORMAT,
+                span: 0..address.len(),
+            }),
+        }
     }
 }
 
@@ -134,21 +307,24 @@ impl From<VisaAddress> for InstAddr {
 
 #[derive(Clone, PartialEq, Debug, Eq, Hash, PartialOrd, Ord)]
 pub struct RawSocket {
-    host_name: Cow<'static,str>,
+    host_name: Cow<'static, str>,
     port: u16,
 }
 
 impl RawSocket {
-    /// get a reference to the hostname 
+    /// get a reference to the hostname
     pub fn host_name<'a>(&'a self) -> Cow<'a, str> {
-        (&self.host_name).clone()
+        self.host_name.clone()
     }
     /// Since RawSocket contains the hostname we don't have guarantee that the host name can be translated into an ip address.
     /// this function gets the SocketAddr object if it exists. The priority will be given to an ipv4 address but an ipv6 will
     /// be returned in the case where the hostname doesn't have an ipv4 address.
     pub fn get_ipv4_first(&self) -> Option<SocketAddr> {
         let mut first_ipv6: Option<SocketAddr> = None;
-        for addr in format!("{}:0", &self.host_name).to_socket_addrs().ok()? {
+        for addr in (self.host_name.as_ref(), self.port)
+            .to_socket_addrs()
+            .ok()?
+        {
             match addr {
                 SocketAddr::V4(ipv4_addr) => return Some(SocketAddr::V4(ipv4_addr)),
                 SocketAddr::V6(ipv6_addr) => {
@@ -164,6 +340,19 @@ impl RawSocket {
 
 impl std::fmt::Display for RawSocket {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.write_fmt(format_args!("{}:{}",&self.host_name,&self.port))
+        f.write_fmt(format_args!("{}:{}", &self.host_name, &self.port))
+    }
+}
+
+impl FromStr for RawSocket {
+    type Err = AddressError;
+    fn from_str(address: &str) -> Result<Self, Self::Err> {
+        match Socket::new(address)? {
+            Socket::Raw(socket) => Ok(socket),
+            _ => Err(AddressError::UnknownFormat {
+                expected: "hostname:port",
+                span: 0..address.len(),
+            }),
+        }
     }
 }
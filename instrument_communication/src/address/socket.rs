@@ -1,3 +1,4 @@
+use super::host::{resource_host, to_ascii_hostname, HOSTNAME_PATTERN};
 use crate::address::*;
 use crate::connection::tcp_conn::TcpConn;
 use std::fmt::Display;
@@ -6,13 +7,11 @@ use std::net::Ipv6Addr;
 const NUMBER_IPV4_OCTETS: usize = 4;
 
 lazy_static! {
-    pub static ref HOSTNAME_REGEX: Regex = Regex::new(
-        r"^(?i)(([a-z0-9]|[a-z0-9][a-z0-9\-]*[a-z0-9])\.)*([a-z0-9]|[a-z0-9][a-z0-9\-]*[a-z0-9])$"
-    )
-    .unwrap();
+    pub static ref HOSTNAME_REGEX: Regex =
+        Regex::new(&format!("^(?i){}$", *HOSTNAME_PATTERN)).unwrap();
 }
 
-pub fn parse_socket<T: AsRef<str>>(address: T) -> Result<InstAddr, String> {
+pub fn parse_socket<T: AsRef<str>>(address: T) -> Result<InstAddr, AddressError> {
     let socket: Socket = address.as_ref().parse()?;
     Ok(InstAddr::Socket(socket))
 }
@@ -21,6 +20,14 @@ pub fn parse_socket<T: AsRef<str>>(address: T) -> Result<InstAddr, String> {
 pub enum Socket {
     V4(SocketAddrV4),
     V6(SocketAddrV6),
+    /// An IPv6 socket with a zone such as `[fe80::1%eth0]:5025`. The zone is kept as it was
+    /// written and is only resolved when connecting, so the address stays valid on machines
+    /// without that interface.
+    ScopedV6 {
+        ip: Ipv6Addr,
+        zone: String,
+        port: u16,
+    },
     Raw(RawSocket),
 }
 
@@ -29,8 +36,9 @@ impl Socket {
     /// if the address matches any of the regex patterns defined above it
     /// will assume that it will not match any other format. It will then
     /// attempt to parse it and return a Result.
-    /// Note this function doesn't handle UTF8 host names yet. It is already
-    /// being explored. This is a non-standard parse IP implementation as It accepts IPs written
+    /// IPv6 addresses are written in brackets when they have a zone, e.g. `[fe80::1%eth0]:5025`,
+    /// host names are lowercased and internationalized ones are converted to punycode.
+    /// This is a non-standard parse IP implementation as It accepts IPs written
     /// with leading zeros such as 127.00.000.001  
     ///
     /// # Examples
@@ -47,34 +55,94 @@ impl Socket {
     /// assert_eq!(method2,method3);
     /// assert_eq!(method3,method4);
     /// ```
-    pub fn new(address: impl AsRef<str>) -> Result<Self, String> {
-        let splits: Vec<&str> = address.as_ref().split(':').map(str::trim).collect();
-        if splits.len() < 2 {
-            return Err("Incorrect Socket address format. Address format is shown inside the quotes \"IP:PortNumber\" Or \"HostName:PortNumber\" examples:\n 192.168.0.20:8080 or PCNAME1:50050".into());
-        }
-        let (port, ip) = splits
-            .split_last()
-            .expect("We already checked it has at least 2 elements");
-        let port = port
-            .parse::<u16>()
-            .map_err(|_| format!("Unable to parse port into a number. port: {}", port))?;
-        let ip = ip.join(":"); //if it is IPV4 there will be one &str and no change. If it is IPV6 they will be joined correctly.
-        let ip = NetworkAddr::from_str(&ip)?;
+    pub fn new(address: impl AsRef<str>) -> Result<Self, AddressError> {
+        let address = address.as_ref();
+        let Some(colon) = address.rfind(':') else {
+            return Err(AddressError::UnknownFormat {
+                expected: SOCKET_FORMAT,
+                span: 0..address.len(),
+            });
+        };
+        let port = parse_number(
+            AddressField::Port,
+            address[colon + 1..].trim(),
+            colon + 1..address.len(),
+            0,
+            u16::MAX,
+        )?;
+        //if it is IPV4 there will be one &str and no change. If it is IPV6 they will be joined correctly.
+        let ip = address[..colon]
+            .split(':')
+            .map(str::trim)
+            .collect::<Vec<_>>()
+            .join(":");
+        let ip = resource_host(&ip).map_err(|e| e.with_span(0..colon))?;
+        Ok(Socket::from_parts(ip, port))
+    }
+
+    pub fn v4(ip: Ipv4Addr, port: u16) -> Socket {
+        Socket::V4(SocketAddrV4::new(ip, port))
+    }
+
+    pub fn v6(ip: Ipv6Addr, port: u16) -> Socket {
+        Socket::V6(SocketAddrV6::new(ip, port, 0, 0))
+    }
+
+    /// A socket of a host given as an IP address, a hostname or an IPv6 address with a zone.
+    pub fn host(host: impl AsRef<str>, port: u16) -> Result<Socket, AddressError> {
+        Ok(Socket::from_parts(resource_host(host.as_ref())?, port))
+    }
+
+    fn from_parts(ip: NetworkAddr, port: u16) -> Socket {
         match ip {
-            NetworkAddr::V4(addr) => Ok(Socket::V4(SocketAddrV4::new(addr, port))),
-            NetworkAddr::V6(addr) => Ok(Socket::V6(SocketAddrV6::new(addr, port, 0, 0))),
-            NetworkAddr::RAW(addr) => Ok(Socket::Raw(RawSocket {
-                host_name: addr.into(),
+            NetworkAddr::V4(addr) => Socket::V4(SocketAddrV4::new(addr, port)),
+            NetworkAddr::V6(addr) => Socket::V6(SocketAddrV6::new(addr, port, 0, 0)),
+            NetworkAddr::ScopedV6 { ip, zone } => Socket::ScopedV6 { ip, zone, port },
+            // Host names are kept lowercase so equal hosts compare equal.
+            NetworkAddr::RAW(addr) => Socket::Raw(RawSocket {
+                host_name: addr.to_ascii_lowercase().into(),
                 port,
-            })),
+            }),
         }
     }
 
-    pub fn ip_or_host(&self) -> Cow<str> {
+    pub fn ip_or_host(&self) -> Cow<'_, str> {
         match self {
             Socket::V4(ref addr) => addr.ip().to_string().into(),
             Socket::V6(ref addr) => addr.ip().to_string().into(),
-            Socket::Raw(addr) => (&addr.host_name).clone(),
+            Socket::ScopedV6 { ip, .. } => ip.to_string().into(),
+            Socket::Raw(addr) => addr.host_name.clone(),
+        }
+    }
+
+    /// The host of the socket. The scope ID of an IPv6 address becomes a numeric zone.
+    pub fn network_addr(&self) -> NetworkAddr {
+        match self {
+            Socket::V4(addr) => NetworkAddr::V4(*addr.ip()),
+            Socket::V6(addr) if addr.scope_id() == 0 => NetworkAddr::V6(*addr.ip()),
+            Socket::V6(addr) => NetworkAddr::ScopedV6 {
+                ip: *addr.ip(),
+                zone: addr.scope_id().to_string(),
+            },
+            Socket::ScopedV6 { ip, zone, .. } => NetworkAddr::ScopedV6 {
+                ip: *ip,
+                zone: zone.clone(),
+            },
+            Socket::Raw(addr) => NetworkAddr::RAW(addr.host_name.to_string()),
+        }
+    }
+
+    /// The VISA form of the socket, e.g. `192.168.0.2:5025` becomes
+    /// `tcpip0::192.168.0.2::5025::socket` on board 0.
+    pub fn to_visa_socket(&self, board: u16) -> VisaAddress {
+        VisaAddress {
+            address: format!(
+                "tcpip{}::{}::{}::socket",
+                board,
+                self.network_addr().resource_host(),
+                self.port()
+            ),
+            visa_type: VisaType::Socket,
         }
     }
 
@@ -82,6 +150,7 @@ impl Socket {
         match self {
             Socket::V4(addr) => addr.port(),
             Socket::V6(addr) => addr.port(),
+            Socket::ScopedV6 { port, .. } => *port,
             Socket::Raw(addr) => addr.port,
         }
     }
@@ -91,8 +160,20 @@ impl Socket {
     }
 }
 
+/// `ip:port`, `[ipv6%scope]:port` or `hostname:port`.
+impl Display for Socket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Socket::V4(addr) => addr.fmt(f),
+            Socket::V6(addr) => addr.fmt(f),
+            Socket::ScopedV6 { ip, zone, port } => write!(f, "[{ip}%{zone}]:{port}"),
+            Socket::Raw(addr) => addr.fmt(f),
+        }
+    }
+}
+
 impl FromStr for Socket {
-    type Err = String;
+    type Err = AddressError;
     fn from_str(address: &str) -> Result<Self, Self::Err> {
         Socket::new(address)
     }
@@ -113,26 +194,38 @@ pub enum NetworkAddr {
     V4(Ipv4Addr),
     /// An IPv6 address.
     V6(Ipv6Addr),
+    /// An IPv6 address with a zone such as `fe80::1%eth0`. The zone is the name or the index of
+    /// the network interface.
+    ScopedV6 { ip: Ipv6Addr, zone: String },
     /// Raw host/computer name
     RAW(String),
 }
 
 impl FromStr for NetworkAddr {
-    type Err = String;
+    type Err = AddressError;
     /// This is a non-standard IP and hostname implementation as It accepts IPs written
     /// with leading zeros such as 127.00.000.001  
     ///
     /// # Examples
     ///
     /// ```
-    /// use instrument_communication::address::socket::parse_ip;
+    /// use instrument_communication::address::socket::NetworkAddr;
+    /// use std::str::FromStr;
     ///
-    /// assert_eq!(parse_ip("127.00.000.001"),parse_ip("127.0.0.1"));
-    /// assert_eq!(parse_ip("127.00.000.001"),parse_ip("localhost "));
-    /// assert_ne!(parse_ip("127.0.0.2"),parse_ip("127.0.0.1"));
+    /// assert_eq!(NetworkAddr::from_str("127.00.000.001"),NetworkAddr::from_str("127.0.0.1"));
+    /// assert_eq!(NetworkAddr::from_str("127.00.000.001"),NetworkAddr::from_str("localhost "));
+    /// assert_ne!(NetworkAddr::from_str("127.0.0.2"),NetworkAddr::from_str("127.0.0.1"));
     /// ```
-    fn from_str(addr: &str) -> Result<NetworkAddr, String> {
+    fn from_str(addr: &str) -> Result<NetworkAddr, AddressError> {
+        let invalid = || AddressError::InvalidHost {
+            host: addr.to_owned(),
+            span: 0..addr.len(),
+        };
         let ip_or_host: &str = addr.trim();
+        let ip_or_host = ip_or_host
+            .strip_prefix('[')
+            .and_then(|ip| ip.strip_suffix(']'))
+            .unwrap_or(ip_or_host);
         let split_ip = ip_or_host.split('.').collect::<Vec<_>>();
         let count = split_ip.len();
         let is_ipv4 =
@@ -150,8 +243,7 @@ impl FromStr for NetworkAddr {
                 })
                 .collect::<Vec<_>>()
                 .join(".");
-            let address =
-                IpAddr::from_str(&ip).map_err(|_| format!("Unable to parse IP address: {}", ip))?;
+            let address = IpAddr::from_str(&ip).map_err(|_| invalid())?;
             match address {
                 IpAddr::V4(ip) => Ok(NetworkAddr::V4(ip)),
                 IpAddr::V6(ip) => Ok(NetworkAddr::V6(ip)),
@@ -163,10 +255,21 @@ impl FromStr for NetworkAddr {
             Ok(NetworkAddr::V4(Ipv4Addr::LOCALHOST))
         } else if let Ok(ipv6) = ip_or_host.parse::<Ipv6Addr>() {
             Ok(NetworkAddr::V6(ipv6))
+        } else if let Some((ip, zone)) = ip_or_host.split_once('%') {
+            let ip = ip.parse::<Ipv6Addr>().map_err(|_| invalid())?;
+            if zone.is_empty() {
+                return Err(invalid());
+            }
+            Ok(NetworkAddr::ScopedV6 {
+                ip,
+                zone: zone.to_owned(),
+            })
         } else if HOSTNAME_REGEX.is_match(ip_or_host) {
-            Ok(NetworkAddr::RAW(ip_or_host.into()))
+            Ok(NetworkAddr::RAW(
+                to_ascii_hostname(ip_or_host).map_err(|_| invalid())?,
+            ))
         } else {
-            return Err(format!("Unable to parse IP or hostname: {}", ip_or_host));
+            Err(invalid())
         }
     }
 }
@@ -176,21 +279,24 @@ impl Display for NetworkAddr {
         match self {
             NetworkAddr::V4(addr) => addr.fmt(f),
             NetworkAddr::V6(addr) => addr.fmt(f),
+            NetworkAddr::ScopedV6 { ip, zone } => write!(f, "{ip}%{zone}"),
             NetworkAddr::RAW(addr) => f.write_str(addr),
         }
     }
 }
 
+impl NetworkAddr {
+    /// The host as it is written in a resource string. IPv6 addresses are bracketed so their
+    /// colons aren't mistaken for separators.
+    pub fn resource_host(&self) -> String {
+        match self {
+            NetworkAddr::V6(_) | NetworkAddr::ScopedV6 { .. } => format!("[{self}]"),
+            _ => self.to_string(),
+        }
+    }
+}
+
 /// checks if the string is a number between 0 and 255
-/// ```rust
-/// use instrument_communication::address::socket::is_u8;
-/// assert!(is_u8("0"));
-/// assert!(is_u8("11"));
-/// assert!(is_u8("123"));
-/// assert!(is_u8("254"));
-/// assert!(is_u8("255"));
-/// assert!(!is_u8("256"));
-/// ```
 fn is_u8(s: &str) -> bool {
     s.parse::<u8>().is_ok()
 }
@@ -209,6 +315,16 @@ mod test {
         assert_eq!(method2, method3);
     }
 
+    #[test]
+    fn testing_is_u8() {
+        assert!(is_u8("0"));
+        assert!(is_u8("11"));
+        assert!(is_u8("123"));
+        assert!(is_u8("254"));
+        assert!(is_u8("255"));
+        assert!(!is_u8("256"));
+    }
+
     #[test]
     fn testing_localhost_and_its_ip_match() {
         assert_eq!(
@@ -224,4 +340,83 @@ mod test {
             NetworkAddr::from_str("127.0.0.1")
         );
     }
+
+    #[test]
+    fn builders_match_the_parser() {
+        assert_eq!(
+            Socket::v4(Ipv4Addr::new(192, 168, 0, 1), 5025),
+            "192.168.0.1:5025".parse().unwrap()
+        );
+        assert_eq!(
+            Socket::v6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 5), 5025),
+            "[2001:db8::5]:5025".parse().unwrap()
+        );
+        assert_eq!(
+            Socket::host("scope-lab-3", 5025).unwrap(),
+            "scope-lab-3:5025".parse().unwrap()
+        );
+        assert!(Socket::host("host_name", 5025).is_err());
+        assert_eq!(
+            Socket::host("Scope", 5025).unwrap(),
+            Socket::new("scope:5025").unwrap()
+        );
+        assert_eq!(
+            Socket::new("Scope-Lab-3:5025").unwrap().to_string(),
+            "scope-lab-3:5025"
+        );
+    }
+
+    #[test]
+    fn visa_and_raw_forms_convert_both_ways() {
+        let raw: Socket = "10.0.0.2:5025".parse().unwrap();
+        let visa: InstAddr = "TCPIP0::10.0.0.2::5025::SOCKET".parse().unwrap();
+        assert_eq!(InstAddr::Visa(raw.to_visa_socket(0)), visa);
+        assert_eq!(visa.to_raw_socket(), Some(raw.clone()));
+        assert!(visa.is_equivalent(&InstAddr::Socket(raw)));
+        assert!(visa.is_equivalent(&"TCPIP1::10.0.0.2::5025::SOCKET".parse().unwrap()));
+        assert!(!visa.is_equivalent(&"10.0.0.2:5026".parse().unwrap()));
+        let mixed_case = InstAddr::Socket(Socket::new("Scope:5025").unwrap());
+        assert!(mixed_case.is_equivalent(&"TCPIP0::Scope::5025::SOCKET".parse().unwrap()));
+        assert!(mixed_case.is_equivalent(&"TCPIP0::SCOPE::5025::SOCKET".parse().unwrap()));
+        let scoped: Socket = "[fe80::1%3]:5025".parse().unwrap();
+        assert_eq!(
+            scoped.to_visa_socket(0).as_str(),
+            "tcpip0::[fe80::1%3]::5025::socket"
+        );
+        assert_eq!(
+            InstAddr::Visa(scoped.to_visa_socket(0)).to_raw_socket(),
+            Some(scoped)
+        );
+    }
+
+    #[test]
+    fn bracketed_ipv6_socket_keeps_its_zone() {
+        let socket = Socket::new("[fe80::1%3]:5025").unwrap();
+        assert_eq!(
+            socket,
+            Socket::ScopedV6 {
+                ip: Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1),
+                zone: "3".to_owned(),
+                port: 5025
+            }
+        );
+        assert_eq!(socket.port(), 5025);
+        assert_eq!(
+            NetworkAddr::from_str("[2001:db8::5]")
+                .unwrap()
+                .resource_host(),
+            "[2001:db8::5]"
+        );
+    }
+
+    #[test]
+    fn zone_names_are_not_resolved_when_parsing() {
+        let socket = Socket::new("[fe80::1%eth7]:5025").unwrap();
+        assert_eq!(socket.to_string(), "[fe80::1%eth7]:5025");
+        assert_eq!(
+            socket.to_visa_socket(0).as_str(),
+            "tcpip0::[fe80::1%eth7]::5025::socket"
+        );
+        assert_eq!(socket.to_string().parse::<Socket>().unwrap(), socket);
+    }
 }
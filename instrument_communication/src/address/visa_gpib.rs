@@ -2,38 +2,155 @@ use crate::address::*;
 
 lazy_static! {
     pub static ref GPIB_ADDRESS_REGEX: Regex =
-        Regex::new(r"^(?i)GPIB(\d*)::(\d+)(?:::\d+)?(?:::INSTR)?$").unwrap();
+        Regex::new(r"^(?i)GPIB(\d*)::(\d+)(?:::(\d+))?(?:::INSTR)?$").unwrap();
 }
-pub fn parse_gpib(captures: regex::Captures) -> Result<InstAddr, String> {
-    let instr_num = captures[2].to_string();
-    if instr_num.len() < 3 && instr_num.cmp(&"31".to_owned()) == std::cmp::Ordering::Less {
-        let board_num = if captures[1].is_empty() {
-            "0".to_owned()
-        } else {
-            captures[1].to_string()
-        };
-        let address = format!("gpib{}::{}::instr", board_num, instr_num);
-        Ok(InstAddr::Visa(VisaAddress {
-            address,
+
+/// The largest primary or secondary address on a GPIB bus.
+pub(crate) const MAX_GPIB_ADDRESS: u8 = 30;
+
+/// A GPIB instrument. Instruments with a secondary address are usually plug-in cards of a
+/// mainframe that listens at the primary address.
+#[derive(Copy, Clone, PartialEq, Debug, Eq, Hash, PartialOrd, Ord)]
+pub struct GpibAddress {
+    pub board: u16,
+    pub primary: u8,
+    pub secondary: Option<u8>,
+}
+
+impl GpibAddress {
+    pub fn new(primary: u8) -> GpibAddress {
+        GpibAddress {
+            board: 0,
+            primary,
+            secondary: None,
+        }
+    }
+}
+
+impl fmt::Display for GpibAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "gpib{}::{}", self.board, self.primary)?;
+        if let Some(secondary) = self.secondary {
+            write!(f, "::{secondary}")?;
+        }
+        f.write_str("::instr")
+    }
+}
+
+impl FromStr for GpibAddress {
+    type Err = AddressError;
+    fn from_str(address: &str) -> Result<Self, Self::Err> {
+        let compact = Compact::new(address);
+        match GPIB_ADDRESS_REGEX.captures(&compact.text) {
+            Some(captures) => gpib_address(captures),
+            None => Err(AddressError::UnknownFormat {
+                expected: GPIB_FORMAT,
+                span: 0..compact.text.len(),
+            }),
+        }
+        .map_err(|e| compact.restore(e))
+    }
+}
+
+impl From<GpibAddress> for VisaAddress {
+    fn from(val: GpibAddress) -> Self {
+        VisaAddress {
+            address: val.to_string(),
             visa_type: VisaType::GPIB,
-        }))
-    } else {
-        Err(format!("Invalid primary GPIB address {}", instr_num))
+        }
     }
 }
 
+impl From<GpibAddress> for InstAddr {
+    fn from(val: GpibAddress) -> Self {
+        InstAddr::Visa(val.into())
+    }
+}
+
+fn gpib_address(captures: regex::Captures) -> Result<GpibAddress, AddressError> {
+    let primary = captures
+        .get(2)
+        .expect("the primary address is not optional");
+    let primary = parse_number(
+        AddressField::GpibPrimary,
+        primary.as_str(),
+        primary.range(),
+        0,
+        MAX_GPIB_ADDRESS,
+    )?;
+    let secondary = match captures.get(3) {
+        Some(secondary) => Some(parse_number(
+            AddressField::GpibSecondary,
+            secondary.as_str(),
+            secondary.range(),
+            0,
+            MAX_GPIB_ADDRESS,
+        )?),
+        None => None,
+    };
+    let board = parse_board(&captures, 1)?;
+    Ok(GpibAddress {
+        board,
+        primary,
+        secondary,
+    })
+}
+
+pub(crate) fn check_gpib_address(primary: u8, secondary: Option<u8>) -> Result<(), AddressError> {
+    let max = MAX_GPIB_ADDRESS.into();
+    check_range(AddressField::GpibPrimary, primary, 0, max)?;
+    match secondary {
+        Some(secondary) => check_range(AddressField::GpibSecondary, secondary, 0, max),
+        None => Ok(()),
+    }
+}
+
+pub fn parse_gpib(captures: regex::Captures) -> Result<InstAddr, AddressError> {
+    gpib_address(captures).map(InstAddr::from)
+}
+
 impl VisaAddress {
+    /// A GPIB instrument. Primary and secondary addresses are at most 30.
+    pub fn gpib(
+        board: u16,
+        primary: u8,
+        secondary: Option<u8>,
+    ) -> Result<VisaAddress, AddressError> {
+        check_gpib_address(primary, secondary)?;
+        Ok(GpibAddress {
+            board,
+            primary,
+            secondary,
+        }
+        .into())
+    }
+
+    /// The typed view of a GPIB address.
+    pub fn as_gpib(&self) -> Option<GpibAddress> {
+        match self.visa_type {
+            VisaType::GPIB => self.address.parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// The interface resource of a GPIB board which is used for bus control.
+    pub(crate) fn gpib_interface(board: u16) -> VisaAddress {
+        VisaAddress {
+            address: format!("gpib{board}::intfc"),
+            visa_type: VisaType::GPIB,
+        }
+    }
+
     #[allow(dead_code)]
     fn gpib_complement_address(&self) -> Option<String> {
         match self.visa_type {
             VisaType::GPIB => {
-                let address_parts: Vec<&str> = self.address.split("::").collect();
+                let mut address_parts: Vec<String> =
+                    self.address.split("::").map(str::to_owned).collect();
                 let mut num = address_parts[1].parse::<i32>().unwrap();
                 num += if num % 2 == 0 { 1 } else { -1 };
-                Some(format!(
-                    "{}::{}::{}",
-                    address_parts[0], num, address_parts[2]
-                ))
+                address_parts[1] = num.to_string();
+                Some(address_parts.join("::"))
             }
             _ => None,
         }
@@ -46,6 +163,7 @@ mod tests {
     use test_case::test_case;
 
     #[test_case("GPIB0::16::INSTR", "GPIB0::17::INSTR")]
+    #[test_case("GPIB0::16::3::INSTR", "GPIB0::17::3::INSTR")]
     #[test_case("GPIB0::15::INSTR", "GPIB0::14::INSTR")]
     #[test_case("GPIB1::22::INSTR", "GPIB1::23::INSTR")]
     fn test_gpib_complement_address(original: &str, expected: &str) {
@@ -77,9 +195,11 @@ mod tests {
     #[test_case("GPIB0: :15::INSTR","gpib0::15::instr";"Spaces between colons.")]
     #[test_case("GPIB0::1 5::INSTR","gpib0::15::instr";"Spaces between numbers.")]
     #[test_case("GPIB0::30::INSTR","gpib0::30::instr";"30 is the maximum number allowed for GPIB instrument.")]
-    #[test_case("GPIB0 :: 1::12:: INSTR ","gpib0::1::instr";"only primary address is used. Secondary address is ignored.")]
+    #[test_case("GPIB0::7::INSTR","gpib0::7::instr";"single digit primary addresses are compared as numbers.")]
+    #[test_case("GPIB0 :: 1::12:: INSTR ","gpib0::1::12::instr";"the secondary address is kept.")]
+    #[test_case("GPIB0::1::0","gpib0::1::0::instr";"0 is the minimum secondary address.")]
     #[test_case("GPIB1::0::INSTR","gpib1::0::instr";"0 is the minimum number allowed for GPIB instrument.")]
-    #[test_case("gpib2 :: 1::12:: insTR ","gpib2::1::instr";"tolerate character cases.")]
+    #[test_case("gpib2 :: 1::12:: insTR ","gpib2::1::12::instr";"tolerate character cases.")]
     #[test_case("GPIB::15::INSTR","GPIB0::15::INSTR";"no GPIB board number provided defaults to 0.")]
     #[test_case("GPIB::13","GPIB0::13::INSTR";"no GPIB board number provided defaults to 0 and no INSTR.")]
     fn test_gpib_parse_valid_address(address: &str, expected: &str) {
@@ -91,7 +211,32 @@ mod tests {
     #[test_case("";"blank address is not valid.")]
     #[test_case("GPIB2 :: 40::12:: INSTR ";"addresses above 30 are not valid.")]
     #[test_case("GPIB2 :: 220::12:: INSTR ";"addresses above 30 are not valid. Here is an example with a number that starts with 2 locations that are less than 30.")]
+    #[test_case("GPIB0::1::31::INSTR";"secondary addresses above 30 are not valid.")]
     fn test_gpib_parse_invalid_address(address: &str) {
         assert!(address.parse::<InstAddr>().is_err());
     }
+
+    #[test]
+    fn typed_view_keeps_the_secondary_address() {
+        let InstAddr::Visa(address) = "GPIB1::9::2::INSTR".parse::<InstAddr>().unwrap() else {
+            panic!("GPIB addresses are visa addresses");
+        };
+        let gpib = address.as_gpib().unwrap();
+        assert_eq!((gpib.board, gpib.primary, gpib.secondary), (1, 9, Some(2)));
+        assert_eq!(VisaAddress::from(gpib), address);
+        assert_eq!(GpibAddress::new(7).to_string(), "gpib0::7::instr");
+    }
+
+    #[test]
+    fn builder_matches_the_parser() {
+        let built = VisaAddress::gpib(1, 9, Some(2)).unwrap();
+        assert_eq!(
+            InstAddr::from(built.clone()),
+            "GPIB1::9::2::INSTR".parse().unwrap()
+        );
+        assert_eq!((built.board(), built.primary_address()), (Some(1), Some(9)));
+        assert_eq!(built.secondary_address(), Some(2));
+        let err = VisaAddress::gpib(0, 40, None).unwrap_err();
+        assert_eq!(err.to_string(), "GPIB primary address 40 out of range 0-30");
+    }
 }
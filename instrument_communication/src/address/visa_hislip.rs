@@ -0,0 +1,92 @@
+use super::host::{resource_host, HOST_PATTERN};
+use super::socket::NetworkAddr;
+use crate::address::*;
+
+lazy_static! {
+    pub static ref VISAHISLIP_ADDRESS_REGEX: Regex = Regex::new(&format!(
+        r"^(?i)TCPIP(\d*)::({})::(hislip\d+)(?:,(\d+))?(?:::INSTR)?$",
+        *HOST_PATTERN
+    ))
+    .unwrap();
+}
+
+impl VisaAddress {
+    /// A HiSLIP instrument such as `hislip0`. The default port 4880 is used when `port` is `None`.
+    pub fn hislip(
+        board: u16,
+        host: impl AsRef<str>,
+        index: u16,
+        port: Option<u16>,
+    ) -> Result<VisaAddress, AddressError> {
+        let ip_or_host = resource_host(host.as_ref())?;
+        let device = LanDeviceName::Hislip { index, port };
+        Ok(VisaAddress {
+            address: format!(
+                "tcpip{}::{}::{}::instr",
+                board,
+                ip_or_host.resource_host(),
+                device
+            ),
+            visa_type: VisaType::Hislip,
+        })
+    }
+}
+
+pub fn parse_visa_hislip(captures: regex::Captures) -> Result<InstAddr, AddressError> {
+    let board_num = if captures[1].is_empty() {
+        "0".to_owned()
+    } else {
+        captures[1].to_string()
+    };
+    let host = captures.get(2).expect("the host is not optional");
+    let ip_or_host = NetworkAddr::from_str(host.as_str()).map_err(|e| e.offset(host.start()))?;
+    let port = match captures.get(4) {
+        Some(port) => {
+            let port = parse_number(AddressField::Port, port.as_str(), port.range(), 0, u16::MAX)?;
+            format!(",{port}")
+        }
+        None => String::new(),
+    };
+    Ok(InstAddr::Visa(VisaAddress {
+        address: format!(
+            "tcpip{}::{}::{}{}::instr",
+            board_num,
+            ip_or_host.resource_host(),
+            &captures[3],
+            port
+        ),
+        visa_type: VisaType::Hislip,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("TCPIP0::192.168.0.1::hislip0::INSTR","tcpip0::192.168.0.1::hislip0::instr";"full address")]
+    #[test_case("TCPIP :: 192.168.0.1 :: HiSLIP1","tcpip0::192.168.0.1::hislip1::instr";"tolerate missing board number and suffix")]
+    #[test_case("TCPIP2::localhost::hislip0,4881::INSTR","tcpip2::127.0.0.1::hislip0,4881::instr";"explicit port")]
+    #[test_case("TCPIP0::[2001:db8::5]::hislip0::INSTR","tcpip0::[2001:db8::5]::hislip0::instr";"IPv6")]
+    fn test_visa_hislip_valid_address(address: &str, expected: &str) {
+        let inst_address = address.parse::<InstAddr>().unwrap();
+        assert_eq!(inst_address.address(), expected);
+        let InstAddr::Visa(visa_address) = inst_address else {
+            panic!("{address} should be a visa address");
+        };
+        assert_eq!(visa_address.get_type(), VisaType::Hislip);
+    }
+
+    #[test_case("TCPIP0::192.168.0.1::hislip0,65536::INSTR";"port out of range")]
+    #[test_case("TCPIP0::192.168.0.1::hislip::INSTR";"missing sub address number")]
+    fn test_visa_hislip_invalid_address(address: &str) {
+        let inst_address = address.parse::<InstAddr>();
+        assert!(!matches!(
+            inst_address,
+            Ok(InstAddr::Visa(VisaAddress {
+                visa_type: VisaType::Hislip,
+                ..
+            }))
+        ));
+    }
+}
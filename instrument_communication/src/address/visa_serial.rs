@@ -0,0 +1,290 @@
+use crate::address::*;
+
+lazy_static! {
+    pub static ref SERIAL_ADDRESS_REGEX: Regex =
+        Regex::new(r"^(?i)ASRL([^:]+)(?:::INSTR)?(?:::([^:]+))?$").unwrap();
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Eq, Hash, PartialOrd, Ord, Default)]
+pub enum Parity {
+    #[default]
+    None,
+    Odd,
+    Even,
+    Mark,
+    Space,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Eq, Hash, PartialOrd, Ord, Default)]
+pub enum StopBits {
+    #[default]
+    One,
+    OneAndHalf,
+    Two,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Eq, Hash, PartialOrd, Ord, Default)]
+pub enum FlowControl {
+    #[default]
+    None,
+    XonXoff,
+    RtsCts,
+    DtrDsr,
+}
+
+/// The line settings of a serial port. They are written after the resource string as
+/// `<baud>,<data bits><parity><stop bits>[,<flow control>]`, e.g. `ASRL3::INSTR::115200,8N1,RTSCTS`.
+/// Addresses without settings use 9600 baud, 8N1 and no flow control.
+#[derive(Copy, Clone, PartialEq, Debug, Eq, Hash, PartialOrd, Ord)]
+pub struct SerialSettings {
+    pub baud_rate: u32,
+    pub data_bits: u8,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+    pub flow_control: FlowControl,
+}
+
+impl Default for SerialSettings {
+    fn default() -> Self {
+        SerialSettings {
+            baud_rate: 9600,
+            data_bits: 8,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+            flow_control: FlowControl::None,
+        }
+    }
+}
+
+impl fmt::Display for SerialSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parity = match self.parity {
+            Parity::None => 'n',
+            Parity::Odd => 'o',
+            Parity::Even => 'e',
+            Parity::Mark => 'm',
+            Parity::Space => 's',
+        };
+        let stop_bits = match self.stop_bits {
+            StopBits::One => "1",
+            StopBits::OneAndHalf => "1.5",
+            StopBits::Two => "2",
+        };
+        let flow_control = match self.flow_control {
+            FlowControl::None => "none",
+            FlowControl::XonXoff => "xonxoff",
+            FlowControl::RtsCts => "rtscts",
+            FlowControl::DtrDsr => "dtrdsr",
+        };
+        write!(
+            f,
+            "{},{}{parity}{stop_bits},{flow_control}",
+            self.baud_rate, self.data_bits
+        )
+    }
+}
+
+impl FromStr for SerialSettings {
+    type Err = AddressError;
+    fn from_str(original: &str) -> Result<Self, Self::Err> {
+        let settings = original.to_ascii_lowercase();
+        let invalid = || AddressError::InvalidSerialSettings {
+            settings: original.to_owned(),
+            span: 0..original.len(),
+        };
+        let mut parts = settings.split(',');
+        let baud_rate = parts
+            .next()
+            .and_then(|baud| baud.parse().ok())
+            .ok_or_else(invalid)?;
+        let frame = parts.next().ok_or_else(invalid)?;
+        let mut chars = frame.chars();
+        let data_bits = chars
+            .next()
+            .and_then(|bits| bits.to_digit(10))
+            .filter(|bits| (5..=8).contains(bits))
+            .ok_or_else(invalid)? as u8;
+        let parity = match chars.next() {
+            Some('n') => Parity::None,
+            Some('o') => Parity::Odd,
+            Some('e') => Parity::Even,
+            Some('m') => Parity::Mark,
+            Some('s') => Parity::Space,
+            _ => Err(invalid())?,
+        };
+        let stop_bits = match chars.as_str() {
+            "1" => StopBits::One,
+            "1.5" => StopBits::OneAndHalf,
+            "2" => StopBits::Two,
+            _ => Err(invalid())?,
+        };
+        let flow_control = match parts.next() {
+            None | Some("none") => FlowControl::None,
+            Some("xonxoff") => FlowControl::XonXoff,
+            Some("rtscts") => FlowControl::RtsCts,
+            Some("dtrdsr") => FlowControl::DtrDsr,
+            Some(_) => Err(invalid())?,
+        };
+        if parts.next().is_some() {
+            Err(invalid())?
+        }
+        Ok(SerialSettings {
+            baud_rate,
+            data_bits,
+            parity,
+            stop_bits,
+            flow_control,
+        })
+    }
+}
+
+/// The port of a serial address. VISA numbers ports from 1 while a path names the device file.
+#[derive(Clone, PartialEq, Debug, Eq, Hash, PartialOrd, Ord)]
+pub enum SerialPort {
+    Number(u16),
+    Path(String),
+}
+
+impl SerialPort {
+    /// The device file of the port. Numbered ports map to `/dev/ttyS<number - 1>` the way
+    /// `ASRL1` is `COM1` on Windows.
+    pub fn device_path(&self) -> String {
+        match self {
+            SerialPort::Number(number) => format!("/dev/ttyS{}", number.saturating_sub(1)),
+            SerialPort::Path(path) => path.clone(),
+        }
+    }
+}
+
+impl fmt::Display for SerialPort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SerialPort::Number(number) => number.fmt(f),
+            SerialPort::Path(path) => f.write_str(path),
+        }
+    }
+}
+
+/// A serial instrument. Paths keep their case since device files are case sensitive.
+#[derive(Clone, PartialEq, Debug, Eq, Hash, PartialOrd, Ord)]
+pub struct SerialAddress {
+    pub port: SerialPort,
+    pub settings: SerialSettings,
+}
+
+impl fmt::Display for SerialAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "asrl{}::instr", self.port)?;
+        if self.settings != SerialSettings::default() {
+            write!(f, "::{}", self.settings)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for SerialAddress {
+    type Err = AddressError;
+    fn from_str(address: &str) -> Result<Self, Self::Err> {
+        let compact = Compact::new(address);
+        match SERIAL_ADDRESS_REGEX.captures(&compact.text) {
+            Some(captures) => serial_address(captures),
+            None => Err(AddressError::UnknownFormat {
+                expected: SERIAL_FORMAT,
+                span: 0..compact.text.len(),
+            }),
+        }
+        .map_err(|e| compact.restore(e))
+    }
+}
+
+impl From<SerialAddress> for VisaAddress {
+    fn from(val: SerialAddress) -> Self {
+        VisaAddress {
+            address: val.to_string(),
+            visa_type: VisaType::Serial,
+        }
+    }
+}
+
+impl From<SerialAddress> for InstAddr {
+    fn from(val: SerialAddress) -> Self {
+        InstAddr::Visa(val.into())
+    }
+}
+
+impl VisaAddress {
+    /// The typed view of a serial address.
+    pub fn as_serial(&self) -> Option<SerialAddress> {
+        match self.visa_type {
+            VisaType::Serial => self.address.parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+fn serial_address(captures: regex::Captures) -> Result<SerialAddress, AddressError> {
+    let port = captures.get(1).expect("the port is not optional");
+    let port = if port.as_str().starts_with('/') {
+        SerialPort::Path(port.as_str().to_owned())
+    } else {
+        SerialPort::Number(parse_number(
+            AddressField::SerialPort,
+            port.as_str(),
+            port.range(),
+            1,
+            u16::MAX,
+        )?)
+    };
+    let settings = match captures.get(2) {
+        Some(settings) => settings
+            .as_str()
+            .parse()
+            .map_err(|e: AddressError| e.offset(settings.start()))?,
+        None => SerialSettings::default(),
+    };
+    Ok(SerialAddress { port, settings })
+}
+
+pub fn parse_serial(captures: regex::Captures) -> Result<InstAddr, AddressError> {
+    serial_address(captures).map(InstAddr::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("ASRL3::INSTR","asrl3::instr";"numbered port")]
+    #[test_case("asrl/dev/ttyUSB0","asrl/dev/ttyUSB0::instr";"path keeps its case")]
+    #[test_case("ASRL1::INSTR::9600,8N1,NONE","asrl1::instr";"default settings are omitted")]
+    #[test_case("ASRL/dev/ttyS1::INSTR::115200,7E2,RTSCTS","asrl/dev/ttyS1::instr::115200,7e2,rtscts";"settings")]
+    #[test_case("ASRL2::INSTR::19200,8O1.5","asrl2::instr::19200,8o1.5,none";"flow control is optional")]
+    fn test_serial_valid_address(address: &str, expected: &str) {
+        let inst_address = address.parse::<InstAddr>().unwrap();
+        assert_eq!(inst_address.address(), expected);
+        assert_eq!(
+            inst_address.address().parse::<InstAddr>().unwrap(),
+            inst_address
+        );
+    }
+
+    #[test_case("ASRL0::INSTR";"ports start at one")]
+    #[test_case("ASRL1::INSTR::9600,9N1";"too many data bits")]
+    #[test_case("ASRL1::INSTR::9600,8X1";"unknown parity")]
+    #[test_case("ASRL1::INSTR::9600,8N1,CTS";"unknown flow control")]
+    fn test_serial_invalid_address(address: &str) {
+        assert!(address.parse::<InstAddr>().is_err());
+    }
+
+    #[test]
+    fn settings_are_not_part_of_the_visa_resource() {
+        let InstAddr::Visa(address) = "ASRL1::INSTR::115200,8N1".parse::<InstAddr>().unwrap()
+        else {
+            panic!("serial addresses are visa addresses");
+        };
+        assert_eq!(address.as_str(), "asrl1::instr");
+        let serial = address.as_serial().unwrap();
+        assert_eq!(serial.port.device_path(), "/dev/ttyS0");
+        assert_eq!(serial.settings.baud_rate, 115200);
+    }
+}
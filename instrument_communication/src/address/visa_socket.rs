@@ -1,25 +1,52 @@
+use super::host::{resource_host, HOST_PATTERN};
+use super::socket::NetworkAddr;
 use crate::address::*;
 
 lazy_static! {
-    pub static ref VISASOCKET_ADDRESS_REGEX: Regex =
-        Regex::new(r"^(?i)TCPIP(\d*)::([a-z0-9\.]+)::(\d+)::SOCKET$").unwrap();
+    pub static ref VISASOCKET_ADDRESS_REGEX: Regex = Regex::new(&format!(
+        r"^(?i)TCPIP(\d*)::({})::(\d+)::SOCKET$",
+        *HOST_PATTERN
+    ))
+    .unwrap();
 }
 
-pub fn parse_visa_socket(captures: regex::Captures) -> Result<InstAddr, String> {
+impl VisaAddress {
+    /// A raw TCP socket that is opened through VISA.
+    pub fn tcpip_socket(
+        board: u16,
+        host: impl AsRef<str>,
+        port: u16,
+    ) -> Result<VisaAddress, AddressError> {
+        let ip_or_host = resource_host(host.as_ref())?;
+        Ok(VisaAddress {
+            address: format!(
+                "tcpip{}::{}::{}::socket",
+                board,
+                ip_or_host.resource_host(),
+                port
+            ),
+            visa_type: VisaType::Socket,
+        })
+    }
+}
+
+pub fn parse_visa_socket(captures: regex::Captures) -> Result<InstAddr, AddressError> {
     let board_num = captures[1].to_string();
     let board_num = if board_num.is_empty() {
         "0".into()
     } else {
         board_num
     };
-    let host_ip = captures[2].to_string();
-    let socket: Socket = format!("{}:{}", host_ip, &captures[3]).parse()?;
+    let host = captures.get(2).expect("the host is not optional");
+    let ip_or_host = NetworkAddr::from_str(host.as_str()).map_err(|e| e.offset(host.start()))?;
+    let port = captures.get(3).expect("the port is not optional");
+    let port = parse_number(AddressField::Port, port.as_str(), port.range(), 0, u16::MAX)?;
     Ok(InstAddr::Visa(VisaAddress {
         address: format!(
             "tcpip{}::{}::{}::socket",
             board_num,
-            socket.ip_or_host(),
-            socket.port()
+            ip_or_host.resource_host(),
+            port
         ),
         visa_type: VisaType::Socket,
     }))
@@ -31,6 +58,10 @@ mod tests {
     use test_case::test_case;
     #[test_case("TCPIP0 :: 192.168.0.1::5025:: SockEt ","TCPIP0::192.168.0.1::5025::socket";"tolerate character cases for socket.")]
     #[test_case("TCPIP :: 192.168.0.1::5025:: SockEt ","TCPIP0::192.168.0.1::5025::socket";"tolerate missing board number")]
+    #[test_case("TCPIP0::scope-lab-3::5025::SOCKET","tcpip0::scope-lab-3::5025::socket";"hyphenated host name")]
+    #[test_case("TCPIP::[fe80::1%eth0]::5025::SOCKET","tcpip0::[fe80::1%eth0]::5025::socket";"IPv6 with zone")]
+    #[test_case("TCPIP0::[2001:DB8::5]::5025::SOCKET","tcpip0::[2001:db8::5]::5025::socket";"IPv6")]
+    #[test_case("TCPIP0::oscilloscope.bücher.example::5025::SOCKET","tcpip0::oscilloscope.xn--bcher-kva.example::5025::socket";"internationalized host name")]
     fn test_visa_socket_valid_address(address: &str, expected: &str) {
         let inst_address = address.parse::<InstAddr>();
         assert!(inst_address.is_ok());
@@ -40,6 +71,22 @@ mod tests {
             .eq_ignore_ascii_case(expected));
     }
 
+    #[test]
+    fn builder_matches_the_parser() {
+        let built = VisaAddress::tcpip_socket(0, "fe80::1%eth0", 5025).unwrap();
+        assert_eq!(
+            InstAddr::from(built.clone()),
+            "TCPIP::[fe80::1%eth0]::5025::SOCKET".parse().unwrap()
+        );
+        assert_eq!(built.host().unwrap().to_string(), "fe80::1%eth0");
+        assert_eq!((built.board(), built.port()), (Some(0), Some(5025)));
+        assert!(VisaAddress::tcpip_socket(0, "host_name", 5025).is_err());
+        assert_eq!(
+            InstAddr::from(VisaAddress::tcpip_socket(0, "SCOPE-LAB-3", 5025).unwrap()),
+            "TCPIP0::SCOPE-LAB-3::5025::SOCKET".parse().unwrap()
+        );
+    }
+
     #[test_case("TCPIP0 :: 256.168.0.1::5025:: SockEt ";"Invalid IP Address is interpreted as Host name as raw socket address")]
     fn test_visa_socket_invalid_address_is_a_valid_host_name(address: &str) {
         let inst_address = address.parse::<InstAddr>();
@@ -0,0 +1,169 @@
+use crate::address::*;
+
+lazy_static! {
+    pub static ref USB_ADDRESS_REGEX: Regex = Regex::new(
+        r"^(?i)USB(\d*)::(0x[0-9a-f]+|\d+)::(0x[0-9a-f]+|\d+)::([^:]+)(?:::(\d+))?(?:::INSTR)?$"
+    )
+    .unwrap();
+}
+
+/// A USBTMC instrument. The serial number keeps its case since some VISA implementations
+/// compare it exactly with the one reported by the device.
+#[derive(Clone, PartialEq, Debug, Eq, Hash, PartialOrd, Ord)]
+pub struct UsbAddress {
+    pub board: u16,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub serial_number: String,
+    /// The USBTMC interface. VISA picks the first one when it is `None`.
+    pub interface: Option<u16>,
+}
+
+impl UsbAddress {
+    pub fn new(vendor_id: u16, product_id: u16, serial_number: impl Into<String>) -> UsbAddress {
+        UsbAddress {
+            board: 0,
+            vendor_id,
+            product_id,
+            serial_number: serial_number.into(),
+            interface: None,
+        }
+    }
+
+    /// True if the instrument has the serial number. The case is ignored.
+    pub fn matches_serial(&self, serial_number: &str) -> bool {
+        self.serial_number
+            .eq_ignore_ascii_case(serial_number.trim())
+    }
+}
+
+impl fmt::Display for UsbAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "usb{}::0x{:04x}::0x{:04x}::{}",
+            self.board, self.vendor_id, self.product_id, self.serial_number
+        )?;
+        if let Some(interface) = self.interface {
+            write!(f, "::{interface}")?;
+        }
+        f.write_str("::instr")
+    }
+}
+
+impl FromStr for UsbAddress {
+    type Err = AddressError;
+    fn from_str(address: &str) -> Result<Self, Self::Err> {
+        let compact = Compact::new(address);
+        match USB_ADDRESS_REGEX.captures(&compact.text) {
+            Some(captures) => usb_address(captures),
+            None => Err(AddressError::UnknownFormat {
+                expected: USB_FORMAT,
+                span: 0..compact.text.len(),
+            }),
+        }
+        .map_err(|e| compact.restore(e))
+    }
+}
+
+impl From<UsbAddress> for VisaAddress {
+    fn from(val: UsbAddress) -> Self {
+        VisaAddress {
+            address: val.to_string(),
+            visa_type: VisaType::USB,
+        }
+    }
+}
+
+impl From<UsbAddress> for InstAddr {
+    fn from(val: UsbAddress) -> Self {
+        InstAddr::Visa(val.into())
+    }
+}
+
+impl VisaAddress {
+    /// The typed view of a USB address.
+    pub fn as_usb(&self) -> Option<UsbAddress> {
+        match self.visa_type {
+            VisaType::USB => self.address.parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+/// Parses a vendor or product ID written in hex with a `0x` prefix or in decimal.
+fn parse_id(field: AddressField, id: regex::Match) -> Result<u16, AddressError> {
+    let text = id.as_str();
+    match text.get(..2) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("0x") => u16::from_str_radix(&text[2..], 16)
+            .map_err(|_| AddressError::OutOfRange {
+                field,
+                value: text.to_owned(),
+                min: 0,
+                max: u16::MAX.into(),
+                span: id.range(),
+            }),
+        _ => parse_number(field, text, id.range(), 0, u16::MAX),
+    }
+}
+
+fn usb_address(captures: regex::Captures) -> Result<UsbAddress, AddressError> {
+    let interface = match captures.get(5) {
+        Some(interface) => Some(parse_number(
+            AddressField::UsbInterface,
+            interface.as_str(),
+            interface.range(),
+            0,
+            u16::MAX,
+        )?),
+        None => None,
+    };
+    Ok(UsbAddress {
+        board: parse_board(&captures, 1)?,
+        vendor_id: parse_id(AddressField::VendorId, captures.get(2).unwrap())?,
+        product_id: parse_id(AddressField::ProductId, captures.get(3).unwrap())?,
+        serial_number: captures[4].to_owned(),
+        interface,
+    })
+}
+
+pub fn parse_usb(captures: regex::Captures) -> Result<InstAddr, AddressError> {
+    usb_address(captures).map(InstAddr::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("USB0::0x2A8D::0x0101::MY12345678::0::INSTR","usb0::0x2a8d::0x0101::MY12345678::0::instr";"full address")]
+    #[test_case("usb::10893::257::MY12345678","usb0::0x2a8d::0x0101::MY12345678::instr";"decimal IDs and missing board and interface")]
+    #[test_case("USB1 :: 0x0957 :: 0x1796 :: MY5 :: 2 :: INSTR","usb1::0x0957::0x1796::MY5::2::instr";"tolerate whitespace")]
+    fn test_usb_valid_address(address: &str, expected: &str) {
+        let inst_address = address.parse::<InstAddr>().unwrap();
+        assert_eq!(inst_address.address(), expected);
+    }
+
+    #[test_case("USB0::0x12345::0x0101::MY1::INSTR";"vendor ID out of range")]
+    #[test_case("USB0::70000::0x0101::MY1::INSTR";"decimal ID out of range")]
+    #[test_case("USB0::0x2A8D::0x0101::MY1::70000::INSTR";"interface out of range")]
+    fn test_usb_invalid_address(address: &str) {
+        assert!(address.parse::<InstAddr>().is_err());
+    }
+
+    #[test]
+    fn typed_view_and_serial_matching() {
+        let InstAddr::Visa(address) = "USB0::0x2A8D::0x0101::MY12345678::0::INSTR"
+            .parse::<InstAddr>()
+            .unwrap()
+        else {
+            panic!("USB addresses are visa addresses");
+        };
+        let usb = address.as_usb().unwrap();
+        assert_eq!((usb.vendor_id, usb.product_id), (0x2A8D, 0x0101));
+        assert_eq!(usb.interface, Some(0));
+        assert!(usb.matches_serial("my12345678"));
+        assert!(!usb.matches_serial("MY1234567"));
+        assert_eq!(VisaAddress::from(usb), address);
+    }
+}
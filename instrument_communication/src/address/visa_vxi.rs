@@ -1,22 +1,183 @@
+use super::host::{resource_host, HOST_PATTERN};
 use super::socket::NetworkAddr;
+use super::visa_gpib::MAX_GPIB_ADDRESS;
 use crate::address::*;
 
 lazy_static! {
-    pub static ref VISAVXI11_ADDRESS_REGEX: Regex =
-     Regex::new(r"^(?i)TCPIP(\d*)::((?:[0-9]{1,3}\.){3}[0-9]{1,3}|(?:(?:[a-z]|[a-z][a-z0-9\-]*[a-z0-9])\.)*(?:[a-z]|[a-z][a-z0-9\-]*[a-z0-9]))(?:::INSTR)?$").unwrap();
+    pub static ref VISAVXI11_ADDRESS_REGEX: Regex = Regex::new(&format!(
+        r"^(?i)TCPIP(\d*)::({})(?:::(inst\d+|gpib\d*,\d+(?:,\d+)?))?(?:::INSTR)?$",
+        *HOST_PATTERN
+    ))
+    .unwrap();
+    static ref LAN_DEVICE_NAME_REGEX: Regex =
+        Regex::new(r"^(?i)(?:inst(\d+)|gpib(\d*),(\d+)(?:,(\d+))?|hislip(\d+)(?:,(\d+))?)$")
+            .unwrap();
 }
 
-pub fn parse_visa_vxi11(captures: regex::Captures) -> Result<InstAddr, String> {
+/// The device name of a LAN instrument. A host can serve several instruments, e.g. the
+/// channels of an LXI box or the GPIB instruments behind a LAN/GPIB gateway.
+#[derive(Copy, Clone, PartialEq, Debug, Eq, Hash, PartialOrd, Ord)]
+pub enum LanDeviceName {
+    /// A VXI-11 instrument of the host. Addresses without a device name use `inst0`.
+    Inst(u16),
+    /// A GPIB instrument behind a VXI-11 gateway.
+    Gpib {
+        board: u16,
+        primary: u8,
+        secondary: Option<u8>,
+    },
+    /// A HiSLIP sub address with an optional port.
+    Hislip { index: u16, port: Option<u16> },
+}
+
+impl fmt::Display for LanDeviceName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LanDeviceName::Inst(index) => write!(f, "inst{index}"),
+            LanDeviceName::Gpib {
+                board,
+                primary,
+                secondary,
+            } => {
+                write!(f, "gpib{board},{primary}")?;
+                match secondary {
+                    Some(secondary) => write!(f, ",{secondary}"),
+                    None => Ok(()),
+                }
+            }
+            LanDeviceName::Hislip { index, port } => {
+                write!(f, "hislip{index}")?;
+                match port {
+                    Some(port) => write!(f, ",{port}"),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for LanDeviceName {
+    type Err = AddressError;
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let invalid = || AddressError::InvalidDeviceName {
+            name: name.to_owned(),
+            span: 0..name.len(),
+        };
+        let captures = LAN_DEVICE_NAME_REGEX.captures(name).ok_or_else(invalid)?;
+        let number = |index: usize, field: AddressField| -> Result<Option<u16>, AddressError> {
+            match captures.get(index) {
+                Some(number) if number.as_str().is_empty() => Ok(Some(0)),
+                Some(number) => {
+                    parse_number(field, number.as_str(), number.range(), 0, u16::MAX).map(Some)
+                }
+                None => Ok(None),
+            }
+        };
+        let gpib_address =
+            |index: usize, field: AddressField| -> Result<Option<u8>, AddressError> {
+                match captures.get(index) {
+                    Some(address) => parse_number(
+                        field,
+                        address.as_str(),
+                        address.range(),
+                        0,
+                        MAX_GPIB_ADDRESS,
+                    )
+                    .map(Some),
+                    None => Ok(None),
+                }
+            };
+        if let Some(index) = number(1, AddressField::LanDevice)? {
+            Ok(LanDeviceName::Inst(index))
+        } else if let Some(board) = number(2, AddressField::Board)? {
+            Ok(LanDeviceName::Gpib {
+                board,
+                primary: gpib_address(3, AddressField::GpibPrimary)?.ok_or_else(invalid)?,
+                secondary: gpib_address(4, AddressField::GpibSecondary)?,
+            })
+        } else {
+            Ok(LanDeviceName::Hislip {
+                index: number(5, AddressField::LanDevice)?.ok_or_else(invalid)?,
+                port: number(6, AddressField::Port)?,
+            })
+        }
+    }
+}
+
+impl VisaAddress {
+    /// The device name of a VXI-11 or HiSLIP address. VXI-11 addresses without one return
+    /// `None` and are served by `inst0`.
+    pub fn lan_device_name(&self) -> Option<LanDeviceName> {
+        match self.visa_type {
+            VisaType::VXI | VisaType::Hislip => match self.parts().as_slice() {
+                [_, _, name, _] => name.parse().ok(),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+impl VisaAddress {
+    /// A VXI-11 instrument. Addresses without a device name refer to `inst0`. HiSLIP names are
+    /// rejected, see [`VisaAddress::hislip`].
+    pub fn vxi11(
+        board: u16,
+        host: impl AsRef<str>,
+        device: Option<LanDeviceName>,
+    ) -> Result<VisaAddress, AddressError> {
+        let ip_or_host = resource_host(host.as_ref())?;
+        match device {
+            Some(LanDeviceName::Gpib {
+                primary, secondary, ..
+            }) => check_gpib_address(primary, secondary)?,
+            Some(name @ LanDeviceName::Hislip { .. }) => {
+                return Err(AddressError::InvalidDeviceName {
+                    name: name.to_string(),
+                    span: 0..0,
+                })
+            }
+            _ => (),
+        }
+        let device_name = device.map_or_else(String::new, |name| format!("::{name}"));
+        Ok(VisaAddress {
+            address: format!(
+                "tcpip{}::{}{}::instr",
+                board,
+                ip_or_host.resource_host(),
+                device_name
+            ),
+            visa_type: VisaType::VXI,
+        })
+    }
+}
+
+pub fn parse_visa_vxi11(captures: regex::Captures) -> Result<InstAddr, AddressError> {
     let board_num = captures[1].to_string();
     let board_num = if board_num.is_empty() {
         "0".to_owned()
     } else {
         board_num
     };
-    let host_ip = captures[2].to_string();
-    let ip_or_host = NetworkAddr::from_str(&host_ip)?;
+    let host = captures.get(2).expect("the host is not optional");
+    let ip_or_host = NetworkAddr::from_str(host.as_str()).map_err(|e| e.offset(host.start()))?;
+    let device_name = match captures.get(3) {
+        Some(name) => {
+            let parsed: LanDeviceName = name
+                .as_str()
+                .parse()
+                .map_err(|e: AddressError| e.offset(name.start()))?;
+            format!("::{parsed}")
+        }
+        None => String::new(),
+    };
     Ok(InstAddr::Visa(VisaAddress {
-        address: format!("tcpip{}::{}::instr", board_num, ip_or_host),
+        address: format!(
+            "tcpip{}::{}{}::instr",
+            board_num,
+            ip_or_host.resource_host(),
+            device_name
+        ),
         visa_type: VisaType::VXI,
     }))
 }
@@ -27,8 +188,58 @@ mod tests {
     use crate::address::socket::LOCAL_MACHINE;
     use test_case::test_case;
 
+    #[test]
+    fn builders_match_the_parser() {
+        let gateway = LanDeviceName::Gpib {
+            board: 0,
+            primary: 7,
+            secondary: None,
+        };
+        let built = VisaAddress::vxi11(0, "e5810", Some(gateway)).unwrap();
+        assert_eq!(
+            InstAddr::from(built.clone()),
+            "TCPIP::e5810::gpib,7".parse().unwrap()
+        );
+        assert_eq!(built.primary_address(), Some(7));
+        assert_eq!(built.host(), Some(NetworkAddr::RAW("e5810".into())));
+        assert_eq!(
+            InstAddr::from(VisaAddress::vxi11(2, "10.0.0.5", None).unwrap()),
+            "TCPIP2::10.0.0.5::INSTR".parse().unwrap()
+        );
+        let hislip = VisaAddress::hislip(0, "10.0.0.5", 1, Some(4881)).unwrap();
+        assert_eq!(
+            InstAddr::from(hislip.clone()),
+            "TCPIP0::10.0.0.5::hislip1,4881::INSTR".parse().unwrap()
+        );
+        assert_eq!(hislip.port(), Some(4881));
+        let name = LanDeviceName::Hislip {
+            index: 0,
+            port: None,
+        };
+        assert!(VisaAddress::vxi11(0, "10.0.0.5", Some(name)).is_err());
+    }
+
+    #[test]
+    fn builders_lowercase_the_host_like_the_parser() {
+        let built = VisaAddress::vxi11(0, "E5810", None).unwrap();
+        assert_eq!(built.as_str(), "tcpip0::e5810::instr");
+        assert_eq!(
+            InstAddr::from(built),
+            "TCPIP0::E5810::INSTR".parse().unwrap()
+        );
+        assert_eq!(
+            InstAddr::from(VisaAddress::hislip(0, "FE80::1%ETH0", 0, None).unwrap()),
+            "TCPIP0::[FE80::1%ETH0]::hislip0::INSTR".parse().unwrap()
+        );
+    }
+
     #[test_case("TCPIP0 :: 192.168.0.1:: insTR ","TCPIP0::192.168.0.1::instr";"tolerate character cases.")]
     #[test_case("TCPIP::192.168.0.1::INSTR ","tcpip0::192.168.0.1::instr";"tolerate missing board number.")]
+    #[test_case("TCPIP0::10.0.0.5::inst1::INSTR","tcpip0::10.0.0.5::inst1::instr";"device name of a multi-channel instrument.")]
+    #[test_case("TCPIP0::10.0.0.5::INST0","tcpip0::10.0.0.5::inst0::instr";"an explicit inst0 is preserved.")]
+    #[test_case("TCPIP0::e5810::gpib0,7::INSTR","tcpip0::e5810::gpib0,7::instr";"GPIB instrument behind a gateway.")]
+    #[test_case("TCPIP0::e5810::GPIB,7,2","tcpip0::e5810::gpib0,7,2::instr";"gateway GPIB board defaults to 0 and the secondary address is kept.")]
+    #[test_case("TCPIP0::[fe80::1%eth0]::inst1::INSTR","tcpip0::[fe80::1%eth0]::inst1::instr";"IPv6 with zone and device name.")]
     fn test_visa_vxi11_valid_address(address: &str, expected: &str) {
         let inst_address = address.parse::<InstAddr>();
         assert!(inst_address.is_ok());
@@ -38,6 +249,25 @@ mod tests {
             .eq_ignore_ascii_case(expected));
     }
 
+    #[test_case("TCPIP0::e5810::gpib0,31::INSTR";"primary address above 30.")]
+    #[test_case("TCPIP0::e5810::gpib0,7,31::INSTR";"secondary address above 30.")]
+    #[test_case("TCPIP0::10.0.0.5::inst::INSTR";"missing instrument number.")]
+    #[test_case("TCPIP0::10.0.0.5::inst70000::INSTR";"instrument number out of range.")]
+    fn test_visa_vxi11_invalid_device_name(address: &str) {
+        assert!(address.parse::<InstAddr>().is_err());
+    }
+
+    #[test_case("TCPIP0::10.0.0.5::INSTR", None)]
+    #[test_case("TCPIP0::10.0.0.5::inst1::INSTR", Some(LanDeviceName::Inst(1)))]
+    #[test_case("TCPIP0::e5810::gpib1,7,2::INSTR",Some(LanDeviceName::Gpib { board: 1, primary: 7, secondary: Some(2) }))]
+    #[test_case("TCPIP0::10.0.0.5::hislip0,4881::INSTR",Some(LanDeviceName::Hislip { index: 0, port: Some(4881) }))]
+    fn test_lan_device_name(address: &str, expected: Option<LanDeviceName>) {
+        let InstAddr::Visa(address) = address.parse::<InstAddr>().unwrap() else {
+            panic!("{address} should be a visa address");
+        };
+        assert_eq!(address.lan_device_name(), expected);
+    }
+
     #[test]
     fn test_machine_name_is_local_host() {
         let inst_address = format!("TCPIP::{}::INSTR", LOCAL_MACHINE.as_str()).parse::<InstAddr>();
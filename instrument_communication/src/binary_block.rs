@@ -0,0 +1,274 @@
+//! IEEE 488.2 arbitrary block data as used for waveforms, screenshots and traces. A definite
+//! length block is `#<n><length><data>` where `n` is the number of digits of `length`. An
+//! indefinite length block is `#0<data>` and ends with the end of the message.
+//!
+//! Blocks of numbers are read straight into the memory of a `Vec<T>` through [`BlockBuffer`]
+//! and their bytes are swapped in place if the instrument sends them in the other order.
+use crate::err::Error;
+use bytemuck::Pod;
+
+/// The order of the bytes of the values in a block. IEEE 488.2 instruments send big endian by
+/// default and most can be switched with a command such as `:FORM:BORD SWAP`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum ByteOrder {
+    #[default]
+    BigEndian,
+    LittleEndian,
+}
+
+impl ByteOrder {
+    /// The byte order of this machine.
+    pub const NATIVE: ByteOrder = if cfg!(target_endian = "big") {
+        ByteOrder::BigEndian
+    } else {
+        ByteOrder::LittleEndian
+    };
+}
+
+/// A number that can be sent in a block.
+pub trait BlockValue: Pod {
+    fn swap_bytes(self) -> Self;
+}
+
+macro_rules! block_value {
+    ($($int:ty),*; $($float:ty),*) => {
+        $(impl BlockValue for $int {
+            fn swap_bytes(self) -> Self {
+                <$int>::swap_bytes(self)
+            }
+        })*
+        $(impl BlockValue for $float {
+            fn swap_bytes(self) -> Self {
+                <$float>::from_bits(self.to_bits().swap_bytes())
+            }
+        })*
+    };
+}
+
+block_value!(i8, u8, i16, u16, i32, u32, i64, u64; f32, f64);
+
+/// Memory that the data of a block is read into once its length is known.
+pub trait BlockBuffer {
+    /// Makes room for `len` bytes and returns them.
+    fn alloc(&mut self, len: usize) -> &mut [u8];
+}
+
+/// The vector is resized to hold `len` bytes in whole values. A partial last value is zero
+/// padded.
+impl<T: BlockValue> BlockBuffer for Vec<T> {
+    fn alloc(&mut self, len: usize) -> &mut [u8] {
+        self.clear();
+        self.resize(len.div_ceil(size_of::<T>()), T::zeroed());
+        &mut bytemuck::cast_slice_mut(self.as_mut_slice())[..len]
+    }
+}
+
+/// Converts values that were received in `order` to the byte order of this machine.
+pub fn to_native<T: BlockValue>(values: &mut [T], order: ByteOrder) {
+    if order != ByteOrder::NATIVE && size_of::<T>() > 1 {
+        // A plain loop over the slice is vectorized into shuffles by the compiler.
+        for value in values.iter_mut() {
+            *value = value.swap_bytes();
+        }
+    }
+}
+
+/// Decodes the data of a block into values sent in `order`.
+/// ```rust
+/// use instrument_communication::binary_block::{decode_values, ByteOrder};
+/// let values: Vec<i16> = decode_values(&[0x01, 0x02, 0xFF, 0xFE], ByteOrder::BigEndian).unwrap();
+/// assert_eq!(values, [0x0102, -2]);
+/// ```
+pub fn decode_values<T: BlockValue>(data: &[u8], order: ByteOrder) -> Result<Vec<T>, Error> {
+    check_whole_values::<T>(data.len())?;
+    let mut values = Vec::new();
+    values.alloc(data.len()).copy_from_slice(data);
+    to_native(&mut values, order);
+    Ok(values)
+}
+
+/// Checks that a block of `len` bytes holds whole values of `T`.
+pub(crate) fn check_whole_values<T>(len: usize) -> Result<(), Error> {
+    let size = size_of::<T>();
+    if len.is_multiple_of(size) {
+        Ok(())
+    } else {
+        Err(Error::ParseFailed(
+            format!("Binary block of {len} bytes is not a whole number of {size} byte values")
+                .into(),
+        ))
+    }
+}
+
+/// The header of a block at the start of a response.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Header {
+    /// At least this many bytes are needed to parse the header.
+    Incomplete(usize),
+    /// The data starts at `start` and is `len` bytes long.
+    Definite { start: usize, len: usize },
+    /// The data starts at `start`, after `#0`, and runs to the end of the message.
+    Indefinite { start: usize },
+}
+
+/// The number of bytes of `stray` at the start of `data`, or `None` while `data` is too short
+/// to tell. An empty `stray` is never found.
+pub(crate) fn stray_len(data: &[u8], stray: &[u8]) -> Option<usize> {
+    if data.starts_with(stray) {
+        Some(stray.len())
+    } else if stray.starts_with(data) {
+        None
+    } else {
+        Some(0)
+    }
+}
+
+/// Parses the header at the start of `data` which may hold only part of the response. One
+/// `stray` terminator of a previous block before the header is skipped.
+pub(crate) fn parse_header(data: &[u8], stray: &[u8]) -> Result<Header, Error> {
+    let Some(skipped) = stray_len(data, stray) else {
+        return Ok(Header::Incomplete(stray.len()));
+    };
+    Ok(match parse_block_header(&data[skipped..])? {
+        Header::Incomplete(needed) => Header::Incomplete(skipped + needed),
+        Header::Definite { start, len } => Header::Definite {
+            start: skipped + start,
+            len,
+        },
+        Header::Indefinite { start } => Header::Indefinite {
+            start: skipped + start,
+        },
+    })
+}
+
+fn parse_block_header(data: &[u8]) -> Result<Header, Error> {
+    let invalid = |msg: String| Error::ParseFailed(format!("Invalid binary block. {msg}").into());
+    match data.first() {
+        None => return Ok(Header::Incomplete(2)),
+        Some(b'#') => (),
+        Some(_) => {
+            let start = String::from_utf8_lossy(&data[..data.len().min(8)]).into_owned();
+            Err(invalid(format!("Expected # but got {start:?}")))?
+        }
+    }
+    let digits = match data.get(1) {
+        None => return Ok(Header::Incomplete(2)),
+        Some(b'0') => return Ok(Header::Indefinite { start: 2 }),
+        Some(digit @ b'1'..=b'9') => usize::from(digit - b'0'),
+        Some(byte) => Err(invalid(format!(
+            "Expected the number of length digits but got {:?}",
+            *byte as char
+        )))?,
+    };
+    let start = 2 + digits;
+    let Some(length) = data.get(2..start) else {
+        return Ok(Header::Incomplete(start));
+    };
+    if !length.iter().all(u8::is_ascii_digit) {
+        Err(invalid(format!(
+            "Expected a length of {digits} digits but got {:?}",
+            String::from_utf8_lossy(length)
+        )))?
+    }
+    // At most 9 digits so the length always fits.
+    let len = std::str::from_utf8(length).unwrap().parse().unwrap();
+    Ok(Header::Definite { start, len })
+}
+
+/// Encodes `data` as a definite length block.
+/// ```rust
+/// use instrument_communication::binary_block::encode;
+/// assert_eq!(encode(b"1\n2"), b"#131\n2");
+/// ```
+pub fn encode(data: &[u8]) -> Vec<u8> {
+    let len = data.len().to_string();
+    let mut block = Vec::with_capacity(2 + len.len() + data.len());
+    block.push(b'#');
+    block.extend_from_slice(len.len().to_string().as_bytes());
+    block.extend_from_slice(len.as_bytes());
+    block.extend_from_slice(data);
+    block
+}
+
+/// Returns the data of a block that was received as one whole message. Bytes after the
+/// declared length of a definite block such as the terminator are ignored.
+pub fn decode(message: &[u8]) -> Result<&[u8], Error> {
+    match parse_header(message, &[])? {
+        Header::Definite { start, len } if message.len() >= start + len => {
+            Ok(&message[start..start + len])
+        }
+        Header::Indefinite { start } => Ok(&message[start..]),
+        Header::Definite { len, .. } => Err(Error::ParseFailed(
+            format!("Binary block ended before its declared length of {len} bytes").into(),
+        )),
+        Header::Incomplete(_) => Err(Error::ParseFailed(
+            "Binary block ended inside its header".into(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(b"", Header::Incomplete(2))]
+    #[test_case(b"#", Header::Incomplete(2))]
+    #[test_case(b"#3", Header::Incomplete(5))]
+    #[test_case(b"#312", Header::Incomplete(5))]
+    #[test_case(b"#3120", Header::Definite { start: 5, len: 120 })]
+    #[test_case(b"#0\n\n", Header::Indefinite { start: 2 })]
+    #[test_case(b"#9123456789", Header::Definite { start: 11, len: 123456789 })]
+    fn header_is_parsed_from_partial_data(data: &[u8], header: Header) {
+        assert_eq!(parse_header(data, &[]).unwrap(), header);
+    }
+
+    #[test_case(b"", Header::Incomplete(2))]
+    #[test_case(b"\r", Header::Incomplete(2))]
+    #[test_case(b"\r\n#3", Header::Incomplete(7))]
+    #[test_case(b"\r\n#3120", Header::Definite { start: 7, len: 120 })]
+    #[test_case(b"#3120", Header::Definite { start: 5, len: 120 })]
+    #[test_case(b"\r\n#0", Header::Indefinite { start: 4 })]
+    fn stray_terminator_before_the_header_is_skipped(data: &[u8], header: Header) {
+        assert_eq!(parse_header(data, b"\r\n").unwrap(), header);
+    }
+
+    #[test_case(b"1.25\n", "Invalid binary block. Expected # but got \"1.25\\n\"")]
+    #[test_case(
+        b"#A12",
+        "Invalid binary block. Expected the number of length digits but got 'A'"
+    )]
+    #[test_case(
+        b"#2 5",
+        "Invalid binary block. Expected a length of 2 digits but got \" 5\""
+    )]
+    fn malformed_headers_are_rejected(data: &[u8], message: &str) {
+        assert_eq!(parse_header(data, &[]).unwrap_err().to_string(), message);
+    }
+
+    #[test_case(b"" ; "empty")]
+    #[test_case(b"\n\r\n#0" ; "terminators")]
+    #[test_case(&[7u8; 1000] ; "four length digits")]
+    fn encoded_blocks_decode(data: &[u8]) {
+        assert_eq!(decode(&encode(data)).unwrap(), data);
+    }
+
+    #[test]
+    fn values_are_decoded_in_either_byte_order() {
+        let data = [0x3F, 0x80, 0x00, 0x00, 0xC0, 0x20, 0x00, 0x00];
+        let big: Vec<f32> = decode_values(&data, ByteOrder::BigEndian).unwrap();
+        assert_eq!(big, [1.0, -2.5]);
+        let little: Vec<u32> = decode_values(&data, ByteOrder::LittleEndian).unwrap();
+        assert_eq!(little, [0x803F, 0x20C0]);
+        let bytes: Vec<i8> = decode_values(&data[..2], ByteOrder::LittleEndian).unwrap();
+        assert_eq!(bytes, [0x3F, -0x80]);
+        assert!(decode_values::<f64>(&data[..7], ByteOrder::BigEndian).is_err());
+    }
+
+    #[test]
+    fn short_blocks_are_errors() {
+        assert!(decode(b"#210abc").is_err());
+        assert!(decode(b"#2").is_err());
+        assert_eq!(decode(b"#0a\nb").unwrap(), b"a\nb");
+    }
+}
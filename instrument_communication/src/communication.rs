@@ -1,10 +1,147 @@
 use std::time::Duration;
 
+use crate::binary_block::{self, BlockBuffer, BlockValue, ByteOrder};
+use crate::framing::Framing;
 use crate::{address::InstAddr, err::Error, termination_bytes::TerminationBytes};
 
+/// Passing this to [`InstConnection::set_timeout`] makes every operation wait indefinitely.
+pub const INFINITE_TIMEOUT: Duration = Duration::MAX;
+
+/// The largest response a connection reads. Longer responses are errors so a device that never
+/// ends its response can't exhaust the memory.
+pub(crate) const MAXIMUM_BUFFER_SIZE: usize = 50000000;
+
+/// How long a block read waits for the terminator after the data. Instruments that send none
+/// would otherwise hold every block read for the whole timeout.
+pub(crate) const TERMINATOR_GRACE: Duration = Duration::from_millis(50);
+
 pub trait InstConnection {
     fn address(&self) -> InstAddr;
-    fn set_timeout(&self, timeout: Duration) -> Result<(), Error>;
+    /// Sets the timeout used by reads and writes. The timeout is stored by the connection
+    /// and reapplied after a reconnect. Use [`INFINITE_TIMEOUT`] to disable the timeout.
+    fn set_timeout(&mut self, timeout: Duration) -> Result<(), Error>;
+    /// Closes the connection then opens it again with the same settings.
     fn reconnect(&mut self) -> Result<(), Error>;
-    fn set_termination(&mut self, term_bytes: TerminationBytes) -> Result<(), Error>;
+    /// Sets the bytes that end a response. A termination of several bytes only ends a response
+    /// once all of its bytes were received, even if they arrive in separate reads.
+    fn set_read_termination(&mut self, term_bytes: TerminationBytes) -> Result<(), Error>;
+    /// Sets the bytes that are appended to every written message.
+    fn set_write_termination(&mut self, term_bytes: TerminationBytes) -> Result<(), Error>;
+
+    /// Sets the same termination for reads and writes.
+    fn set_termination(&mut self, term_bytes: TerminationBytes) -> Result<(), Error> {
+        self.set_read_termination(term_bytes.clone())?;
+        self.set_write_termination(term_bytes)
+    }
+
+    /// Delimits the messages of both directions with `framing` instead of the terminations,
+    /// e.g. [`LengthPrefixed::U16`](crate::framing::LengthPrefixed) for a binary protocol. Only
+    /// byte stream connections such as raw sockets support a framing.
+    fn set_framing(&mut self, _framing: Box<dyn Framing>) -> Result<(), Error> {
+        Err(Error::ConflictingSettings(
+            format!(
+                "Connections to {} don't support a custom framing",
+                self.address()
+            )
+            .into(),
+        ))
+    }
+    /// Sends the message to the instrument followed by the termination bytes.
+    fn write_bytes(&mut self, message: &[u8]) -> Result<(), Error>;
+    /// Reads a single response from the instrument. A response ends when the termination
+    /// bytes are received or, if no termination is set, when a full frame is received.
+    /// The termination bytes are not part of the returned message.
+    fn read_bytes(&mut self) -> Result<Vec<u8>, Error>;
+
+    /// Sends a text command followed by the termination bytes.
+    fn write(&mut self, message: &str) -> Result<(), Error> {
+        self.write_bytes(message.as_bytes())
+    }
+
+    /// Reads a single response and converts it into text.
+    fn read(&mut self) -> Result<String, Error> {
+        let response = self.read_bytes()?;
+        String::from_utf8(response).map_err(|e| {
+            Error::ParseFailed(format!("Response is not valid UTF-8. Error: {e}").into())
+        })
+    }
+
+    /// Sends a command then reads back the raw response.
+    fn query_bytes(&mut self, message: &str) -> Result<Vec<u8>, Error> {
+        self.write(message)?;
+        self.read_bytes()
+    }
+
+    /// Sends a command then reads back the response as text.
+    fn query(&mut self, message: &str) -> Result<String, Error> {
+        self.write(message)?;
+        self.read()
+    }
+
+    /// Sends a command followed by `data` as a definite length block, e.g.
+    /// `:TRAC:DATA #3100...`, then the termination bytes.
+    fn write_binary_block(&mut self, command: &str, data: &[u8]) -> Result<(), Error> {
+        let mut message = command.as_bytes().to_vec();
+        message.extend_from_slice(&binary_block::encode(data));
+        self.write_bytes(&message)
+    }
+
+    /// Reads a `#<n><length><data>` or `#0<data>` block response into `buffer` and returns
+    /// the length of its data. The trailing terminator is consumed. The default reads one
+    /// response with [`read_bytes`](InstConnection::read_bytes) which suits connections that
+    /// end a response on END rather than on the termination bytes.
+    fn read_binary_block_into(&mut self, buffer: &mut dyn BlockBuffer) -> Result<usize, Error> {
+        let response = self.read_bytes()?;
+        let data = binary_block::decode(&response)?;
+        buffer.alloc(data.len()).copy_from_slice(data);
+        Ok(data.len())
+    }
+
+    /// Reads a `#<n><length><data>` or `#0<data>` block response and returns its data.
+    fn read_binary_block(&mut self) -> Result<Vec<u8>, Error> {
+        let mut data = Vec::new();
+        self.read_binary_block_into(&mut data)?;
+        Ok(data)
+    }
+
+    /// Sends a command then reads back the data of its block response.
+    fn query_binary_block(&mut self, message: &str) -> Result<Vec<u8>, Error> {
+        self.write(message)?;
+        self.read_binary_block()
+    }
+}
+
+/// Typed reads of blocks of numbers such as waveforms. It is implemented for every connection
+/// including `Box<dyn InstConnection>`.
+pub trait BinaryValues {
+    /// Reads a block of values that the instrument sends in `order`. The data is read straight
+    /// into the returned vector.
+    fn read_binary_values<T: BlockValue>(&mut self, order: ByteOrder) -> Result<Vec<T>, Error>;
+
+    /// Sends a command then reads back a block of values, e.g.
+    /// `conn.query_binary_values::<f32>("CURV?", ByteOrder::BigEndian)`.
+    fn query_binary_values<T: BlockValue>(
+        &mut self,
+        message: &str,
+        order: ByteOrder,
+    ) -> Result<Vec<T>, Error>;
+}
+
+impl<C: InstConnection + ?Sized> BinaryValues for C {
+    fn read_binary_values<T: BlockValue>(&mut self, order: ByteOrder) -> Result<Vec<T>, Error> {
+        let mut values = Vec::new();
+        let len = self.read_binary_block_into(&mut values)?;
+        binary_block::check_whole_values::<T>(len)?;
+        binary_block::to_native(&mut values, order);
+        Ok(values)
+    }
+
+    fn query_binary_values<T: BlockValue>(
+        &mut self,
+        message: &str,
+        order: ByteOrder,
+    ) -> Result<Vec<T>, Error> {
+        self.write(message)?;
+        self.read_binary_values(order)
+    }
 }
@@ -0,0 +1,147 @@
+//! Board level control of a GPIB bus through the `GPIB[n]::INTFC` resource of VISA. It is used
+//! for operations that address every instrument on the bus such as interface clear or local
+//! lockout. Instruments are controlled individually through [`VisaConn`](super::visa_conn::VisaConn).
+use super::visa_conn::{try_load_binary, visa_error, VisaConn};
+use crate::address::visa_gpib::GpibAddress;
+use crate::address::{InstAddr, VisaAddress};
+use crate::err::{Context, Error, Operation};
+use std::sync::Arc;
+use std::time::Instant;
+use visa::*;
+
+/// How [`GpibBus::set_attention`] drives the ATN line.
+#[derive(Copy, Clone, PartialEq, Debug, Eq, Hash)]
+pub enum AtnMode {
+    Deassert,
+    Assert,
+    /// Deasserts ATN and takes part in the data handshake as a listener.
+    DeassertHandshake,
+    /// Asserts ATN without waiting for the current data transfer to finish.
+    AssertImmediate,
+}
+
+impl AtnMode {
+    fn mode(self) -> u16 {
+        (match self {
+            AtnMode::Deassert => VI_GPIB_ATN_DEASSERT,
+            AtnMode::Assert => VI_GPIB_ATN_ASSERT,
+            AtnMode::DeassertHandshake => VI_GPIB_ATN_DEASSERT_HANDSHAKE,
+            AtnMode::AssertImmediate => VI_GPIB_ATN_ASSERT_IMMEDIATE,
+        }) as u16
+    }
+}
+
+pub struct GpibBus {
+    rm: Arc<ResourceManager>,
+    session: Session,
+    address: VisaAddress,
+}
+
+impl GpibBus {
+    /// Opens the interface of a GPIB board. The default binary of [`VisaConn`] is used unless
+    /// it is overridden.
+    pub fn open(board: u16, override_binary: Option<Binary>) -> Result<GpibBus, Error> {
+        let binary = override_binary.unwrap_or_else(VisaConn::get_default_binary);
+        let rm = try_load_binary(binary)?;
+        let address = VisaAddress::gpib_interface(board);
+        let started = Instant::now();
+        let session = rm.open(address.as_str()).map_err(|e| {
+            visa_error(
+                &rm,
+                e,
+                Context::new(
+                    InstAddr::Visa(address.clone()),
+                    Operation::Connect,
+                    "Failed to open GPIB interface",
+                )
+                .with_elapsed(started.elapsed()),
+            )
+        })?;
+        Ok(GpibBus {
+            rm,
+            session,
+            address,
+        })
+    }
+
+    /// The VISA session of the interface for attributes such as `attr::GpibCicState`.
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    fn context(&self, message: &'static str) -> Context {
+        Context::new(
+            InstAddr::Visa(self.address.clone()),
+            Operation::GpibControl,
+            message,
+        )
+    }
+
+    fn check<T>(&self, result: visa::safe::Result<T>, message: &'static str) -> Result<T, Error> {
+        result.map_err(|e| visa_error(&self.rm, e, self.context(message)))
+    }
+
+    /// Pulses IFC which resets the bus and makes this board the controller in charge.
+    pub fn interface_clear(&self) -> Result<(), Error> {
+        self.check(
+            self.session.gpib_send_ifc(),
+            "Failed to send interface clear",
+        )
+    }
+
+    /// Asserts or deasserts REN. Instruments only go to remote while REN is asserted.
+    pub fn set_remote_enable(&self, enable: bool) -> Result<(), Error> {
+        let mode = if enable {
+            VI_GPIB_REN_ASSERT
+        } else {
+            VI_GPIB_REN_DEASSERT
+        };
+        self.check(
+            self.session.gpib_control_ren(mode as u16),
+            "Failed to control REN",
+        )
+    }
+
+    /// Sends local lockout to every instrument on the bus so their front panels are disabled.
+    pub fn local_lockout(&self) -> Result<(), Error> {
+        self.check(
+            self.session.gpib_control_ren(VI_GPIB_REN_ASSERT_LLO as u16),
+            "Failed to send local lockout",
+        )
+    }
+
+    /// Deasserts REN and sends go-to-local so every instrument returns to front panel control.
+    pub fn go_to_local(&self) -> Result<(), Error> {
+        self.check(
+            self.session
+                .gpib_control_ren(VI_GPIB_REN_DEASSERT_GTL as u16),
+            "Failed to send go to local",
+        )
+    }
+
+    pub fn set_attention(&self, mode: AtnMode) -> Result<(), Error> {
+        self.check(
+            self.session.gpib_control_atn(mode.mode()),
+            "Failed to control ATN",
+        )
+    }
+
+    /// Sends raw command bytes such as `UNL` or `UNT` with ATN asserted. Returns the number of
+    /// bytes that were sent.
+    pub fn command(&self, commands: &[u8]) -> Result<usize, Error> {
+        self.check(
+            self.session.gpib_command(commands),
+            "Failed to send GPIB command bytes",
+        )
+    }
+
+    /// Makes another device the controller in charge.
+    pub fn pass_control(&self, address: &GpibAddress) -> Result<(), Error> {
+        let secondary = address.secondary.map_or(VI_NO_SEC_ADDR as u16, u16::from);
+        self.check(
+            self.session
+                .gpib_pass_control(address.primary as u16, secondary),
+            "Failed to pass control",
+        )
+    }
+}
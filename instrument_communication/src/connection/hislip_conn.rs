@@ -0,0 +1,581 @@
+//! A HiSLIP client that talks to LAN instruments without a VISA library. Messages and responses
+//! go through the synchronous channel. Device clear, status queries and locks go through the
+//! asynchronous channel.
+use super::resolve_host;
+use super::tcp_conn::connect_timeout;
+use crate::address::{InstAddr, VisaAddress};
+use crate::communication::{InstConnection, INFINITE_TIMEOUT, MAXIMUM_BUFFER_SIZE};
+use crate::err::{Context, Error, Operation};
+use crate::termination_bytes::TerminationBytes;
+use std::borrow::Cow;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream};
+use std::time::{Duration, Instant};
+
+/// The port HiSLIP servers listen on when the address doesn't name one.
+pub const DEFAULT_HISLIP_PORT: u16 = 4880;
+const DEFAULT_BUFFER_SIZE: usize = 4096;
+
+const PROLOGUE: &[u8; 2] = b"HS";
+const HEADER_SIZE: usize = 16;
+/// The client asks for HiSLIP 2.0. The server answers with the version both sides support.
+const PROTOCOL_VERSION: u16 = 0x0200;
+const VENDOR_ID: u16 = u16::from_be_bytes(*b"CM");
+const FIRST_MESSAGE_ID: u32 = 0xFFFF_FF00;
+
+const INITIALIZE: u8 = 0;
+const INITIALIZE_RESPONSE: u8 = 1;
+const FATAL_ERROR: u8 = 2;
+const ERROR: u8 = 3;
+const ASYNC_LOCK: u8 = 4;
+const ASYNC_LOCK_RESPONSE: u8 = 5;
+const DATA: u8 = 6;
+const DATA_END: u8 = 7;
+const DEVICE_CLEAR_COMPLETE: u8 = 8;
+const DEVICE_CLEAR_ACKNOWLEDGE: u8 = 9;
+const TRIGGER: u8 = 12;
+const INTERRUPTED: u8 = 13;
+const ASYNC_MAXIMUM_MESSAGE_SIZE: u8 = 15;
+const ASYNC_MAXIMUM_MESSAGE_SIZE_RESPONSE: u8 = 16;
+const ASYNC_INITIALIZE: u8 = 17;
+const ASYNC_INITIALIZE_RESPONSE: u8 = 18;
+const ASYNC_DEVICE_CLEAR: u8 = 19;
+const ASYNC_STATUS_QUERY: u8 = 21;
+const ASYNC_STATUS_RESPONSE: u8 = 22;
+const ASYNC_DEVICE_CLEAR_ACKNOWLEDGE: u8 = 23;
+
+const LOCK_RELEASE: u8 = 0;
+const LOCK_REQUEST: u8 = 1;
+const LOCK_SUCCESS: u8 = 1;
+const LOCK_SHARED_RELEASED: u8 = 2;
+
+/// How the server handles a new message while the response to an earlier one wasn't read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HislipMode {
+    /// Unread responses are discarded when a new message is sent.
+    Synchronized,
+    /// Responses are queued and read in the order the messages were sent.
+    Overlapped,
+}
+
+impl HislipMode {
+    fn from_control(control: u8) -> HislipMode {
+        if control & 1 == 0 {
+            HislipMode::Synchronized
+        } else {
+            HislipMode::Overlapped
+        }
+    }
+
+    fn control(self) -> u8 {
+        match self {
+            HislipMode::Synchronized => 0,
+            HislipMode::Overlapped => 1,
+        }
+    }
+}
+
+struct Message {
+    kind: u8,
+    control: u8,
+    parameter: u32,
+    payload: Vec<u8>,
+}
+
+/// One of the two TCP connections of a session. Received bytes are kept until a full message
+/// has arrived so that a timeout never loses part of a message.
+struct Channel {
+    stream: TcpStream,
+    pending: Vec<u8>,
+}
+
+impl Channel {
+    fn connect(addr: SocketAddr) -> io::Result<Channel> {
+        let stream = TcpStream::connect_timeout(&addr, connect_timeout)?;
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(connect_timeout))?;
+        stream.set_write_timeout(Some(connect_timeout))?;
+        Ok(Channel {
+            stream,
+            pending: Vec::new(),
+        })
+    }
+
+    fn set_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.stream.set_read_timeout(timeout)?;
+        self.stream.set_write_timeout(timeout)
+    }
+
+    fn send(&mut self, kind: u8, control: u8, parameter: u32, payload: &[u8]) -> io::Result<()> {
+        let mut message = Vec::with_capacity(HEADER_SIZE + payload.len());
+        message.extend_from_slice(PROLOGUE);
+        message.push(kind);
+        message.push(control);
+        message.extend_from_slice(&parameter.to_be_bytes());
+        message.extend_from_slice(&(payload.len() as u64).to_be_bytes());
+        message.extend_from_slice(payload);
+        self.stream.write_all(&message)
+    }
+
+    /// Receives the next message. Error messages from the server are returned as errors.
+    fn receive(&mut self) -> io::Result<Message> {
+        let mut chunk = [0u8; DEFAULT_BUFFER_SIZE];
+        loop {
+            if self.pending.len() >= HEADER_SIZE {
+                if &self.pending[..2] != PROLOGUE {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Received a message without the HiSLIP prologue",
+                    ));
+                }
+                let mut len = [0u8; 8];
+                len.copy_from_slice(&self.pending[8..HEADER_SIZE]);
+                let len = u64::from_be_bytes(len);
+                if len > MAXIMUM_BUFFER_SIZE as u64 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("HiSLIP message of {len} bytes is too large"),
+                    ));
+                }
+                let end = HEADER_SIZE + len as usize;
+                if self.pending.len() >= end {
+                    let message: Vec<u8> = self.pending.drain(..end).collect();
+                    let message = Message {
+                        kind: message[2],
+                        control: message[3],
+                        parameter: u32::from_be_bytes([
+                            message[4], message[5], message[6], message[7],
+                        ]),
+                        payload: message[HEADER_SIZE..].to_vec(),
+                    };
+                    return match message.kind {
+                        FATAL_ERROR | ERROR => Err(io::Error::other(format!(
+                            "HiSLIP error {}: {}",
+                            message.control,
+                            String::from_utf8_lossy(&message.payload)
+                        ))),
+                        _ => Ok(message),
+                    };
+                }
+            }
+            match self.stream.read(&mut chunk) {
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "Connection closed by the instrument",
+                    ))
+                }
+                Ok(n) => self.pending.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Receives messages until one of the kind arrives. Anything else is discarded, e.g.
+    /// service requests on the asynchronous channel or stale data during a device clear.
+    fn expect(&mut self, kind: u8) -> io::Result<Message> {
+        loop {
+            let message = self.receive()?;
+            if message.kind == kind {
+                return Ok(message);
+            }
+        }
+    }
+}
+
+impl Drop for Channel {
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+/// The channels of a session and what was negotiated when it was opened.
+struct Session {
+    sync: Channel,
+    asynchronous: Channel,
+    session_id: u16,
+    protocol_version: u16,
+    mode: HislipMode,
+    max_message_size: u64,
+}
+
+/// The host, the sub address such as `hislip0` and the port of a resource string like
+/// `tcpip0::192.168.0.2::hislip0,4880::instr`.
+fn parse_device(addr: &VisaAddress) -> (&str, u16) {
+    let device = addr.parts().get(2).copied().unwrap_or_default();
+    match device.split_once(',') {
+        Some((sub_address, port)) => (sub_address, port.parse().unwrap_or(DEFAULT_HISLIP_PORT)),
+        None => (device, DEFAULT_HISLIP_PORT),
+    }
+}
+
+fn open_session(addr: &VisaAddress) -> io::Result<Session> {
+    let (sub_address, port) = parse_device(addr);
+    let host = resolve_host(addr, port)?;
+    let mut sync = Channel::connect(host)?;
+    let parameter = (PROTOCOL_VERSION as u32) << 16 | VENDOR_ID as u32;
+    sync.send(INITIALIZE, 0, parameter, sub_address.as_bytes())?;
+    let response = sync.expect(INITIALIZE_RESPONSE)?;
+    let session_id = response.parameter as u16;
+    let mut asynchronous = Channel::connect(host)?;
+    asynchronous.send(ASYNC_INITIALIZE, 0, session_id as u32, &[])?;
+    asynchronous.expect(ASYNC_INITIALIZE_RESPONSE)?;
+    let size = (MAXIMUM_BUFFER_SIZE as u64).to_be_bytes();
+    asynchronous.send(ASYNC_MAXIMUM_MESSAGE_SIZE, 0, 0, &size)?;
+    let size = asynchronous.expect(ASYNC_MAXIMUM_MESSAGE_SIZE_RESPONSE)?;
+    let max_message_size = size
+        .payload
+        .get(..8)
+        .map(|size| u64::from_be_bytes(size.try_into().unwrap()))
+        .ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "Invalid maximum message size")
+        })?;
+    Ok(Session {
+        sync,
+        asynchronous,
+        session_id,
+        protocol_version: (response.parameter >> 16) as u16,
+        mode: HislipMode::from_control(response.control),
+        max_message_size,
+    })
+}
+
+pub struct HislipConn {
+    address: VisaAddress,
+    session: Session,
+    /// The ID of the next message sent on the synchronous channel.
+    message_id: u32,
+    /// Set once a complete response was read. The server is told with the next message.
+    rmt_delivered: bool,
+    /// Ends the responses that are read.
+    read_term: Option<TerminationBytes>,
+    /// Appended to every message that is written.
+    write_term: Option<TerminationBytes>,
+    timeout: Duration,
+    lock_timeout: Duration,
+    /// The last message that was written. It is reported in errors.
+    last_command: Option<Vec<u8>>,
+}
+
+impl HislipConn {
+    pub fn connect(addr: VisaAddress) -> Result<HislipConn, Error> {
+        let started = Instant::now();
+        let session = open_session(&addr).map_err(|e| {
+            Error::ConnectionFailed(Box::new(
+                Context::new(
+                    InstAddr::Visa(addr.clone()),
+                    Operation::Connect,
+                    "Failed to open HiSLIP session",
+                )
+                .with_elapsed(started.elapsed())
+                .with_io_error(e),
+            ))
+        })?;
+        let mut conn = HislipConn {
+            address: addr,
+            session,
+            message_id: FIRST_MESSAGE_ID,
+            rmt_delivered: false,
+            read_term: None,
+            write_term: None,
+            timeout: connect_timeout,
+            lock_timeout: Duration::ZERO,
+            last_command: None,
+        };
+        conn.apply_timeout()?;
+        conn.set_termination(TerminationBytes::LF)?;
+        Ok(conn)
+    }
+
+    /// The mode the server agreed to.
+    pub fn mode(&self) -> HislipMode {
+        self.session.mode
+    }
+
+    pub fn session_id(&self) -> u16 {
+        self.session.session_id
+    }
+
+    /// The protocol version as (major, minor).
+    pub fn protocol_version(&self) -> (u8, u8) {
+        let [major, minor] = self.session.protocol_version.to_be_bytes();
+        (major, minor)
+    }
+
+    /// The largest message the server accepts. Longer writes are split into several messages.
+    pub fn max_message_size(&self) -> u64 {
+        self.session.max_message_size
+    }
+
+    /// Sets how long [`HislipConn::lock`] waits for a lock held by another session.
+    pub fn set_lock_timeout(&mut self, timeout: Duration) -> Result<(), Error> {
+        self.lock_timeout = timeout;
+        self.apply_timeout()
+    }
+
+    /// Sends a device clear and keeps the current mode.
+    pub fn clear(&mut self) -> Result<(), Error> {
+        self.set_mode(self.session.mode).map(|_| ())
+    }
+
+    /// Sends a device clear that asks the server to switch to the mode. Returns the mode
+    /// the server agreed to.
+    pub fn set_mode(&mut self, mode: HislipMode) -> Result<HislipMode, Error> {
+        let started = Instant::now();
+        let session = &mut self.session;
+        let result = session
+            .asynchronous
+            .send(ASYNC_DEVICE_CLEAR, 0, 0, &[])
+            .and_then(|_| session.asynchronous.expect(ASYNC_DEVICE_CLEAR_ACKNOWLEDGE))
+            .and_then(|_| {
+                session
+                    .sync
+                    .send(DEVICE_CLEAR_COMPLETE, mode.control(), 0, &[])
+            })
+            .and_then(|_| session.sync.expect(DEVICE_CLEAR_ACKNOWLEDGE));
+        let acknowledge = result.map_err(|e| self.io_error(e, Operation::Clear, started))?;
+        self.session.mode = HislipMode::from_control(acknowledge.control);
+        self.message_id = FIRST_MESSAGE_ID;
+        self.rmt_delivered = false;
+        Ok(self.session.mode)
+    }
+
+    /// Sends a trigger message to the instrument.
+    pub fn trigger(&mut self) -> Result<(), Error> {
+        let started = Instant::now();
+        let message_id = self.next_message_id();
+        let control = self.take_rmt_delivered();
+        self.session
+            .sync
+            .send(TRIGGER, control, message_id, &[])
+            .map_err(|e| self.io_error(e, Operation::Trigger, started))
+    }
+
+    /// Reads the status byte of the instrument.
+    pub fn read_stb(&mut self) -> Result<u8, Error> {
+        let started = Instant::now();
+        let control = self.take_rmt_delivered();
+        let message_id = self.last_message_id();
+        let asynchronous = &mut self.session.asynchronous;
+        let response = asynchronous
+            .send(ASYNC_STATUS_QUERY, control, message_id, &[])
+            .and_then(|_| asynchronous.expect(ASYNC_STATUS_RESPONSE))
+            .map_err(|e| self.io_error(e, Operation::ReadStatusByte, started))?;
+        Ok(response.control)
+    }
+
+    /// Locks the instrument exclusively. Waits up to the lock timeout for other sessions to
+    /// release their locks.
+    pub fn lock(&mut self) -> Result<(), Error> {
+        self.request_lock("")
+    }
+
+    /// Takes a shared lock. Sessions that use the same lock name can share the instrument.
+    pub fn lock_shared(&mut self, name: &str) -> Result<(), Error> {
+        self.request_lock(name)
+    }
+
+    /// Releases the lock held by this session.
+    pub fn unlock(&mut self) -> Result<(), Error> {
+        let message_id = self.last_message_id();
+        let response = self.lock_call(LOCK_RELEASE, message_id, "")?;
+        match response {
+            LOCK_SUCCESS | LOCK_SHARED_RELEASED => Ok(()),
+            _ => Err(Error::FunctionFailure(Box::new(
+                self.context(Operation::Lock, "No lock is held by this session"),
+            ))),
+        }
+    }
+
+    fn request_lock(&mut self, name: &str) -> Result<(), Error> {
+        let timeout = u32::try_from(self.lock_timeout.as_millis()).unwrap_or(u32::MAX);
+        match self.lock_call(LOCK_REQUEST, timeout, name)? {
+            LOCK_SUCCESS => Ok(()),
+            _ => Err(Error::FunctionFailure(Box::new(self.context(
+                Operation::Lock,
+                "The lock was not granted before the lock timeout",
+            )))),
+        }
+    }
+
+    fn lock_call(&mut self, control: u8, parameter: u32, name: &str) -> Result<u8, Error> {
+        let started = Instant::now();
+        let asynchronous = &mut self.session.asynchronous;
+        let response = asynchronous
+            .send(ASYNC_LOCK, control, parameter, name.as_bytes())
+            .and_then(|_| asynchronous.expect(ASYNC_LOCK_RESPONSE))
+            .map_err(|e| self.io_error(e, Operation::Lock, started))?;
+        Ok(response.control)
+    }
+
+    fn next_message_id(&mut self) -> u32 {
+        let message_id = self.message_id;
+        self.message_id = self.message_id.wrapping_add(2);
+        message_id
+    }
+
+    /// The ID of the last message sent on the synchronous channel.
+    fn last_message_id(&self) -> u32 {
+        self.message_id.wrapping_sub(2)
+    }
+
+    fn take_rmt_delivered(&mut self) -> u8 {
+        std::mem::take(&mut self.rmt_delivered) as u8
+    }
+
+    fn context(&self, operation: Operation, message: impl Into<Cow<'static, str>>) -> Context {
+        Context::new(self.address(), operation, message).with_command(self.last_command.as_deref())
+    }
+
+    /// Timeouts on a socket are reported as either WouldBlock or TimedOut depending on the
+    /// platform.
+    fn io_error(&self, e: io::Error, operation: Operation, started: Instant) -> Error {
+        let timed_out = matches!(
+            e.kind(),
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+        );
+        let context = Box::new(
+            self.context(operation, "HiSLIP transfer failed")
+                .with_elapsed(started.elapsed())
+                .with_io_error(e),
+        );
+        if timed_out {
+            Error::Timeout(context)
+        } else {
+            Error::FunctionFailure(context)
+        }
+    }
+
+    /// The asynchronous channel also waits for locks held by other sessions.
+    fn apply_timeout(&self) -> Result<(), Error> {
+        let (sync, asynchronous) = if self.timeout == INFINITE_TIMEOUT {
+            (None, None)
+        } else {
+            (
+                Some(self.timeout),
+                Some(self.timeout.saturating_add(self.lock_timeout)),
+            )
+        };
+        self.session
+            .sync
+            .set_timeout(sync)
+            .and_then(|_| self.session.asynchronous.set_timeout(asynchronous))
+            .map_err(|e| {
+                Error::FunctionFailure(Box::new(
+                    self.context(Operation::SetAttribute, "Failed to set connection timeout")
+                        .with_io_error(e),
+                ))
+            })
+    }
+}
+
+impl InstConnection for HislipConn {
+    fn address(&self) -> InstAddr {
+        InstAddr::Visa(self.address.clone())
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> Result<(), Error> {
+        let previous = std::mem::replace(&mut self.timeout, timeout);
+        self.apply_timeout()
+            .inspect_err(|_| self.timeout = previous)
+    }
+
+    fn reconnect(&mut self) -> Result<(), Error> {
+        let started = Instant::now();
+        self.session = open_session(&self.address).map_err(|e| {
+            Error::ConnectionFailed(Box::new(
+                self.context(Operation::Connect, "Failed to open HiSLIP session")
+                    .with_elapsed(started.elapsed())
+                    .with_io_error(e),
+            ))
+        })?;
+        self.message_id = FIRST_MESSAGE_ID;
+        self.rmt_delivered = false;
+        self.apply_timeout()
+    }
+
+    fn set_read_termination(&mut self, term_bytes: TerminationBytes) -> Result<(), Error> {
+        self.read_term = Some(term_bytes);
+        Ok(())
+    }
+
+    fn set_write_termination(&mut self, term_bytes: TerminationBytes) -> Result<(), Error> {
+        self.write_term = Some(term_bytes);
+        Ok(())
+    }
+
+    fn write_bytes(&mut self, message: &[u8]) -> Result<(), Error> {
+        let term = self.write_term.as_ref().unwrap_or(&TerminationBytes::None);
+        let mut buffer = Vec::with_capacity(message.len() + term.bytes().len());
+        buffer.extend_from_slice(message);
+        buffer.extend_from_slice(term.bytes());
+        self.last_command = Some(message.to_vec());
+        let started = Instant::now();
+        // Every message including its header has to fit in the server's maximum message size.
+        let chunk_size = (self.session.max_message_size as usize)
+            .saturating_sub(HEADER_SIZE)
+            .max(1);
+        let mut chunks = buffer.chunks(chunk_size).peekable();
+        let mut chunk: &[u8] = chunks.next().unwrap_or_default();
+        loop {
+            let is_last = chunks.peek().is_none();
+            let kind = if is_last { DATA_END } else { DATA };
+            let message_id = self.next_message_id();
+            let control = self.take_rmt_delivered();
+            self.session
+                .sync
+                .send(kind, control, message_id, chunk)
+                .map_err(|e| self.io_error(e, Operation::Write, started))?;
+            match chunks.next() {
+                Some(next) => chunk = next,
+                None => return Ok(()),
+            }
+        }
+    }
+
+    fn read_bytes(&mut self) -> Result<Vec<u8>, Error> {
+        let started = Instant::now();
+        let mut response: Vec<u8> = Vec::new();
+        loop {
+            let message = self
+                .session
+                .sync
+                .receive()
+                .map_err(|e| self.io_error(e, Operation::Read, started))?;
+            match message.kind {
+                // In synchronized mode only the response to the last message is wanted.
+                DATA | DATA_END
+                    if self.session.mode == HislipMode::Synchronized
+                        && message.parameter != self.last_message_id() =>
+                {
+                    response.clear();
+                }
+                DATA | DATA_END => {
+                    response.extend_from_slice(&message.payload);
+                    if response.len() > MAXIMUM_BUFFER_SIZE {
+                        let message = format!(
+                            "Response exceeded the maximum size of {MAXIMUM_BUFFER_SIZE} bytes"
+                        );
+                        let context = self
+                            .context(Operation::Read, message)
+                            .with_elapsed(started.elapsed());
+                        Err(Error::FunctionFailure(Box::new(context)))?
+                    }
+                    if message.kind == DATA_END {
+                        self.rmt_delivered = true;
+                        break;
+                    }
+                }
+                // The server dropped a response that was being received.
+                INTERRUPTED => response.clear(),
+                _ => (),
+            }
+        }
+        if let Some(term) = &self.read_term {
+            let len = term.strip(&response).len();
+            response.truncate(len);
+        }
+        Ok(response)
+    }
+}
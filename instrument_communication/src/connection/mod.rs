@@ -1,2 +1,51 @@
+use crate::address::error::AddressError;
+use crate::address::host::scope_id;
+use crate::address::socket::NetworkAddr;
+use crate::address::VisaAddress;
+use std::io;
+use std::net::{Ipv6Addr, SocketAddr, SocketAddrV6, ToSocketAddrs};
+use std::str::FromStr;
+
+pub mod gpib_bus;
+pub mod hislip_conn;
+mod onc_rpc;
+#[cfg(target_os = "linux")]
+pub mod serial_conn;
 pub mod tcp_conn;
+#[cfg(test)]
+mod test_support;
 pub mod visa_conn;
+pub mod vxi11_conn;
+
+/// The host is the second part of the resource string, e.g. `tcpip0::192.168.0.2::instr` or
+/// `tcpip0::[fe80::1%eth0]::instr`. IPv4 addresses are preferred.
+pub(crate) fn resolve_host(addr: &VisaAddress, port: u16) -> io::Result<SocketAddr> {
+    let host = addr.parts().get(1).copied().unwrap_or_default();
+    let invalid = |e: AddressError| io::Error::new(io::ErrorKind::InvalidInput, e);
+    match NetworkAddr::from_str(host).map_err(invalid)? {
+        NetworkAddr::V4(ip) => Ok(SocketAddr::new(ip.into(), port)),
+        NetworkAddr::V6(ip) => Ok(SocketAddr::new(ip.into(), port)),
+        NetworkAddr::ScopedV6 { ip, zone } => scoped_v6(ip, &zone, port),
+        NetworkAddr::RAW(host) => (host.as_str(), port)
+            .to_socket_addrs()?
+            .min_by_key(|addr| addr.is_ipv6())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Unable to resolve hostname: {host}"),
+                )
+            }),
+    }
+}
+
+/// An IPv6 socket whose zone is resolved to the scope ID of an interface of this machine.
+pub(crate) fn scoped_v6(ip: Ipv6Addr, zone: &str, port: u16) -> io::Result<SocketAddr> {
+    let scope_id = scope_id(zone).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    Ok(SocketAddr::V6(SocketAddrV6::new(ip, port, 0, scope_id)))
+}
+
+/// The same host on another port.
+pub(crate) fn with_port(mut addr: SocketAddr, port: u16) -> SocketAddr {
+    addr.set_port(port);
+    addr
+}
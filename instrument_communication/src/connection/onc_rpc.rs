@@ -0,0 +1,247 @@
+//! A minimal ONC RPC (RFC 5531) client over TCP and the XDR (RFC 4506) encoding it needs. It
+//! only implements what VXI-11 uses: AUTH_NONE calls, record marking and the portmapper.
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream};
+use std::time::Duration;
+
+const LAST_FRAGMENT: u32 = 0x8000_0000;
+/// Replies larger than this are rejected instead of being allocated.
+const MAX_RECORD_SIZE: usize = 64 * 1024 * 1024;
+const RPC_VERSION: u32 = 2;
+const CALL: u32 = 0;
+const REPLY: u32 = 1;
+const MSG_ACCEPTED: u32 = 0;
+const SUCCESS: u32 = 0;
+const AUTH_NONE: u32 = 0;
+
+const PORTMAPPER_PROGRAM: u32 = 100000;
+const PORTMAPPER_VERSION: u32 = 2;
+const PMAPPROC_GETPORT: u32 = 3;
+const IPPROTO_TCP: u32 = 6;
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Encodes values in XDR. Every item is a multiple of four bytes.
+#[derive(Default)]
+pub(crate) struct XdrWriter {
+    buf: Vec<u8>,
+}
+
+impl XdrWriter {
+    pub fn u32(&mut self, value: u32) -> &mut Self {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub fn i32(&mut self, value: i32) -> &mut Self {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub fn bool(&mut self, value: bool) -> &mut Self {
+        self.u32(value as u32)
+    }
+
+    /// Variable length opaque data. It is prefixed with its length and padded with zeros.
+    pub fn opaque(&mut self, data: &[u8]) -> &mut Self {
+        self.u32(data.len() as u32);
+        self.buf.extend_from_slice(data);
+        self.buf.resize(self.buf.len() + padding(data.len()), 0);
+        self
+    }
+
+    pub fn string(&mut self, value: &str) -> &mut Self {
+        self.opaque(value.as_bytes())
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+fn padding(len: usize) -> usize {
+    (4 - len % 4) % 4
+}
+
+/// Decodes XDR values from a reply.
+pub(crate) struct XdrReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> XdrReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        XdrReader { data }
+    }
+
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if self.data.len() < len {
+            return Err(invalid_data("XDR data ended early"));
+        }
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    pub fn u32(&mut self) -> io::Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub fn i32(&mut self) -> io::Result<i32> {
+        self.u32().map(|value| value as i32)
+    }
+
+    pub fn opaque(&mut self) -> io::Result<&'a [u8]> {
+        let len = self.u32()? as usize;
+        let data = self.take(len)?;
+        self.take(padding(len))?;
+        Ok(data)
+    }
+}
+
+/// A connection to one program of an RPC server.
+pub(crate) struct RpcClient {
+    stream: TcpStream,
+    program: u32,
+    version: u32,
+    xid: u32,
+}
+
+impl RpcClient {
+    pub fn connect(
+        addr: SocketAddr,
+        program: u32,
+        version: u32,
+        timeout: Duration,
+    ) -> io::Result<RpcClient> {
+        let stream = TcpStream::connect_timeout(&addr, timeout)?;
+        stream.set_nodelay(true)?;
+        Ok(RpcClient {
+            stream,
+            program,
+            version,
+            xid: std::process::id(),
+        })
+    }
+
+    /// Sets the time to wait for a reply. `None` waits indefinitely.
+    pub fn set_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.stream.set_read_timeout(timeout)?;
+        self.stream.set_write_timeout(timeout)
+    }
+
+    /// Calls a procedure and returns the encoded results.
+    pub fn call(&mut self, procedure: u32, args: &XdrWriter) -> io::Result<Vec<u8>> {
+        self.xid = self.xid.wrapping_add(1);
+        let mut call = XdrWriter::default();
+        call.u32(self.xid)
+            .u32(CALL)
+            .u32(RPC_VERSION)
+            .u32(self.program)
+            .u32(self.version)
+            .u32(procedure)
+            .u32(AUTH_NONE)
+            .opaque(&[])
+            .u32(AUTH_NONE)
+            .opaque(&[]);
+        call.buf.extend_from_slice(args.as_bytes());
+        write_record(&mut self.stream, call.as_bytes())?;
+        loop {
+            let reply = read_record(&mut self.stream)?;
+            let mut reader = XdrReader::new(&reply);
+            // A reply to an earlier call that timed out can still arrive. It is skipped.
+            if reader.u32()? != self.xid {
+                continue;
+            }
+            if reader.u32()? != REPLY {
+                return Err(invalid_data("Expected an RPC reply"));
+            }
+            if reader.u32()? != MSG_ACCEPTED {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "RPC call was denied",
+                ));
+            }
+            let _verifier_flavor = reader.u32()?;
+            reader.opaque()?;
+            return match reader.u32()? {
+                SUCCESS => Ok(reader.data.to_vec()),
+                status => Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("RPC call was not accepted. Status: {status}"),
+                )),
+            };
+        }
+    }
+}
+
+impl Drop for RpcClient {
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+fn write_record(stream: &mut TcpStream, data: &[u8]) -> io::Result<()> {
+    let mut record = Vec::with_capacity(data.len() + 4);
+    record.extend_from_slice(&(LAST_FRAGMENT | data.len() as u32).to_be_bytes());
+    record.extend_from_slice(data);
+    stream.write_all(&record)
+}
+
+fn read_record(stream: &mut TcpStream) -> io::Result<Vec<u8>> {
+    let mut record = Vec::new();
+    loop {
+        let mut header = [0u8; 4];
+        stream.read_exact(&mut header)?;
+        let header = u32::from_be_bytes(header);
+        let len = (header & !LAST_FRAGMENT) as usize;
+        if record.len() + len > MAX_RECORD_SIZE {
+            return Err(invalid_data("RPC record is too large"));
+        }
+        let start = record.len();
+        record.resize(start + len, 0);
+        stream.read_exact(&mut record[start..])?;
+        if header & LAST_FRAGMENT != 0 {
+            return Ok(record);
+        }
+    }
+}
+
+/// Asks the portmapper at `portmapper` for the TCP port of a program. A port of 0 means that
+/// the program isn't registered.
+pub(crate) fn get_port(
+    portmapper: SocketAddr,
+    program: u32,
+    version: u32,
+    timeout: Duration,
+) -> io::Result<u16> {
+    let mut client =
+        RpcClient::connect(portmapper, PORTMAPPER_PROGRAM, PORTMAPPER_VERSION, timeout)?;
+    client.set_timeout(Some(timeout))?;
+    let mut args = XdrWriter::default();
+    args.u32(program).u32(version).u32(IPPROTO_TCP).u32(0);
+    let reply = client.call(PMAPPROC_GETPORT, &args)?;
+    let port = XdrReader::new(&reply).u32()?;
+    u16::try_from(port).map_err(|_| invalid_data(format!("Invalid port {port}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opaque_is_padded_to_four_bytes() {
+        let mut writer = XdrWriter::default();
+        writer.string("inst0").bool(true);
+        assert_eq!(
+            writer.as_bytes(),
+            [0, 0, 0, 5, b'i', b'n', b's', b't', b'0', 0, 0, 0, 0, 0, 0, 1]
+        );
+        let mut reader = XdrReader::new(writer.as_bytes());
+        assert_eq!(reader.opaque().unwrap(), b"inst0");
+        assert_eq!(reader.u32().unwrap(), 1);
+        assert!(reader.u32().is_err());
+    }
+}
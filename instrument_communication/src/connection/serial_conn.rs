@@ -0,0 +1,472 @@
+//! A native serial port connection for RS-232 instruments on Linux. The port is configured
+//! through termios with the settings of the address so no VISA library is needed.
+//!
+//! Binary blocks are read as one line, so blocks whose data contains the read termination are
+//! not supported. Instruments that send such blocks need a VISA connection to the port.
+use crate::address::visa_serial::{FlowControl, Parity, SerialAddress, SerialSettings, StopBits};
+use crate::address::{InstAddr, VisaAddress};
+use crate::communication::{InstConnection, INFINITE_TIMEOUT, MAXIMUM_BUFFER_SIZE};
+use crate::err::{Context, Error, Operation};
+use crate::termination_bytes::TerminationBytes;
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::time::{Duration, Instant};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
+const DEFAULT_BUFFER_SIZE: usize = 4096;
+
+pub struct SerialConn {
+    port: File,
+    address: VisaAddress,
+    serial: SerialAddress,
+    /// Ends the responses that are read.
+    read_term: Option<TerminationBytes>,
+    /// Appended to every message that is written.
+    write_term: Option<TerminationBytes>,
+    timeout: Duration,
+    /// True if the instrument echoes every command back before it responds.
+    echo: bool,
+    /// The commands whose echo wasn't read yet.
+    pending_echoes: VecDeque<Vec<u8>>,
+    /// The prompt some instruments print when they are ready for the next command.
+    prompt: Option<Vec<u8>>,
+    /// Bytes received from the instrument that belong to the next response.
+    pending: Vec<u8>,
+    /// The last message that was written. It is reported in errors.
+    last_command: Option<Vec<u8>>,
+}
+
+impl SerialConn {
+    pub fn connect(addr: VisaAddress) -> Result<SerialConn, Error> {
+        let serial = addr.as_serial().ok_or_else(|| {
+            Error::ConflictingSettings(format!("{} is not a serial address", addr.as_str()).into())
+        })?;
+        let port = open_port(&addr, &serial)?;
+        Ok(SerialConn {
+            port,
+            address: addr,
+            serial,
+            read_term: Some(TerminationBytes::default()),
+            write_term: Some(TerminationBytes::default()),
+            timeout: DEFAULT_TIMEOUT,
+            echo: false,
+            pending_echoes: VecDeque::new(),
+            prompt: None,
+            pending: Vec::new(),
+            last_command: None,
+        })
+    }
+
+    pub fn settings(&self) -> &SerialSettings {
+        &self.serial.settings
+    }
+
+    /// Instruments that echo the characters they receive send every command back before
+    /// its response. When this is enabled, reads skip the lines that repeat the written
+    /// commands in order.
+    pub fn set_echo(&mut self, echo: bool) {
+        self.echo = echo;
+        self.pending_echoes.clear();
+    }
+
+    /// Removes the prompt, e.g. `> `, from the start and the end of every response.
+    pub fn set_prompt(&mut self, prompt: Option<Vec<u8>>) {
+        self.prompt = prompt.filter(|prompt| !prompt.is_empty());
+    }
+
+    /// Discards everything that was received but not read yet.
+    pub fn clear(&mut self) -> Result<(), Error> {
+        self.pending.clear();
+        self.pending_echoes.clear();
+        if unsafe { libc::tcflush(self.port.as_raw_fd(), libc::TCIOFLUSH) } != 0 {
+            Err(Error::FunctionFailure(Box::new(
+                self.context(Operation::Clear, "Failed to flush serial port")
+                    .with_io_error(io::Error::last_os_error()),
+            )))?
+        }
+        Ok(())
+    }
+
+    fn context(&self, operation: Operation, message: impl Into<Cow<'static, str>>) -> Context {
+        Context::new(InstAddr::Visa(self.address.clone()), operation, message)
+            .with_command(self.last_command.as_deref())
+    }
+
+    fn io_error(
+        &self,
+        e: io::Error,
+        operation: Operation,
+        message: &'static str,
+        started: Instant,
+    ) -> Error {
+        let timed_out = matches!(
+            e.kind(),
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+        );
+        let context = Box::new(
+            self.context(operation, message)
+                .with_elapsed(started.elapsed())
+                .with_io_error(e),
+        );
+        if timed_out {
+            Error::Timeout(context)
+        } else {
+            Error::FunctionFailure(context)
+        }
+    }
+
+    /// Waits until the port has data to read or the timeout runs out.
+    fn wait_readable(&self, started: Instant) -> io::Result<()> {
+        self.poll(
+            libc::POLLIN,
+            started,
+            "No data received from the serial port",
+        )
+    }
+
+    /// Waits until the port can take more data or the timeout runs out. A port stays full
+    /// while flow control holds back its output.
+    fn wait_writable(&self, started: Instant) -> io::Result<()> {
+        self.poll(libc::POLLOUT, started, "Serial port didn't accept the data")
+    }
+
+    fn poll(
+        &self,
+        events: libc::c_short,
+        started: Instant,
+        timeout_message: &'static str,
+    ) -> io::Result<()> {
+        let mut fd = libc::pollfd {
+            fd: self.port.as_raw_fd(),
+            events,
+            revents: 0,
+        };
+        loop {
+            let timeout = if self.timeout == INFINITE_TIMEOUT {
+                -1
+            } else {
+                self.timeout
+                    .saturating_sub(started.elapsed())
+                    .as_millis()
+                    .min(i32::MAX as u128) as i32
+            };
+            match unsafe { libc::poll(&mut fd, 1, timeout) } {
+                0 => Err(io::Error::new(io::ErrorKind::TimedOut, timeout_message))?,
+                n if n > 0 => return Ok(()),
+                _ => {
+                    let e = io::Error::last_os_error();
+                    if e.kind() != io::ErrorKind::Interrupted {
+                        Err(e)?
+                    }
+                }
+            }
+        }
+    }
+
+    /// Extracts a complete line from the pending bytes if one has been received.
+    /// `searched` is the number of pending bytes that were already scanned for the terminator.
+    fn take_line(&mut self, searched: usize) -> Option<Vec<u8>> {
+        let term = self.read_term.as_ref().unwrap_or(&TerminationBytes::None);
+        let term_len = term.bytes().len();
+        if term_len > 0 {
+            let start = searched.saturating_sub(term_len - 1);
+            let end = term.find(&self.pending, start)?;
+            let mut line: Vec<u8> = self.pending.drain(..end + term_len).collect();
+            line.truncate(end);
+            Some(line)
+        } else if !self.pending.is_empty() {
+            Some(std::mem::take(&mut self.pending))
+        } else {
+            None
+        }
+    }
+
+    fn strip_prompt(&self, mut line: Vec<u8>) -> Vec<u8> {
+        if let Some(prompt) = &self.prompt {
+            let mut start = 0;
+            while line[start..].starts_with(prompt) {
+                start += prompt.len();
+            }
+            line.drain(..start);
+            while line.ends_with(prompt) {
+                line.truncate(line.len() - prompt.len());
+            }
+        }
+        line
+    }
+
+    fn read_line(&mut self) -> Result<Vec<u8>, Error> {
+        let mut chunk = vec![0u8; DEFAULT_BUFFER_SIZE];
+        let mut searched = 0;
+        let started = Instant::now();
+        loop {
+            if let Some(line) = self.take_line(searched) {
+                return Ok(self.strip_prompt(line));
+            }
+            if self.pending.len() + chunk.len() > MAXIMUM_BUFFER_SIZE {
+                // The partial line is discarded so the next read doesn't continue it.
+                self.pending.clear();
+                let message =
+                    format!("Response exceeded the maximum size of {MAXIMUM_BUFFER_SIZE} bytes");
+                let context = self
+                    .context(Operation::Read, message)
+                    .with_elapsed(started.elapsed());
+                Err(Error::FunctionFailure(Box::new(context)))?
+            }
+            searched = self.pending.len();
+            let read = self
+                .wait_readable(started)
+                .and_then(|_| self.port.read(&mut chunk));
+            match read {
+                Ok(0) => Err(Error::ConnectionFailed(Box::new(
+                    self.context(Operation::Read, "Serial port was closed")
+                        .with_elapsed(started.elapsed()),
+                )))?,
+                Ok(n) => self.pending.extend_from_slice(&chunk[..n]),
+                Err(e) if is_retryable(&e) => (),
+                Err(e) => Err(self.io_error(
+                    e,
+                    Operation::Read,
+                    "Failed to read from serial port",
+                    started,
+                ))?,
+            }
+        }
+    }
+}
+
+/// The port is never blocked so a read or write that poll allowed can still find it busy.
+fn is_retryable(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock
+    )
+}
+
+/// Terminals may turn the line ending of the echo into CR LF so trailing whitespace is ignored.
+fn is_echo(command: &[u8], line: &[u8]) -> bool {
+    command.trim_ascii_end() == line.trim_ascii_end()
+}
+
+fn open_port(addr: &VisaAddress, serial: &SerialAddress) -> Result<File, Error> {
+    let flags =
+        termios_flags(&serial.settings).map_err(|e| Error::ConflictingSettings(e.into()))?;
+    let started = Instant::now();
+    let path = serial.port.device_path();
+    // The port is opened without blocking on the carrier detect line until CLOCAL is set. It
+    // stays non-blocking and every read and write waits with poll so the timeout covers both.
+    let result = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NOCTTY | libc::O_NONBLOCK)
+        .open(&path)
+        .and_then(|port| {
+            configure(&port, flags)?;
+            Ok(port)
+        });
+    result.map_err(|e| {
+        Error::ConnectionFailed(Box::new(
+            Context::new(
+                InstAddr::Visa(addr.clone()),
+                Operation::Connect,
+                "Failed to open serial port",
+            )
+            .with_elapsed(started.elapsed())
+            .with_io_error(e),
+        ))
+    })
+}
+
+/// The speed and the control and input flags of a port.
+type TermiosFlags = (libc::speed_t, libc::tcflag_t, libc::tcflag_t);
+
+/// The termios flags for the settings. Settings that termios can't express are rejected.
+fn termios_flags(settings: &SerialSettings) -> Result<TermiosFlags, String> {
+    let speed = match settings.baud_rate {
+        50 => libc::B50,
+        75 => libc::B75,
+        110 => libc::B110,
+        134 => libc::B134,
+        150 => libc::B150,
+        200 => libc::B200,
+        300 => libc::B300,
+        600 => libc::B600,
+        1200 => libc::B1200,
+        1800 => libc::B1800,
+        2400 => libc::B2400,
+        4800 => libc::B4800,
+        9600 => libc::B9600,
+        19200 => libc::B19200,
+        38400 => libc::B38400,
+        57600 => libc::B57600,
+        115200 => libc::B115200,
+        230400 => libc::B230400,
+        460800 => libc::B460800,
+        500000 => libc::B500000,
+        576000 => libc::B576000,
+        921600 => libc::B921600,
+        1000000 => libc::B1000000,
+        baud => Err(format!("Unsupported baud rate {baud}"))?,
+    };
+    let mut cflag = libc::CLOCAL | libc::CREAD;
+    let mut iflag = 0;
+    cflag |= match settings.data_bits {
+        5 => libc::CS5,
+        6 => libc::CS6,
+        7 => libc::CS7,
+        _ => libc::CS8,
+    };
+    cflag |= match settings.parity {
+        Parity::None => 0,
+        Parity::Odd => libc::PARENB | libc::PARODD,
+        Parity::Even => libc::PARENB,
+        Parity::Mark => libc::PARENB | libc::PARODD | libc::CMSPAR,
+        Parity::Space => libc::PARENB | libc::CMSPAR,
+    };
+    if settings.parity != Parity::None {
+        iflag |= libc::INPCK;
+    }
+    // Two stop bits become one and a half with five data bits.
+    cflag |= match (settings.stop_bits, settings.data_bits) {
+        (StopBits::One, _) => 0,
+        (StopBits::Two, 5) => Err("Two stop bits are not supported with five data bits")?,
+        (StopBits::OneAndHalf, 5) | (StopBits::Two, _) => libc::CSTOPB,
+        (StopBits::OneAndHalf, _) => {
+            Err("One and a half stop bits are only supported with five data bits")?
+        }
+    };
+    match settings.flow_control {
+        FlowControl::None => (),
+        FlowControl::XonXoff => iflag |= libc::IXON | libc::IXOFF,
+        FlowControl::RtsCts => cflag |= libc::CRTSCTS,
+        FlowControl::DtrDsr => Err("DTR/DSR flow control is not supported by termios")?,
+    }
+    Ok((speed, cflag, iflag))
+}
+
+/// Puts the port in raw mode with the line settings. Reads return whatever was received.
+fn configure(port: &File, (speed, cflag, iflag): TermiosFlags) -> io::Result<()> {
+    let fd = port.as_raw_fd();
+    let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(fd, &mut termios) } != 0 {
+        Err(io::Error::last_os_error())?
+    }
+    unsafe { libc::cfmakeraw(&mut termios) };
+    termios.c_cflag &=
+        !(libc::CSIZE | libc::PARENB | libc::PARODD | libc::CMSPAR | libc::CSTOPB | libc::CRTSCTS);
+    termios.c_cflag |= cflag;
+    termios.c_iflag &= !(libc::IXON | libc::IXOFF | libc::IXANY | libc::INPCK);
+    termios.c_iflag |= iflag;
+    termios.c_cc[libc::VMIN] = 0;
+    termios.c_cc[libc::VTIME] = 0;
+    let applied = unsafe {
+        libc::cfsetispeed(&mut termios, speed) == 0
+            && libc::cfsetospeed(&mut termios, speed) == 0
+            && libc::tcsetattr(fd, libc::TCSANOW, &termios) == 0
+            && libc::tcflush(fd, libc::TCIOFLUSH) == 0
+    };
+    if !applied {
+        Err(io::Error::last_os_error())?
+    }
+    Ok(())
+}
+
+impl InstConnection for SerialConn {
+    fn address(&self) -> InstAddr {
+        InstAddr::Visa(self.address.clone())
+    }
+
+    /// The timeout is measured by the reads themselves so it can't fail to apply.
+    fn set_timeout(&mut self, timeout: Duration) -> Result<(), Error> {
+        self.timeout = timeout;
+        Ok(())
+    }
+
+    fn reconnect(&mut self) -> Result<(), Error> {
+        self.port = open_port(&self.address, &self.serial)?;
+        self.pending.clear();
+        self.pending_echoes.clear();
+        Ok(())
+    }
+
+    fn set_read_termination(&mut self, term_bytes: TerminationBytes) -> Result<(), Error> {
+        self.read_term = Some(term_bytes);
+        Ok(())
+    }
+
+    fn set_write_termination(&mut self, term_bytes: TerminationBytes) -> Result<(), Error> {
+        self.write_term = Some(term_bytes);
+        Ok(())
+    }
+
+    fn write_bytes(&mut self, message: &[u8]) -> Result<(), Error> {
+        let term = self.write_term.as_ref().unwrap_or(&TerminationBytes::None);
+        let mut buffer = Vec::with_capacity(message.len() + term.bytes().len());
+        buffer.extend_from_slice(message);
+        buffer.extend_from_slice(term.bytes());
+        self.last_command = Some(message.to_vec());
+        let started = Instant::now();
+        let mut sent = 0;
+        while sent < buffer.len() {
+            let written = self
+                .wait_writable(started)
+                .and_then(|_| self.port.write(&buffer[sent..]));
+            match written {
+                Ok(0) => Err(self.io_error(
+                    io::ErrorKind::WriteZero.into(),
+                    Operation::Write,
+                    "Failed to write to serial port",
+                    started,
+                ))?,
+                Ok(n) => sent += n,
+                Err(e) if is_retryable(&e) => (),
+                Err(e) => Err(self.io_error(
+                    e,
+                    Operation::Write,
+                    "Failed to write to serial port",
+                    started,
+                ))?,
+            }
+        }
+        if self.echo {
+            self.pending_echoes.push_back(message.to_vec());
+        }
+        Ok(())
+    }
+
+    fn read_bytes(&mut self) -> Result<Vec<u8>, Error> {
+        loop {
+            let line = self.read_line()?;
+            match self.pending_echoes.front() {
+                Some(echo) if is_echo(echo, &line) => {
+                    self.pending_echoes.pop_front();
+                }
+                _ => return Ok(line),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("9600,8N1,NONE";"default")]
+    #[test_case("115200,7E2,RTSCTS";"even parity and hardware flow control")]
+    #[test_case("19200,5M1.5,XONXOFF";"mark parity and one and a half stop bits")]
+    fn supported_settings(settings: &str) {
+        assert!(termios_flags(&settings.parse().unwrap()).is_ok());
+    }
+
+    #[test_case("14400,8N1";"baud rate without a termios speed")]
+    #[test_case("9600,8N1.5";"one and a half stop bits with eight data bits")]
+    #[test_case("9600,8N1,DTRDSR";"DTR/DSR flow control")]
+    fn unsupported_settings(settings: &str) {
+        assert!(termios_flags(&settings.parse().unwrap()).is_err());
+    }
+}
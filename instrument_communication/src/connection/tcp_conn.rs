@@ -1,59 +1,246 @@
+use super::scoped_v6;
 use crate::address::InstAddr;
-use crate::connect;
-use crate::termination_bytes::{self, TerminationBytes};
+use crate::binary_block::{self, BlockBuffer, Header};
+use crate::communication::{INFINITE_TIMEOUT, MAXIMUM_BUFFER_SIZE, TERMINATOR_GRACE};
+use crate::err::{Context, Operation};
+use crate::framing::{Frame, Framing};
+use crate::termination_bytes::TerminationBytes;
 use crate::{address::socket::Socket, communication::InstConnection, err::Error};
-use std::net::{Shutdown, SocketAddr, TcpStream, ToSocketAddrs};
-use std::time::Duration;
-///This function allows us to convert the hostname to an ip Address. This prioritizes an IPV4 address.
+use std::borrow::Cow;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream};
+use std::time::{Duration, Instant};
 
+#[allow(non_upper_case_globals)]
 pub static connect_timeout: Duration = Duration::from_secs(2);
 const DEFAULT_BUFFER_SIZE: usize = 4096;
 pub struct TcpConn {
     connection: TcpStream,
     address: Socket,
     buffer_size: usize,
-    term_string: Option<TerminationBytes>,
-    frame_size: Option<usize>,
+    /// Ends the responses that are read.
+    read_term: Option<TerminationBytes>,
+    /// Appended to every message that is written.
+    write_term: Option<TerminationBytes>,
+    /// Delimits messages in place of the terminations when it is set.
+    framing: Option<Box<dyn Framing>>,
     timeout: Duration,
+    /// Bytes received from the instrument that belong to the next response.
+    pending: Vec<u8>,
+    /// The terminator of the last block didn't arrive in time. It is skipped if it is the
+    /// first thing that is read next.
+    stray_term: bool,
+    /// The last message that was written. It is reported in errors.
+    last_command: Option<Vec<u8>>,
 }
 
 impl TcpConn {
     pub fn connect(addr: Socket) -> Result<Box<dyn InstConnection>, Error> {
         let connection = get_tcp_stream(addr.clone())?;
-        Ok(Box::new(TcpConn {
+        let conn = TcpConn {
             connection,
             address: addr,
             buffer_size: DEFAULT_BUFFER_SIZE,
-            term_string: Some(TerminationBytes::default()),
-            frame_size: None,
+            read_term: Some(TerminationBytes::default()),
+            write_term: Some(TerminationBytes::default()),
+            framing: None,
             timeout: connect_timeout,
-        }))
+            pending: Vec::new(),
+            stray_term: false,
+            last_command: None,
+        };
+        conn.apply_timeout()?;
+        Ok(Box::new(conn))
     }
-}
 
-fn get_tcp_stream(addr: Socket) -> Result<TcpStream, Error> {
-    Ok(match addr {
-        Socket::V4(addr) => TcpStream::connect_timeout(&SocketAddr::V4(addr), connect_timeout)
-            .map_err(|e| {
-                Error::ConnectionFailed(format!("Failed to connect. Error message:{:?}", e).into())
-            })?,
-        Socket::V6(addr) => TcpStream::connect_timeout(&SocketAddr::V6(addr), connect_timeout)
+    fn apply_timeout(&self) -> Result<(), Error> {
+        let timeout = if self.timeout == INFINITE_TIMEOUT {
+            None
+        } else {
+            Some(self.timeout)
+        };
+        self.connection
+            .set_read_timeout(timeout)
+            .and_then(|_| self.connection.set_write_timeout(timeout))
             .map_err(|e| {
-                Error::ConnectionFailed(format!("Failed to connect. Error message:{:?}", e).into())
-            })?,
-        Socket::Raw(addr) => {
-            if let Some(addr) = addr.get_ipv4_first() {
-                TcpStream::connect_timeout(&addr, connect_timeout).map_err(|e| {
-                    Error::ConnectionFailed(
-                        format!("Failed to connect. Error message:{:?}", e).into(),
-                    )
-                })?
-            } else {
-                Err(Error::ConnectionFailed(
-                    format!("Unable to connect to hostname: {addr}").into(),
-                ))?
+                Error::FunctionFailure(Box::new(
+                    self.context(Operation::SetAttribute, "Failed to set connection timeout")
+                        .with_io_error(e),
+                ))
+            })
+    }
+
+    fn context(&self, operation: Operation, message: impl Into<Cow<'static, str>>) -> Context {
+        Context::new(InstAddr::Socket(self.address.clone()), operation, message)
+            .with_command(self.last_command.as_deref())
+    }
+
+    /// Timeouts on a socket are reported as either WouldBlock or TimedOut depending on the
+    /// platform.
+    fn io_error(
+        &self,
+        e: io::Error,
+        operation: Operation,
+        message: &'static str,
+        started: Instant,
+    ) -> Error {
+        let timed_out = matches!(
+            e.kind(),
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+        );
+        let context = Box::new(
+            self.context(operation, message)
+                .with_elapsed(started.elapsed())
+                .with_io_error(e),
+        );
+        if timed_out {
+            Error::Timeout(context)
+        } else {
+            Error::FunctionFailure(context)
+        }
+    }
+
+    /// Extracts a complete response from the pending bytes if one has been received.
+    /// `searched` is the number of pending bytes that were already scanned for a frame.
+    fn take_response(&mut self, searched: usize) -> Result<Option<Vec<u8>>, Error> {
+        let mut no_term = TerminationBytes::None;
+        let framing: &mut dyn Framing = match &mut self.framing {
+            Some(framing) => framing.as_mut(),
+            None => self.read_term.as_mut().unwrap_or(&mut no_term),
+        };
+        let frame = match framing.decode(&self.pending, searched) {
+            Ok(Some(frame)) => frame,
+            Ok(None) => return Ok(None),
+            Err(message) => Err(Error::FunctionFailure(Box::new(
+                self.context(Operation::Read, message),
+            )))?,
+        };
+        // Frames of a custom framing are checked so a wrong closure can't panic or stall reads.
+        let Frame { payload, len } = frame;
+        if len == 0 || len > self.pending.len() || payload.start > payload.end || payload.end > len
+        {
+            let message = format!(
+                "Framing returned the payload {payload:?} of a {len} byte frame but {} bytes were received",
+                self.pending.len()
+            );
+            Err(Error::FunctionFailure(Box::new(
+                self.context(Operation::Read, message),
+            )))?
+        }
+        let response = self.pending[payload].to_vec();
+        self.pending.drain(..len);
+        Ok(Some(response))
+    }
+
+    /// Reads once from the socket and appends the bytes to the pending bytes.
+    fn receive(&mut self, chunk: &mut [u8], started: Instant) -> Result<(), Error> {
+        let n = self.read_socket(chunk, started)?;
+        self.pending.extend_from_slice(&chunk[..n]);
+        Ok(())
+    }
+
+    /// Reads once from the socket into `buf` and returns the number of bytes received.
+    fn read_socket(&mut self, buf: &mut [u8], started: Instant) -> Result<usize, Error> {
+        match self.connection.read(buf) {
+            Ok(0) => Err(Error::ConnectionFailed(Box::new(
+                self.context(Operation::Read, "Connection closed by the instrument")
+                    .with_elapsed(started.elapsed()),
+            ))),
+            Ok(n) => Ok(n),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => Ok(0),
+            Err(e) => Err(self.io_error(
+                e,
+                Operation::Read,
+                "Failed to read from instrument",
+                started,
+            )),
+        }
+    }
+
+    /// Drops the terminator of the last block if it is the first pending byte. Returns true if
+    /// it was dropped.
+    fn skip_stray_term(&mut self) -> bool {
+        let term = self
+            .read_term
+            .as_ref()
+            .map_or(&[][..], TerminationBytes::bytes);
+        match binary_block::stray_len(&self.pending, term) {
+            Some(skipped) if self.stray_term => {
+                self.pending.drain(..skipped);
+                self.stray_term = false;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Consumes the terminator after the data of a block if it is pending or arrives within
+    /// the grace period. A terminator that arrives later is skipped by the next read.
+    fn consume_block_term(&mut self, chunk: &mut [u8], started: Instant) -> Result<(), Error> {
+        let term = self.read_term.clone().unwrap_or(TerminationBytes::None);
+        if binary_block::stray_len(&self.pending, term.bytes()).is_none() {
+            let timeout = self.timeout;
+            self.timeout = timeout.min(TERMINATOR_GRACE);
+            let mut received = self.apply_timeout();
+            while received.is_ok() && binary_block::stray_len(&self.pending, term.bytes()).is_none()
+            {
+                received = self.receive(chunk, started);
+            }
+            self.timeout = timeout;
+            self.apply_timeout()?;
+            match received {
+                Err(e) if !e.is_timeout() => Err(e)?,
+                _ => (),
+            }
+        }
+        match binary_block::stray_len(&self.pending, term.bytes()) {
+            Some(skipped) => {
+                self.pending.drain(..skipped);
             }
+            None => self.stray_term = true,
         }
+        Ok(())
+    }
+
+    /// Receives until at least `len` bytes are pending.
+    fn receive_at_least(
+        &mut self,
+        len: usize,
+        chunk: &mut [u8],
+        started: Instant,
+    ) -> Result<(), Error> {
+        while self.pending.len() < len {
+            self.receive(chunk, started)?;
+        }
+        Ok(())
+    }
+}
+
+fn get_tcp_stream(addr: Socket) -> Result<TcpStream, Error> {
+    let started = Instant::now();
+    let result = match &addr {
+        Socket::V4(v4) => TcpStream::connect_timeout(&SocketAddr::V4(*v4), connect_timeout),
+        Socket::V6(v6) => TcpStream::connect_timeout(&SocketAddr::V6(*v6), connect_timeout),
+        Socket::ScopedV6 { ip, zone, port } => scoped_v6(*ip, zone, *port)
+            .and_then(|addr| TcpStream::connect_timeout(&addr, connect_timeout)),
+        Socket::Raw(raw) => match raw.get_ipv4_first() {
+            Some(ip) => TcpStream::connect_timeout(&ip, connect_timeout),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Unable to resolve hostname: {raw}"),
+            )),
+        },
+    };
+    result.map_err(|e| {
+        Error::ConnectionFailed(Box::new(
+            Context::new(
+                InstAddr::Socket(addr),
+                Operation::Connect,
+                "Failed to connect",
+            )
+            .with_elapsed(started.elapsed())
+            .with_io_error(e),
+        ))
     })
 }
 
@@ -62,22 +249,10 @@ impl InstConnection for TcpConn {
         InstAddr::Socket(self.address.clone())
     }
 
-    fn set_timeout(&self, timeout: Duration) -> Result<(), Error> {
-        self.connection
-            .set_read_timeout(Some(timeout))
-            .map_err(|e| {
-                Error::FunctionFailure(
-                    format!("Failed to set connection timeout. Error: {e}").into(),
-                )
-            })?;
-        self.connection
-            .set_write_timeout(Some(timeout))
-            .map_err(|e| {
-                Error::FunctionFailure(
-                    format!("Failed to set connection timeout. Error: {e}").into(),
-                )
-            })?;
-        Ok(())
+    fn set_timeout(&mut self, timeout: Duration) -> Result<(), Error> {
+        let previous = std::mem::replace(&mut self.timeout, timeout);
+        self.apply_timeout()
+            .inspect_err(|_| self.timeout = previous)
     }
 
     fn reconnect(&mut self) -> Result<(), Error> {
@@ -87,17 +262,329 @@ impl InstConnection for TcpConn {
             .map_err(|e| log::error!("{e}"));
         let conn = get_tcp_stream(self.address.clone())?;
         self.connection = conn;
-        Ok(())
+        self.pending.clear();
+        self.stray_term = false;
+        self.apply_timeout()
     }
 
-    fn set_termination(&mut self, term_bytes: TerminationBytes) -> Result<(), Error> {
+    fn set_read_termination(&mut self, term_bytes: TerminationBytes) -> Result<(), Error> {
         match term_bytes {
-            TerminationBytes::None=> match self.frame_size  {
-                None=>Err(Error::ConflictingSettings(format!("Cannot set no termination when frame size is not fixed. We will not know when to return. Typically this is not a problem but some instruments might send data in bursts and an early return will cause a problem.").into()))?,
-                _=>self.term_string=Some(term_bytes),
-            }
-            _=> self.term_string=Some(term_bytes),
+            TerminationBytes::None if self.framing.is_none() => Err(Error::ConflictingSettings("Cannot set no termination when no framing is set. We will not know when to return. Typically this is not a problem but some instruments might send data in bursts and an early return will cause a problem.".into()))?,
+            TerminationBytes::None => (),
+            // A termination replaces the framing.
+            _ => self.framing = None,
         }
+        self.read_term = Some(term_bytes);
+        Ok(())
+    }
+
+    fn set_write_termination(&mut self, term_bytes: TerminationBytes) -> Result<(), Error> {
+        self.write_term = Some(term_bytes);
         Ok(())
     }
+
+    /// Replaces the terminations until a read termination other than `None` is set.
+    fn set_framing(&mut self, framing: Box<dyn Framing>) -> Result<(), Error> {
+        self.framing = Some(framing);
+        Ok(())
+    }
+
+    fn write_bytes(&mut self, message: &[u8]) -> Result<(), Error> {
+        let mut no_term = TerminationBytes::None;
+        let framing: &mut dyn Framing = match &mut self.framing {
+            Some(framing) => framing.as_mut(),
+            None => self.write_term.as_mut().unwrap_or(&mut no_term),
+        };
+        let mut buffer = Vec::with_capacity(message.len() + 4);
+        let encoded = framing.encode(message, &mut buffer);
+        self.last_command = Some(message.to_vec());
+        encoded
+            .map_err(|msg| Error::FunctionFailure(Box::new(self.context(Operation::Write, msg))))?;
+        let started = Instant::now();
+        self.connection.write_all(&buffer).map_err(|e| {
+            self.io_error(
+                e,
+                Operation::Write,
+                "Failed to write to instrument",
+                started,
+            )
+        })
+    }
+
+    fn read_bytes(&mut self) -> Result<Vec<u8>, Error> {
+        let mut chunk = vec![0u8; self.buffer_size];
+        let mut searched = 0;
+        let started = Instant::now();
+        loop {
+            if self.skip_stray_term() {
+                searched = 0;
+            }
+            if let Some(response) = self.take_response(searched)? {
+                return Ok(response);
+            }
+            if self.pending.len() + chunk.len() > MAXIMUM_BUFFER_SIZE {
+                // The partial response is discarded so the next read doesn't continue it.
+                self.pending.clear();
+                let message =
+                    format!("Response exceeded the maximum size of {MAXIMUM_BUFFER_SIZE} bytes");
+                let context = self
+                    .context(Operation::Read, message)
+                    .with_elapsed(started.elapsed());
+                Err(Error::FunctionFailure(Box::new(context)))?
+            }
+            searched = self.pending.len();
+            self.receive(&mut chunk, started)?;
+        }
+    }
+
+    /// Reads exactly the declared length of a definite block so termination bytes inside the
+    /// data don't end it. The data is read from the socket straight into `buffer`. A socket has
+    /// no END signal so an indefinite block ends at the first termination bytes. The terminator
+    /// after a definite block is only waited for briefly since some instruments send none.
+    fn read_binary_block_into(&mut self, buffer: &mut dyn BlockBuffer) -> Result<usize, Error> {
+        // A block inside a frame is read with the frame.
+        if self.framing.is_some() {
+            let response = self.read_bytes()?;
+            let data = binary_block::decode(&response)?;
+            buffer.alloc(data.len()).copy_from_slice(data);
+            return Ok(data.len());
+        }
+        let mut chunk = vec![0u8; self.buffer_size];
+        let started = Instant::now();
+        let stray = if std::mem::take(&mut self.stray_term) {
+            self.read_term.clone().unwrap_or(TerminationBytes::None)
+        } else {
+            TerminationBytes::None
+        };
+        let (start, len) = loop {
+            match binary_block::parse_header(&self.pending, stray.bytes())? {
+                Header::Incomplete(needed) => self.receive_at_least(needed, &mut chunk, started)?,
+                Header::Definite { start, len } => break (start, len),
+                Header::Indefinite { start } => {
+                    self.pending.drain(..start);
+                    let data = self.read_bytes()?;
+                    buffer.alloc(data.len()).copy_from_slice(&data);
+                    return Ok(data.len());
+                }
+            }
+        };
+        if len > MAXIMUM_BUFFER_SIZE {
+            // The received part of the block is discarded so the next read doesn't start in it.
+            self.pending.clear();
+            let message = format!(
+                "Binary block of {len} bytes exceeds the maximum size of {MAXIMUM_BUFFER_SIZE} bytes"
+            );
+            let context = self
+                .context(Operation::Read, message)
+                .with_elapsed(started.elapsed());
+            Err(Error::FunctionFailure(Box::new(context)))?
+        }
+        self.pending.drain(..start);
+        let data = buffer.alloc(len);
+        let mut filled = self.pending.len().min(len);
+        data[..filled].copy_from_slice(&self.pending[..filled]);
+        self.pending.drain(..filled);
+        while filled < len {
+            filled += self.read_socket(&mut data[filled..], started)?;
+        }
+        self.consume_block_term(&mut chunk, started)?;
+        Ok(len)
+    }
+}
+
+impl Drop for TcpConn {
+    fn drop(&mut self) {
+        if let Err(e) = self.connection.shutdown(Shutdown::Both) {
+            log::debug!(
+                "Failed to shut down connection to {}. Error: {e}",
+                self.address.ip_or_host()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::test_support;
+    use crate::framing::{Custom, FixedSize, LengthPrefixed};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// A server that sends every chunk of `replies` separately after a command is received.
+    fn serve(replies: Vec<&'static [u8]>) -> Socket {
+        serve_paced(replies, Duration::from_millis(20))
+    }
+
+    /// Like [`serve`] but waits `pause` after every chunk.
+    fn serve_paced(replies: Vec<&'static [u8]>, pause: Duration) -> Socket {
+        let (port, _) = test_support::serve(replies, pause);
+        format!("127.0.0.1:{port}").parse().unwrap()
+    }
+
+    /// Starts a single client server on an ephemeral port that sends back everything it
+    /// receives.
+    fn echo() -> Socket {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0u8; 1024];
+            while let Ok(n @ 1..) = stream.read(&mut buf) {
+                stream.write_all(&buf[..n]).unwrap();
+            }
+        });
+        format!("127.0.0.1:{port}").parse().unwrap()
+    }
+
+    #[test]
+    fn query_strips_termination() {
+        let mut conn = TcpConn::connect(serve(vec![b"Cosmere,mock1000\n"])).unwrap();
+        assert_eq!(conn.query("*IDN?").unwrap(), "Cosmere,mock1000");
+    }
+
+    #[test]
+    fn multi_byte_termination_split_across_packets() {
+        let mut conn = TcpConn::connect(serve(vec![b"1.25\r", b"\n2.5\r\n"])).unwrap();
+        conn.set_termination(TerminationBytes::CRLF).unwrap();
+        assert_eq!(conn.query("MEAS?").unwrap(), "1.25");
+        assert_eq!(conn.read().unwrap(), "2.5");
+    }
+
+    #[test]
+    fn binary_block_reads_past_termination_in_data() {
+        let replies = vec![&b"#212\n\n34"[..], b"5\r\n6789\n\n", b"next\n"];
+        let mut conn = TcpConn::connect(serve(replies)).unwrap();
+        assert_eq!(
+            conn.query_binary_block("CURV?").unwrap(),
+            b"\n\n345\r\n6789\n"
+        );
+        assert_eq!(conn.read().unwrap(), "next");
+    }
+
+    #[test]
+    fn binary_block_without_terminator_is_returned() {
+        let mut conn = TcpConn::connect(serve(vec![b"#13abc"])).unwrap();
+        let started = Instant::now();
+        assert_eq!(conn.query_binary_block("CURV?").unwrap(), b"abc");
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn late_terminator_of_a_binary_block_is_skipped() {
+        let replies = vec![&b"#13abc"[..], b"\n#12de", b"\n", b"next\n"];
+        let mut conn = TcpConn::connect(serve_paced(replies, Duration::from_millis(200))).unwrap();
+        assert_eq!(conn.query_binary_block("CURV?").unwrap(), b"abc");
+        assert_eq!(conn.read_binary_block().unwrap(), b"de");
+        assert_eq!(conn.read().unwrap(), "next");
+    }
+
+    #[test]
+    fn oversized_binary_block_is_rejected_before_reading() {
+        let mut conn = TcpConn::connect(serve(vec![b"#9100000000", b"next\n"])).unwrap();
+        let err = conn.query_binary_block("CURV?").unwrap_err();
+        assert!(matches!(err, Error::FunctionFailure(_)));
+        assert!(err.to_string().ends_with(
+            "Binary block of 100000000 bytes exceeds the maximum size of 50000000 bytes"
+        ));
+        assert_eq!(conn.read().unwrap(), "next");
+    }
+
+    #[test]
+    fn binary_values_are_decoded_from_split_packets() {
+        use crate::binary_block::ByteOrder;
+        use crate::communication::BinaryValues;
+        let replies = vec![&b"#18\x00\x0A\xFF"[..], b"\xFE\x01", b"\x00\x00\x00\n"];
+        let mut conn = TcpConn::connect(serve(replies)).unwrap();
+        let values = conn
+            .query_binary_values::<i16>("CURV?", ByteOrder::BigEndian)
+            .unwrap();
+        assert_eq!(values, [10, -2, 256, 0]);
+    }
+
+    #[test]
+    fn read_and_write_terminations_are_independent() {
+        let mut conn = TcpConn::connect(echo()).unwrap();
+        conn.set_write_termination(TerminationBytes::Custom(b"\r\r\n".to_vec()))
+            .unwrap();
+        conn.set_read_termination(TerminationBytes::CRLF).unwrap();
+        assert_eq!(conn.query("*IDN?").unwrap(), "*IDN?\r");
+    }
+
+    #[test]
+    fn length_prefixed_frames_keep_terminators() {
+        let mut conn = TcpConn::connect(echo()).unwrap();
+        conn.set_framing(Box::new(LengthPrefixed::U16)).unwrap();
+        conn.set_read_termination(TerminationBytes::None).unwrap();
+        conn.write_bytes(b"\x01\n").unwrap();
+        conn.write_bytes(b"\r\n\x02").unwrap();
+        assert_eq!(conn.read_bytes().unwrap(), b"\x01\n");
+        assert_eq!(conn.read_bytes().unwrap(), b"\r\n\x02");
+    }
+
+    #[test]
+    fn fixed_size_frames_are_checked_on_write() {
+        let mut conn = TcpConn::connect(echo()).unwrap();
+        conn.set_framing(Box::new(FixedSize::new(4).unwrap()))
+            .unwrap();
+        let err = conn.write_bytes(b"abc").unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("Message of 3 bytes doesn't match the frame size of 4 bytes"));
+        assert_eq!(conn.query_bytes("a\nbc").unwrap(), b"a\nbc");
+    }
+
+    #[test]
+    fn frames_past_the_received_bytes_are_rejected() {
+        let mut conn = TcpConn::connect(echo()).unwrap();
+        let framing = Custom::new(|received: &[u8]| {
+            (!received.is_empty()).then(|| Frame {
+                payload: 0..received.len() + 1,
+                len: received.len() + 1,
+            })
+        });
+        conn.set_framing(Box::new(framing)).unwrap();
+        let err = conn.query_bytes("abc").unwrap_err();
+        assert!(matches!(err, Error::FunctionFailure(_)));
+        assert!(err.to_string().contains("byte frame but"));
+    }
+
+    #[test]
+    fn response_without_terminator_stops_at_the_maximum_size() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let chunk = vec![b'x'; 1 << 20];
+            while stream.write_all(&chunk).is_ok() {}
+        });
+        let mut conn = TcpConn::connect(format!("127.0.0.1:{port}").parse().unwrap()).unwrap();
+        let err = conn.read_bytes().unwrap_err();
+        assert!(matches!(err, Error::FunctionFailure(_)));
+        assert!(err
+            .to_string()
+            .ends_with("Response exceeded the maximum size of 50000000 bytes"));
+    }
+
+    #[test]
+    fn read_without_response_times_out() {
+        let mut conn = TcpConn::connect(serve(vec![])).unwrap();
+        conn.set_timeout(Duration::from_millis(100)).unwrap();
+        conn.write("*RST").unwrap();
+        let err = conn.read_bytes().unwrap_err();
+        assert!(matches!(err, Error::Timeout(_)));
+        assert_eq!(err.context().unwrap().command.as_deref(), Some("*RST"));
+    }
+
+    #[test]
+    fn unknown_zone_fails_when_connecting() {
+        let address: Socket = "[fe80::1%no-such-interface0]:5025".parse().unwrap();
+        let Err(Error::ConnectionFailed(context)) = TcpConn::connect(address) else {
+            panic!("the zone doesn't name an interface");
+        };
+        assert_eq!(
+            context.io_error().unwrap().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
 }
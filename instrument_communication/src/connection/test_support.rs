@@ -0,0 +1,28 @@
+//! Stand-in instruments for the unit tests of the connections.
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+/// Starts a single client server on an ephemeral port of the loopback interface and returns
+/// the port. Every chunk in `replies` is sent separately, `pause` apart, after a command is
+/// received. The command is passed on through the channel.
+pub(crate) fn serve(replies: Vec<&'static [u8]>, pause: Duration) -> (u16, Receiver<Vec<u8>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut buf = [0u8; 1024];
+        let n = stream.read(&mut buf).unwrap_or(0);
+        let _ = sender.send(buf[..n].to_vec());
+        for reply in replies {
+            stream.write_all(reply).unwrap();
+            stream.flush().unwrap();
+            thread::sleep(pause);
+        }
+        thread::sleep(Duration::from_secs(1));
+    });
+    (port, receiver)
+}
@@ -1,45 +1,61 @@
+use crate::address::visa_serial::{FlowControl, Parity, SerialSettings, StopBits};
 use crate::address::{InstAddr, VisaAddress, VisaType};
-use crate::communication::InstConnection;
-use crate::err::Error;
+use crate::binary_block::{self, BlockBuffer, Header};
+use crate::communication::{InstConnection, MAXIMUM_BUFFER_SIZE, TERMINATOR_GRACE};
+use crate::err::{Context, Error, Operation};
 use crate::termination_bytes::TerminationBytes;
 use dlopen::wrapper::Container;
 use lazy_static::*;
 use log::error;
 use std::borrow::Cow;
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
 use visa::*;
 
-const MAXIMUM_BUFFER_SIZE: usize = 50000000;
 const DEFAULT_BUFFER_SIZE: usize = 4096;
-const ERR_MSG_BUFFER_SIZE: usize = 512;
+
+/// A loaded binary and its default resource manager if one is currently open. The resource
+/// manager is only referenced weakly so that it closes when its last session is dropped.
+struct LoadedBinary {
+    visa: Arc<Container<VisaFuncs>>,
+    rm: Weak<ResourceManager>,
+}
 
 lazy_static! {
     pub static ref DEFAULT_BINARY: Mutex<Binary> = Mutex::new(Binary::Primary);
-    static ref VISA_DICTIONARY: Mutex<HashMap<Binary, Result<(Arc<Container<VisaFuncs>>, ViSession), Error>>> =
+    static ref VISA_DICTIONARY: Mutex<HashMap<Binary, Result<LoadedBinary, Error>>> =
         Mutex::new(HashMap::new());
 }
+
 pub struct VisaConn {
-    visa: Arc<Container<VisaFuncs>>,
+    rm: Arc<ResourceManager>,
     bin: Binary,
     address: VisaAddress,
     buffer_size: usize,
-    session: u32,
-    term_string: Option<TerminationBytes>,
-    frame_size: Option<usize>,
+    /// The instrument session. It is only `None` while reconnecting.
+    session: Option<Session>,
+    /// Ends the responses that are read.
+    read_term: Option<TerminationBytes>,
+    /// Appended to every message that is written.
+    write_term: Option<TerminationBytes>,
     is_term_char_attr_set: bool,
+    /// The terminator of the last block didn't arrive in time. It is skipped if it is the
+    /// first thing that is read next.
+    stray_term: bool,
     timeout: Duration,
+    /// The last message that was written. It is reported in errors.
+    last_command: Option<Vec<u8>>,
 }
 
 impl VisaConn {
-    ///
+    /// Sets the binary used by connections that don't explicitly override it.
     pub fn set_default_binary(binary: Binary) {
         let mut guard = DEFAULT_BINARY.lock().unwrap();
         *guard = binary;
     }
 
-    fn get_default_binary() -> Binary {
+    pub(crate) fn get_default_binary() -> Binary {
         match DEFAULT_BINARY.lock() {
             Ok(bin) => (*bin).clone(),
             Err(_) => Binary::default(),
@@ -51,36 +67,99 @@ impl VisaConn {
             Some(b) => b,
             None => VisaConn::get_default_binary(),
         };
-        let lib = try_load_binary(binary.clone())?;
-        let mut vi = 0;
-        match lib.0.viOpen(lib.1, addr.clone().into(), 0, 0, &mut vi) {
-            status if status < 0 => {
-                let msg = get_error_code(&lib.0, vi, status).unwrap_or("Failed to connect".into());
-                return Err(Error::ConnectionFailed(msg));
-            }
-            _ => (),
-        };
-        match lib.0.viClear(vi) {
-            status if status < 0 => {
-                let msg = get_error_code(&lib.0, vi, status).unwrap_or("Failed to Clear, which indicate that most likely no usable instrument exists on this address even if it opens.".into());
-                return Err(Error::ConnectionFailed(msg));
-            }
-            _ => (),
-        };
+        let rm = try_load_binary(binary.clone())?;
+        let session = open_session(&rm, &addr)?;
         let mut visa_conn = VisaConn {
-            visa: lib.0,
+            rm,
             bin: binary,
             address: addr,
             buffer_size: DEFAULT_BUFFER_SIZE,
-            session: vi,
-            term_string: None,
-            frame_size: None,
+            session: Some(session),
+            read_term: None,
+            write_term: None,
             is_term_char_attr_set: false,
+            stray_term: false,
             timeout: Duration::from_secs(2),
+            last_command: None,
         };
+        visa_conn.apply_timeout(visa_conn.timeout)?;
+        visa_conn.apply_serial_settings()?;
         visa_conn.set_termination(TerminationBytes::LF)?;
         Ok(visa_conn)
     }
+
+    fn session(&self) -> Result<&Session, Error> {
+        self.session.as_ref().ok_or_else(|| {
+            Error::ConnectionFailed(Box::new(
+                self.context(Operation::Connect, "Session is not open"),
+            ))
+        })
+    }
+
+    fn context(&self, operation: Operation, message: impl Into<Cow<'static, str>>) -> Context {
+        Context::new(self.address(), operation, message).with_command(self.last_command.as_deref())
+    }
+
+    /// The VISA binary that this connection was opened with.
+    pub fn binary(&self) -> &Binary {
+        &self.bin
+    }
+
+    fn apply_timeout(&self, timeout: Duration) -> Result<(), Error> {
+        let millis = u32::try_from(timeout.as_millis()).unwrap_or(visa::VI_TMO_INFINITE);
+        let session = self.session()?;
+        session.set::<attr::TmoValue>(millis).map_err(|e| {
+            visa_error(
+                &self.rm,
+                e,
+                self.context(Operation::SetAttribute, "Failed to set timeout"),
+            )
+        })
+    }
+
+    /// Addresses a GPIB instrument and sends it local lockout so its front panel is disabled.
+    pub fn local_lockout(&self) -> Result<(), Error> {
+        self.control_ren(
+            VI_GPIB_REN_ASSERT_ADDRESS_LLO,
+            "Failed to send local lockout",
+        )
+    }
+
+    /// Addresses a GPIB instrument and sends it go-to-local so its front panel is enabled.
+    pub fn go_to_local(&self) -> Result<(), Error> {
+        self.control_ren(VI_GPIB_REN_ADDRESS_GTL, "Failed to send go to local")
+    }
+
+    fn control_ren(&self, mode: u32, message: &'static str) -> Result<(), Error> {
+        self.session()?
+            .gpib_control_ren(mode as u16)
+            .map_err(|e| visa_error(&self.rm, e, self.context(Operation::GpibControl, message)))
+    }
+
+    /// Serial addresses carry their line settings which map onto the `VI_ATTR_ASRL_*`
+    /// attributes.
+    fn apply_serial_settings(&self) -> Result<(), Error> {
+        let Some(serial) = self.address.as_serial() else {
+            return Ok(());
+        };
+        let (baud_rate, data_bits, parity, stop_bits, flow_control) =
+            asrl_attributes(&serial.settings);
+        let session = self.session()?;
+        session
+            .set::<attr::AsrlBaud>(baud_rate)
+            .and_then(|_| session.set::<attr::AsrlDataBits>(data_bits))
+            .and_then(|_| session.set::<attr::AsrlParity>(parity))
+            .and_then(|_| session.set::<attr::AsrlStopBits>(stop_bits))
+            .and_then(|_| session.set::<attr::AsrlFlowCntrl>(flow_control))
+            .map_err(|e| {
+                visa_error(
+                    &self.rm,
+                    e,
+                    self.context(Operation::SetAttribute, "Failed to apply serial settings"),
+                )
+            })
+    }
+
     /// Checks if we should avoid enabling the term character attribute in the VISA driver.
     /// GPIB could have legacy equipment that sends binary data, which might have the
     /// termination character as a false positive. Moreover, GPIB has special signaling that
@@ -91,41 +170,183 @@ impl VisaConn {
     }
 
     fn disable_term_char(&mut self) -> Result<(), Error> {
-        Ok(
-            match self
-                .visa
-                .viSetAttribute(self.session, visa::VI_ATTR_TERMCHAR_EN, 0)
-            {
-                status if status < 0 => {
-                    let msg = get_error_code(&self.visa, self.session, status)
-                        .unwrap_or_else(|| "Failed to disable termination char".into());
-                    Err(Error::FunctionFailure(msg))?
+        let session = self.session()?;
+        session.set::<attr::TermcharEn>(false).map_err(|e| {
+            visa_error(
+                &self.rm,
+                e,
+                self.context(
+                    Operation::SetAttribute,
+                    "Failed to disable termination char",
+                ),
+            )
+        })?;
+        self.is_term_char_attr_set = false;
+        Ok(())
+    }
+
+    /// Reads until `buf` is full or the instrument signals END. Returns the number of bytes
+    /// received and whether the last one carried END.
+    fn read_into(&self, buf: &mut [u8], started: Instant) -> Result<(usize, bool), Error> {
+        let session = self.session()?;
+        let mut filled = 0;
+        while filled < buf.len() {
+            let (count, status) = session.read(&mut buf[filled..]).map_err(|e| {
+                let context = self
+                    .context(Operation::Read, "Failed to read from instrument")
+                    .with_elapsed(started.elapsed());
+                visa_error(&self.rm, e, context)
+            })?;
+            filled += count;
+            if status != VisaStatus::SUCCESS_MAX_CNT {
+                return Ok((filled, true));
+            }
+        }
+        Ok((filled, false))
+    }
+
+    /// Reads a block into `buffer` while the term char is disabled.
+    fn read_block(
+        &mut self,
+        buffer: &mut dyn BlockBuffer,
+        started: Instant,
+    ) -> Result<usize, Error> {
+        let ended_early = |this: &VisaConn, received: usize, expected: usize| {
+            let message = format!("Binary block ended after {received} of {expected} bytes");
+            let context = this
+                .context(Operation::Read, message)
+                .with_elapsed(started.elapsed());
+            Error::FunctionFailure(Box::new(context))
+        };
+        let stray = if std::mem::take(&mut self.stray_term) {
+            self.read_term.clone().unwrap_or(TerminationBytes::None)
+        } else {
+            TerminationBytes::None
+        };
+        let mut header = Vec::new();
+        let len = loop {
+            match binary_block::parse_header(&header, stray.bytes())? {
+                Header::Incomplete(needed) => {
+                    let received = header.len();
+                    header.resize(needed, 0);
+                    let (count, _) = self.read_into(&mut header[received..], started)?;
+                    if received + count < needed {
+                        Err(ended_early(self, received + count, needed))?
+                    }
                 }
-                _ => (),
-            },
-        )
+                Header::Definite { len, .. } => break len,
+                // The rest of the message up to END is the data.
+                Header::Indefinite { .. } => {
+                    let data = self.read_bytes()?;
+                    buffer.alloc(data.len()).copy_from_slice(&data);
+                    return Ok(data.len());
+                }
+            }
+        };
+        if len > MAXIMUM_BUFFER_SIZE {
+            let message = format!(
+                "Binary block of {len} bytes exceeds the maximum size of {MAXIMUM_BUFFER_SIZE} bytes"
+            );
+            let context = self
+                .context(Operation::Read, message)
+                .with_elapsed(started.elapsed());
+            Err(Error::FunctionFailure(Box::new(context)))?
+        }
+        let (count, ended) = self.read_into(buffer.alloc(len), started)?;
+        if count < len {
+            Err(ended_early(self, count, len))?
+        }
+        let term_len = self.read_term.as_ref().map_or(0, |term| term.bytes().len());
+        // The block is returned even if an instrument that doesn't send END never terminates it.
+        // The terminator is only waited for briefly and skipped by the next read if it is late.
+        if !ended && term_len > 0 {
+            self.apply_timeout(self.timeout.min(TERMINATOR_GRACE))?;
+            let received = self.read_into(&mut vec![0u8; term_len], started);
+            self.apply_timeout(self.timeout)?;
+            match received {
+                Err(e) if e.is_timeout() => self.stray_term = true,
+                Err(e) => Err(e)?,
+                Ok(_) => (),
+            }
+        }
+        Ok(len)
     }
 }
 
-fn get_error_code(
-    lib: &Arc<Container<VisaFuncs>>,
-    vi: u32,
-    status: i32,
-) -> Option<Cow<'static, str>> {
-    let mut resp = vec![0u8; ERR_MSG_BUFFER_SIZE];
-    match lib.viStatusDesc(vi, status, resp.as_mut_ptr()) {
-        0 => {
-            if let Ok(msg) = std::str::from_utf8(&resp) {
-                Some(msg.to_owned().into())
-            } else {
-                None
+/// The values of the baud rate, data bits, parity, stop bits and flow control attributes.
+fn asrl_attributes(settings: &SerialSettings) -> (u32, u16, u16, u16, u16) {
+    let parity = match settings.parity {
+        Parity::None => VI_ASRL_PAR_NONE,
+        Parity::Odd => VI_ASRL_PAR_ODD,
+        Parity::Even => VI_ASRL_PAR_EVEN,
+        Parity::Mark => VI_ASRL_PAR_MARK,
+        Parity::Space => VI_ASRL_PAR_SPACE,
+    };
+    let stop_bits = match settings.stop_bits {
+        StopBits::One => VI_ASRL_STOP_ONE,
+        StopBits::OneAndHalf => VI_ASRL_STOP_ONE5,
+        StopBits::Two => VI_ASRL_STOP_TWO,
+    };
+    let flow_control = match settings.flow_control {
+        FlowControl::None => VI_ASRL_FLOW_NONE,
+        FlowControl::XonXoff => VI_ASRL_FLOW_XON_XOFF,
+        FlowControl::RtsCts => VI_ASRL_FLOW_RTS_CTS,
+        FlowControl::DtrDsr => VI_ASRL_FLOW_DTR_DSR,
+    };
+    (
+        settings.baud_rate,
+        settings.data_bits as u16,
+        parity as u16,
+        stop_bits as u16,
+        flow_control as u16,
+    )
+}
+
+/// Opens a session to the address through the resource manager then clears the device.
+fn open_session(rm: &Arc<ResourceManager>, addr: &VisaAddress) -> Result<Session, Error> {
+    let started = Instant::now();
+    let context = |message: &'static str| {
+        Context::new(InstAddr::Visa(addr.clone()), Operation::Connect, message)
+            .with_elapsed(started.elapsed())
+    };
+    let session = rm
+        .open(addr.as_str())
+        .map_err(|e| visa_error(rm, e, context("Failed to connect")))?;
+    session.clear().map_err(|e| visa_error(rm, e, context("Failed to Clear, which indicate that most likely no usable instrument exists on this address even if it opens.")))?;
+    Ok(session)
+}
+
+/// Converts an error of the visa crate into an error of this crate. Timeouts become
+/// [`Error::Timeout`] like those of the other connections and keep the status in their
+/// context. Vendor specific statuses are not known by [`VisaStatus`] so the binary is asked to
+/// describe them.
+pub(crate) fn visa_error(
+    rm: &ResourceManager,
+    err: visa::err::Error,
+    mut context: Context,
+) -> Error {
+    match err {
+        visa::err::Error::Status(VisaStatus::ERROR_TMO) => {
+            Error::Timeout(Box::new(context.with_visa_status(VisaStatus::ERROR_TMO)))
+        }
+        visa::err::Error::Status(status) => {
+            if status.description().is_none() {
+                if let Some(desc) = rm.status_description(status) {
+                    context.message = format!("{}. {desc}", context.message).into();
+                }
             }
+            Error::Visa(status, Box::new(context))
+        }
+        err => {
+            context.message = format!("{}. Error: {err}", context.message).into();
+            Error::FunctionFailure(Box::new(context))
         }
-        n => None,
     }
 }
 
-fn try_load_binary(binary: Binary) -> Result<(Arc<Container<VisaFuncs>>, u32), Error> {
+/// Returns the default resource manager of the binary. The binary is loaded on first use and
+/// the resource manager is opened again if every previous session was closed.
+pub(crate) fn try_load_binary(binary: Binary) -> Result<Arc<ResourceManager>, Error> {
     let mut mutx_grd = match VISA_DICTIONARY.lock() {
         Ok(m) => m,
         Err(e) => {
@@ -133,31 +354,26 @@ fn try_load_binary(binary: Binary) -> Result<(Arc<Container<VisaFuncs>>, u32), E
             e.into_inner()
         }
     };
-    let visa = match mutx_grd.get(&binary) {
-        Some(visa_lib) => visa_lib,
-        None => {
-            match visa::create(&binary) {
-                Ok(lib) => {
-                    let mut vi_session: u32 = 0;
-                    let status = lib.viOpenDefaultRM(&mut vi_session);
-                    if vi_session != 0 {
-                        mutx_grd.insert(binary.clone(), Ok((Arc::new(lib), vi_session)));
-                    } else {
-                        mutx_grd.insert(binary.clone(), Err(Error::OpenSessionError(format!("visa session did not instantiate properly. visa dll exists but there might be a missing dependancy. status error code: {status}").into())));
-                    }
-                }
-                Err(err) => {
-                    mutx_grd.insert(
-                        binary.clone(),
-                        Err(Error::BinaryError(format!("{:?}", err).into())),
-                    );
-                }
+    let loaded = mutx_grd.entry(binary).or_insert_with_key(|binary| {
+        visa::create(binary)
+            .map(|lib| LoadedBinary {
+                visa: Arc::new(lib),
+                rm: Weak::new(),
+            })
+            .map_err(|err| Error::BinaryError(format!("{:?}", err).into()))
+    });
+    match loaded {
+        Ok(loaded) => match loaded.rm.upgrade() {
+            Some(rm) => Ok(rm),
+            None => {
+                let rm = ResourceManager::open_default(loaded.visa.clone()).map_err(|e| {
+                    Error::OpenSessionError(format!("visa session did not instantiate properly. visa dll exists but there might be a missing dependancy. Error: {e}").into())
+                })?;
+                let rm = Arc::new(rm);
+                loaded.rm = Arc::downgrade(&rm);
+                Ok(rm)
             }
-            mutx_grd.get(&binary).unwrap()
-        }
-    };
-    match visa {
-        Ok(v) => Ok(v.clone()),
+        },
         Err(e) => Err(e.clone()),
     }
 }
@@ -167,50 +383,244 @@ impl InstConnection for VisaConn {
         InstAddr::Visa(self.address.clone())
     }
 
-    fn set_timeout(&self, timeout: Duration) -> Result<(), Error> {
-        todo!()
+    fn set_timeout(&mut self, timeout: Duration) -> Result<(), Error> {
+        self.apply_timeout(timeout)?;
+        self.timeout = timeout;
+        Ok(())
     }
 
     fn reconnect(&mut self) -> Result<(), Error> {
-        todo!()
+        // The old session is closed before opening a new one since some resources only allow
+        // a single session.
+        self.session = None;
+        self.stray_term = false;
+        self.session = Some(open_session(&self.rm, &self.address)?);
+        self.apply_timeout(self.timeout)?;
+        self.apply_serial_settings()?;
+        let term_bytes = self.read_term.clone().unwrap_or_default();
+        self.set_read_termination(term_bytes)
     }
 
-    fn set_termination(&mut self, term_bytes: TerminationBytes) -> Result<(), Error> {
+    /// Only the last byte of the termination can be programmed as the term char of the driver.
+    /// A read that stops on it continues until the whole termination was received.
+    fn set_read_termination(&mut self, term_bytes: TerminationBytes) -> Result<(), Error> {
         if VisaConn::should_avoid_term_char(self.address.get_type()) {
             self.disable_term_char()?;
         } else if let Some(last_byte) = term_bytes.bytes().last() {
-            match self
-                .visa
-                .viSetAttribute(self.session, visa::VI_ATTR_TERMCHAR, *last_byte as u64)
-            {
-                status if status < 0 => {
-                    let msg = get_error_code(&self.visa, self.session, status)
-                        .unwrap_or_else(|| "Failed to set termination char".into());
-                    Err(Error::FunctionFailure(msg))?
-                }
-                _ => (),
+            let session = self.session()?;
+            session.set::<attr::Termchar>(*last_byte).map_err(|e| {
+                visa_error(
+                    &self.rm,
+                    e,
+                    self.context(Operation::SetAttribute, "Failed to set termination char"),
+                )
+            })?;
+            session.set::<attr::TermcharEn>(true).map_err(|e| {
+                visa_error(
+                    &self.rm,
+                    e,
+                    self.context(Operation::SetAttribute, "Failed to enable termination char"),
+                )
+            })?;
+            self.is_term_char_attr_set = true;
+        } else {
+            self.disable_term_char()?;
+        }
+        self.read_term = Some(term_bytes);
+        Ok(())
+    }
+
+    fn set_write_termination(&mut self, term_bytes: TerminationBytes) -> Result<(), Error> {
+        self.write_term = Some(term_bytes);
+        Ok(())
+    }
+
+    fn write_bytes(&mut self, message: &[u8]) -> Result<(), Error> {
+        let term = self.write_term.as_ref().unwrap_or(&TerminationBytes::None);
+        let mut buffer = Vec::with_capacity(message.len() + term.bytes().len());
+        buffer.extend_from_slice(message);
+        buffer.extend_from_slice(term.bytes());
+        self.last_command = Some(message.to_vec());
+        let started = Instant::now();
+        let session = self.session()?;
+        session.write_all(&buffer).map_err(|e| {
+            let context = self
+                .context(Operation::Write, "Failed to write to instrument")
+                .with_elapsed(started.elapsed());
+            visa_error(&self.rm, e, context)
+        })
+    }
+
+    fn read_bytes(&mut self) -> Result<Vec<u8>, Error> {
+        let session = self.session()?;
+        let started = Instant::now();
+        let mut response: Vec<u8> = Vec::new();
+        loop {
+            let received = response.len();
+            let count = self.buffer_size;
+            if received + count > MAXIMUM_BUFFER_SIZE {
+                let message =
+                    format!("Response exceeded the maximum size of {MAXIMUM_BUFFER_SIZE} bytes");
+                let context = self
+                    .context(Operation::Read, message)
+                    .with_elapsed(started.elapsed());
+                Err(Error::FunctionFailure(Box::new(context)))?
+            }
+            response.resize(received + count, 0);
+            let (ret_cnt, status) = session.read(&mut response[received..]).map_err(|e| {
+                let context = self
+                    .context(Operation::Read, "Failed to read from instrument")
+                    .with_elapsed(started.elapsed());
+                visa_error(&self.rm, e, context)
+            })?;
+            response.truncate(received + ret_cnt);
+            let more = match status {
+                // The buffer filled up before the instrument signaled the end of the message.
+                VisaStatus::SUCCESS_MAX_CNT => true,
+                // The term char is only the last byte of the termination so it may be part of
+                // the data. The termination may also be split across reads.
+                VisaStatus::SUCCESS_TERM_CHAR => self
+                    .read_term
+                    .as_ref()
+                    .is_some_and(|term| !response.ends_with(term.bytes())),
+                // END always ends the response.
+                _ => false,
+            };
+            if !more {
+                break;
             }
-            match self
-                .visa
-                .viSetAttribute(self.session, visa::VI_ATTR_TERMCHAR_EN, 1)
-            {
-                status if status < 0 => {
-                    let msg = get_error_code(&self.visa, self.session, status)
-                        .unwrap_or_else(|| "Failed to enable termination char".into());
-                    Err(Error::FunctionFailure(msg))?
+        }
+        if let Some(term) = &self.read_term {
+            // The terminator of the last block may have arrived after the block was returned.
+            if std::mem::take(&mut self.stray_term) && response.starts_with(term.bytes()) {
+                response.drain(..term.bytes().len());
+                if response.is_empty() {
+                    return self.read_bytes();
                 }
-                _ => (),
             }
-        } else {
+            let len = term.strip(&response).len();
+            response.truncate(len);
+        }
+        Ok(response)
+    }
+
+    /// Reads exactly the declared length of a definite block with the term char disabled so
+    /// termination bytes inside the data don't end the read. The data is read by the driver
+    /// straight into `buffer`. An indefinite block ends with END.
+    fn read_binary_block_into(&mut self, buffer: &mut dyn BlockBuffer) -> Result<usize, Error> {
+        let started = Instant::now();
+        let term_char_was_set = self.is_term_char_attr_set;
+        if term_char_was_set {
             self.disable_term_char()?;
         }
-        self.term_string = Some(term_bytes);
-        Ok(())
+        let block = self.read_block(buffer, started);
+        if !term_char_was_set {
+            return block;
+        }
+        // An error of the block is reported before a failure to enable the term char again.
+        let term_bytes = self.read_term.clone().unwrap_or_default();
+        let restored = self.set_read_termination(term_bytes);
+        let len = block?;
+        restored.map(|_| len)
     }
 }
 
 #[test]
 fn test_if_visa_not_installed_change_visa_socket_to_use_raw_socket() {
-    let x = InstAddr::new("tcpip::localhost::5025::socket").unwrap();
-    x.connect();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let x = InstAddr::new(format!("tcpip::localhost::{port}::socket")).unwrap();
+    assert!(x.connect().is_ok());
+}
+
+#[test]
+fn serial_settings_are_applied_as_attributes() {
+    let InstAddr::Visa(address) = InstAddr::new("ASRL1::INSTR::115200,7E2,RTSCTS").unwrap() else {
+        panic!("serial addresses are visa addresses");
+    };
+    let conn = VisaConn::connect(address, Some(visa_mock::binary())).unwrap();
+    let session = conn.session().unwrap();
+    assert_eq!(session.get::<attr::AsrlBaud>().unwrap(), 115200);
+    assert_eq!(session.get::<attr::AsrlDataBits>().unwrap(), 7);
+    assert_eq!(
+        session.get::<attr::AsrlParity>().unwrap(),
+        VI_ASRL_PAR_EVEN as u16
+    );
+    assert_eq!(
+        session.get::<attr::AsrlStopBits>().unwrap(),
+        VI_ASRL_STOP_TWO as u16
+    );
+    assert_eq!(
+        session.get::<attr::AsrlFlowCntrl>().unwrap(),
+        VI_ASRL_FLOW_RTS_CTS as u16
+    );
+}
+
+#[test]
+fn timeout_and_termination_survive_a_reconnect() {
+    let InstAddr::Visa(address) =
+        InstAddr::new("USB0::0x2A8D::0x0101::MY12345678::0::INSTR").unwrap()
+    else {
+        panic!("USB addresses are visa addresses");
+    };
+    let mut conn = VisaConn::connect(address, Some(visa_mock::binary())).unwrap();
+    conn.set_timeout(Duration::from_millis(750)).unwrap();
+    conn.set_read_termination(TerminationBytes::CR).unwrap();
+    conn.reconnect().unwrap();
+    let session = conn.session().unwrap();
+    assert_eq!(session.get::<attr::TmoValue>().unwrap(), 750);
+    assert_eq!(session.get::<attr::Termchar>().unwrap(), b'\r');
+    assert!(session.get::<attr::TermcharEn>().unwrap());
+    assert_eq!(conn.read_term, Some(TerminationBytes::CR));
+}
+
+/// A socket resource of the mock that sends back every chunk of `replies` separately after it
+/// receives a command. The command is passed on through the channel.
+#[cfg(test)]
+fn socket_replying(
+    replies: Vec<&'static [u8]>,
+) -> (VisaAddress, std::sync::mpsc::Receiver<Vec<u8>>) {
+    let (port, command) = super::test_support::serve(replies, Duration::from_millis(20));
+    let address = format!("TCPIP0::127.0.0.1::{port}::SOCKET")
+        .parse()
+        .unwrap();
+    (address, command)
+}
+
+#[test]
+fn binary_block_is_read_past_the_term_char() {
+    let (address, _) = socket_replying(vec![b"#15a\nb\nc\nnext\n"]);
+    let mut conn = VisaConn::connect(address, Some(visa_mock::binary())).unwrap();
+    assert_eq!(conn.query_binary_block("CURV?").unwrap(), b"a\nb\nc");
+    assert_eq!(conn.read().unwrap(), "next");
+}
+
+#[test]
+fn binary_block_without_terminator_is_returned() {
+    let (address, _) = socket_replying(vec![b"#13abc"]);
+    let mut conn = VisaConn::connect(address, Some(visa_mock::binary())).unwrap();
+    let started = Instant::now();
+    assert_eq!(conn.query_binary_block("CURV?").unwrap(), b"abc");
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn termination_is_kept_when_a_binary_block_fails() {
+    let (address, _) = socket_replying(vec![b"1.25\n"]);
+    let mut conn = VisaConn::connect(address, Some(visa_mock::binary())).unwrap();
+    conn.set_read_termination(TerminationBytes::LF).unwrap();
+    assert!(conn.query_binary_block("CURV?").is_err());
+    assert_eq!(conn.read_term, Some(TerminationBytes::LF));
+    assert!(conn.session().unwrap().get::<attr::TermcharEn>().unwrap());
+}
+
+#[test]
+fn multi_byte_read_termination_is_not_ended_by_its_last_byte() {
+    let (address, command) = socket_replying(vec![b"1\n2\r", b"\n3\r\n"]);
+    let mut conn = VisaConn::connect(address, Some(visa_mock::binary())).unwrap();
+    conn.set_read_termination(TerminationBytes::CRLF).unwrap();
+    conn.set_write_termination(TerminationBytes::CR).unwrap();
+    assert_eq!(conn.query("MEAS?").unwrap(), "1\n2");
+    assert_eq!(command.recv().unwrap(), b"MEAS?\r");
+    assert_eq!(conn.read().unwrap(), "3");
 }
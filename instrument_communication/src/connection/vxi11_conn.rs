@@ -0,0 +1,632 @@
+//! A VXI-11 client that talks to LAN instruments without a VISA library. Every operation goes
+//! through the core channel of the instrument. The abort channel is only connected by
+//! [`Vxi11Conn::abort_handle`].
+use super::onc_rpc::{self, RpcClient, XdrReader, XdrWriter};
+use super::tcp_conn::connect_timeout;
+use super::{resolve_host, with_port};
+use crate::address::{InstAddr, VisaAddress};
+use crate::binary_block::{self, BlockBuffer, Header};
+use crate::communication::{
+    InstConnection, INFINITE_TIMEOUT, MAXIMUM_BUFFER_SIZE, TERMINATOR_GRACE,
+};
+use crate::err::{Context, Error, Operation};
+use crate::termination_bytes::TerminationBytes;
+use std::borrow::Cow;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The port of the portmapper that tells clients where the core channel listens.
+pub const DEFAULT_PORTMAPPER_PORT: u16 = 111;
+/// Addresses without a device name refer to the first instrument of the host.
+const DEFAULT_DEVICE_NAME: &str = "inst0";
+const DEFAULT_BUFFER_SIZE: usize = 4096;
+/// Extra time given to a call on top of the timeouts sent to the instrument so that the
+/// instrument reports its own timeout before the socket gives up.
+const RPC_TIMEOUT_MARGIN: Duration = Duration::from_secs(1);
+
+const DEVICE_CORE: u32 = 0x0607AF;
+const DEVICE_CORE_VERSION: u32 = 1;
+const DEVICE_ASYNC: u32 = 0x0607B0;
+const DEVICE_ASYNC_VERSION: u32 = 1;
+
+const CREATE_LINK: u32 = 10;
+const DEVICE_WRITE: u32 = 11;
+const DEVICE_READ: u32 = 12;
+const DEVICE_READSTB: u32 = 13;
+const DEVICE_TRIGGER: u32 = 14;
+const DEVICE_CLEAR: u32 = 15;
+const DEVICE_LOCK: u32 = 18;
+const DEVICE_UNLOCK: u32 = 19;
+const DESTROY_LINK: u32 = 23;
+const DEVICE_ABORT: u32 = 1;
+
+const FLAG_WAITLOCK: i32 = 0x01;
+const FLAG_END: i32 = 0x08;
+const FLAG_TERMCHRSET: i32 = 0x80;
+
+const REASON_CHR: i32 = 0x02;
+const REASON_END: i32 = 0x04;
+
+const ERROR_IO_TIMEOUT: i32 = 15;
+
+/// The description of a VXI-11 device error code.
+fn device_error_message(code: i32) -> &'static str {
+    match code {
+        1 => "Syntax error",
+        3 => "Device not accessible",
+        4 => "Invalid link identifier",
+        5 => "Parameter error",
+        6 => "Channel not established",
+        8 => "Operation not supported",
+        9 => "Out of resources",
+        11 => "Device locked by another link",
+        12 => "No lock held by this link",
+        15 => "I/O timeout",
+        17 => "I/O error",
+        21 => "Invalid address",
+        23 => "Abort",
+        29 => "Channel already established",
+        _ => "Unknown error",
+    }
+}
+
+fn millis(timeout: Duration) -> u32 {
+    u32::try_from(timeout.as_millis()).unwrap_or(u32::MAX)
+}
+
+/// The link returned by create_link.
+struct Link {
+    id: i32,
+    abort_port: u16,
+    max_recv_size: usize,
+}
+
+pub struct Vxi11Conn {
+    address: VisaAddress,
+    /// The instrument at the port of its portmapper.
+    host: SocketAddr,
+    core: RpcClient,
+    link: Link,
+    buffer_size: usize,
+    /// Ends the responses that are read.
+    read_term: Option<TerminationBytes>,
+    /// Appended to every message that is written.
+    write_term: Option<TerminationBytes>,
+    timeout: Duration,
+    lock_timeout: Duration,
+    /// The last message that was written. It is reported in errors.
+    last_command: Option<Vec<u8>>,
+}
+
+impl Vxi11Conn {
+    pub fn connect(addr: VisaAddress) -> Result<Vxi11Conn, Error> {
+        Vxi11Conn::connect_with_portmapper(addr, DEFAULT_PORTMAPPER_PORT)
+    }
+
+    /// Connects through a portmapper that doesn't listen on the default port.
+    pub fn connect_with_portmapper(
+        addr: VisaAddress,
+        portmapper_port: u16,
+    ) -> Result<Vxi11Conn, Error> {
+        let started = Instant::now();
+        let connect_error = |e: io::Error, message: &'static str| {
+            Error::ConnectionFailed(Box::new(
+                Context::new(InstAddr::Visa(addr.clone()), Operation::Connect, message)
+                    .with_elapsed(started.elapsed())
+                    .with_io_error(e),
+            ))
+        };
+        let host = resolve_host(&addr, portmapper_port)
+            .map_err(|e| connect_error(e, "Failed to resolve host"))?;
+        let (core, link) = open_link(host, &device_name(&addr))
+            .map_err(|e| connect_error(e, "Failed to create VXI-11 link"))?;
+        let mut conn = Vxi11Conn {
+            address: addr,
+            host,
+            core,
+            link,
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            read_term: None,
+            write_term: None,
+            timeout: connect_timeout,
+            lock_timeout: Duration::ZERO,
+            last_command: None,
+        };
+        conn.apply_timeout()?;
+        conn.set_termination(TerminationBytes::LF)?;
+        Ok(conn)
+    }
+
+    /// Sets how long operations wait for a lock held by another link. With the default of
+    /// zero they fail immediately when the instrument is locked.
+    pub fn set_lock_timeout(&mut self, timeout: Duration) -> Result<(), Error> {
+        self.lock_timeout = timeout;
+        self.apply_timeout()
+    }
+
+    /// Sends a device clear to the instrument.
+    pub fn clear(&mut self) -> Result<(), Error> {
+        let args = self.generic_params();
+        self.call(Operation::Clear, DEVICE_CLEAR, &args)?;
+        Ok(())
+    }
+
+    /// Sends a group execute trigger to the instrument.
+    pub fn trigger(&mut self) -> Result<(), Error> {
+        let args = self.generic_params();
+        self.call(Operation::Trigger, DEVICE_TRIGGER, &args)?;
+        Ok(())
+    }
+
+    /// Reads the status byte of the instrument.
+    pub fn read_stb(&mut self) -> Result<u8, Error> {
+        let args = self.generic_params();
+        let reply = self.call(Operation::ReadStatusByte, DEVICE_READSTB, &args)?;
+        let stb = XdrReader::new(&reply)
+            .u32()
+            .map_err(|e| self.io_error(e, Operation::ReadStatusByte, Instant::now()))?;
+        Ok(stb as u8)
+    }
+
+    /// Locks the instrument so that other links can't use it until [`Vxi11Conn::unlock`] is
+    /// called or the link is closed. Waits up to the lock timeout for another link to unlock.
+    pub fn lock(&mut self) -> Result<(), Error> {
+        let mut args = XdrWriter::default();
+        args.i32(self.link.id)
+            .i32(self.flags())
+            .u32(millis(self.lock_timeout));
+        self.call(Operation::Lock, DEVICE_LOCK, &args)?;
+        Ok(())
+    }
+
+    pub fn unlock(&mut self) -> Result<(), Error> {
+        let mut args = XdrWriter::default();
+        args.i32(self.link.id);
+        self.call(Operation::Lock, DEVICE_UNLOCK, &args)?;
+        Ok(())
+    }
+
+    /// Connects to the abort channel of the link. The handle can be moved to another thread to
+    /// abort a read or write that is blocking this connection.
+    pub fn abort_handle(&self) -> Result<Vxi11Abort, Error> {
+        let started = Instant::now();
+        let addr = with_port(self.host, self.link.abort_port);
+        let client = RpcClient::connect(addr, DEVICE_ASYNC, DEVICE_ASYNC_VERSION, connect_timeout)
+            .and_then(|client| client.set_timeout(Some(connect_timeout)).map(|_| client))
+            .map_err(|e| {
+                Error::ConnectionFailed(Box::new(
+                    self.context(Operation::Abort, "Failed to connect to the abort channel")
+                        .with_elapsed(started.elapsed())
+                        .with_io_error(e),
+                ))
+            })?;
+        Ok(Vxi11Abort {
+            client: Mutex::new(client),
+            link_id: self.link.id,
+            address: self.address.clone(),
+        })
+    }
+
+    fn context(&self, operation: Operation, message: impl Into<Cow<'static, str>>) -> Context {
+        Context::new(self.address(), operation, message).with_command(self.last_command.as_deref())
+    }
+
+    /// Timeouts on a socket are reported as either WouldBlock or TimedOut depending on the
+    /// platform.
+    fn io_error(&self, e: io::Error, operation: Operation, started: Instant) -> Error {
+        let timed_out = matches!(
+            e.kind(),
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+        );
+        let context = Box::new(
+            self.context(operation, "VXI-11 call failed")
+                .with_elapsed(started.elapsed())
+                .with_io_error(e),
+        );
+        if timed_out {
+            Error::Timeout(context)
+        } else {
+            Error::FunctionFailure(context)
+        }
+    }
+
+    fn device_error(&self, code: i32, operation: Operation, started: Instant) -> Error {
+        let message = format!("VXI-11 error {code}: {}", device_error_message(code));
+        let context = Box::new(
+            self.context(operation, message)
+                .with_elapsed(started.elapsed()),
+        );
+        if code == ERROR_IO_TIMEOUT {
+            Error::Timeout(context)
+        } else {
+            Error::FunctionFailure(context)
+        }
+    }
+
+    /// Calls a procedure of the core channel. Every reply starts with a device error code
+    /// which is checked here. The rest of the reply is returned.
+    fn call(
+        &mut self,
+        operation: Operation,
+        procedure: u32,
+        args: &XdrWriter,
+    ) -> Result<Vec<u8>, Error> {
+        let started = Instant::now();
+        let reply = match self.core.call(procedure, args) {
+            Ok(reply) => reply,
+            Err(e) => return Err(self.io_error(e, operation, started)),
+        };
+        let mut reader = XdrReader::new(&reply);
+        match reader.i32() {
+            Ok(0) => Ok(reply[4..].to_vec()),
+            Ok(code) => Err(self.device_error(code, operation, started)),
+            Err(e) => Err(self.io_error(e, operation, started)),
+        }
+    }
+
+    fn flags(&self) -> i32 {
+        if self.lock_timeout.is_zero() {
+            0
+        } else {
+            FLAG_WAITLOCK
+        }
+    }
+
+    /// The arguments of clear, trigger and readstb.
+    fn generic_params(&self) -> XdrWriter {
+        let mut args = XdrWriter::default();
+        args.i32(self.link.id)
+            .i32(self.flags())
+            .u32(millis(self.lock_timeout))
+            .u32(self.io_timeout());
+        args
+    }
+
+    fn io_timeout(&self) -> u32 {
+        millis(self.timeout)
+    }
+
+    /// Calls device_read for at most `size` bytes. Returns the reason the instrument ended the
+    /// read and the data. A `term_char` of `None` only ends the read on END or `size`.
+    fn device_read(
+        &mut self,
+        size: usize,
+        term_char: Option<u8>,
+        io_timeout: u32,
+        started: Instant,
+    ) -> Result<(i32, Vec<u8>), Error> {
+        let flags = match term_char {
+            Some(_) => self.flags() | FLAG_TERMCHRSET,
+            None => self.flags(),
+        };
+        let mut args = XdrWriter::default();
+        args.i32(self.link.id)
+            .u32(u32::try_from(size).unwrap_or(u32::MAX))
+            .u32(io_timeout)
+            .u32(millis(self.lock_timeout))
+            .i32(flags)
+            .u32(term_char.unwrap_or(0) as u32);
+        let reply = self.call(Operation::Read, DEVICE_READ, &args)?;
+        let mut reader = XdrReader::new(&reply);
+        reader
+            .i32()
+            .and_then(|reason| Ok((reason, reader.opaque()?.to_vec())))
+            .map_err(|e| self.io_error(e, Operation::Read, started))
+    }
+
+    /// Reads until END or until `term` was received.
+    fn read_until(&mut self, term: &TerminationBytes, started: Instant) -> Result<Vec<u8>, Error> {
+        let mut response: Vec<u8> = Vec::new();
+        loop {
+            if response.len() + self.buffer_size > MAXIMUM_BUFFER_SIZE {
+                let message =
+                    format!("Response exceeded the maximum size of {MAXIMUM_BUFFER_SIZE} bytes");
+                let context = self
+                    .context(Operation::Read, message)
+                    .with_elapsed(started.elapsed());
+                Err(Error::FunctionFailure(Box::new(context)))?
+            }
+            let term_char = term.bytes().last().copied();
+            let (reason, data) =
+                self.device_read(self.buffer_size, term_char, self.io_timeout(), started)?;
+            response.extend_from_slice(&data);
+            // A term char only ends the response once the whole termination was received.
+            if reason & REASON_END != 0
+                || (reason & REASON_CHR != 0 && response.ends_with(term.bytes()))
+            {
+                return Ok(response);
+            }
+        }
+    }
+
+    /// The socket waits for the instrument's own timeouts plus a margin.
+    fn apply_timeout(&self) -> Result<(), Error> {
+        let timeout = if self.timeout == INFINITE_TIMEOUT {
+            None
+        } else {
+            Some(
+                self.timeout
+                    .saturating_add(self.lock_timeout)
+                    .saturating_add(RPC_TIMEOUT_MARGIN),
+            )
+        };
+        self.core.set_timeout(timeout).map_err(|e| {
+            Error::FunctionFailure(Box::new(
+                self.context(Operation::SetAttribute, "Failed to set connection timeout")
+                    .with_io_error(e),
+            ))
+        })
+    }
+
+    fn destroy_link(&mut self) {
+        let mut args = XdrWriter::default();
+        args.i32(self.link.id);
+        if let Err(e) = self.call(Operation::Connect, DESTROY_LINK, &args) {
+            log::debug!("Failed to destroy VXI-11 link. Error: {e}");
+        }
+    }
+}
+
+/// Aborts the operation in progress on a VXI-11 link.
+pub struct Vxi11Abort {
+    client: Mutex<RpcClient>,
+    link_id: i32,
+    address: VisaAddress,
+}
+
+impl Vxi11Abort {
+    pub fn abort(&self) -> Result<(), Error> {
+        let started = Instant::now();
+        let context = |message: Cow<'static, str>| {
+            Context::new(
+                InstAddr::Visa(self.address.clone()),
+                Operation::Abort,
+                message,
+            )
+            .with_elapsed(started.elapsed())
+        };
+        let mut args = XdrWriter::default();
+        args.i32(self.link_id);
+        let mut client = match self.client.lock() {
+            Ok(client) => client,
+            Err(e) => e.into_inner(),
+        };
+        let reply = client.call(DEVICE_ABORT, &args).map_err(|e| {
+            Error::FunctionFailure(Box::new(
+                context("VXI-11 call failed".into()).with_io_error(e),
+            ))
+        })?;
+        match XdrReader::new(&reply).i32() {
+            Ok(0) => Ok(()),
+            Ok(code) => Err(Error::FunctionFailure(Box::new(context(
+                format!("VXI-11 error {code}: {}", device_error_message(code)).into(),
+            )))),
+            Err(e) => Err(Error::FunctionFailure(Box::new(
+                context("VXI-11 call failed".into()).with_io_error(e),
+            ))),
+        }
+    }
+}
+
+/// The device name of the instrument on its host such as `inst1` or `gpib0,7`.
+fn device_name(addr: &VisaAddress) -> String {
+    addr.lan_device_name()
+        .map_or_else(|| DEFAULT_DEVICE_NAME.to_owned(), |name| name.to_string())
+}
+
+/// Finds the core channel through the portmapper then creates a link to the instrument.
+fn open_link(portmapper: SocketAddr, device: &str) -> io::Result<(RpcClient, Link)> {
+    let port = onc_rpc::get_port(
+        portmapper,
+        DEVICE_CORE,
+        DEVICE_CORE_VERSION,
+        connect_timeout,
+    )?;
+    if port == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "The host doesn't have a VXI-11 core channel",
+        ));
+    }
+    let mut core = RpcClient::connect(
+        with_port(portmapper, port),
+        DEVICE_CORE,
+        DEVICE_CORE_VERSION,
+        connect_timeout,
+    )?;
+    core.set_timeout(Some(connect_timeout))?;
+    let mut args = XdrWriter::default();
+    args.i32(std::process::id() as i32)
+        .bool(false)
+        .u32(0)
+        .string(device);
+    let reply = core.call(CREATE_LINK, &args)?;
+    let mut reader = XdrReader::new(&reply);
+    let error = reader.i32()?;
+    if error != 0 {
+        return Err(io::Error::other(format!(
+            "VXI-11 error {error}: {}",
+            device_error_message(error)
+        )));
+    }
+    let id = reader.i32()?;
+    let abort_port = reader.u32()? as u16;
+    let max_recv_size = reader.u32()? as usize;
+    Ok((
+        core,
+        Link {
+            id,
+            abort_port,
+            max_recv_size,
+        },
+    ))
+}
+
+impl InstConnection for Vxi11Conn {
+    fn address(&self) -> InstAddr {
+        InstAddr::Visa(self.address.clone())
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> Result<(), Error> {
+        let previous = std::mem::replace(&mut self.timeout, timeout);
+        self.apply_timeout()
+            .inspect_err(|_| self.timeout = previous)
+    }
+
+    fn reconnect(&mut self) -> Result<(), Error> {
+        self.destroy_link();
+        let started = Instant::now();
+        let device_name = device_name(&self.address);
+        let (core, link) = open_link(self.host, &device_name).map_err(|e| {
+            Error::ConnectionFailed(Box::new(
+                self.context(Operation::Connect, "Failed to create VXI-11 link")
+                    .with_elapsed(started.elapsed())
+                    .with_io_error(e),
+            ))
+        })?;
+        self.core = core;
+        self.link = link;
+        self.apply_timeout()
+    }
+
+    fn set_read_termination(&mut self, term_bytes: TerminationBytes) -> Result<(), Error> {
+        self.read_term = Some(term_bytes);
+        Ok(())
+    }
+
+    fn set_write_termination(&mut self, term_bytes: TerminationBytes) -> Result<(), Error> {
+        self.write_term = Some(term_bytes);
+        Ok(())
+    }
+
+    fn write_bytes(&mut self, message: &[u8]) -> Result<(), Error> {
+        let term = self.write_term.as_ref().unwrap_or(&TerminationBytes::None);
+        let mut buffer = Vec::with_capacity(message.len() + term.bytes().len());
+        buffer.extend_from_slice(message);
+        buffer.extend_from_slice(term.bytes());
+        self.last_command = Some(message.to_vec());
+        // The instrument accepts at most max_recv_size bytes per call. END is only set on
+        // the last chunk.
+        let chunk_size = match self.link.max_recv_size {
+            0 => buffer.len().max(1),
+            size => size,
+        };
+        let mut sent = 0;
+        while sent < buffer.len() {
+            let end = buffer.len().min(sent + chunk_size);
+            let flags = if end == buffer.len() {
+                self.flags() | FLAG_END
+            } else {
+                self.flags()
+            };
+            let mut args = XdrWriter::default();
+            args.i32(self.link.id)
+                .u32(self.io_timeout())
+                .u32(millis(self.lock_timeout))
+                .i32(flags)
+                .opaque(&buffer[sent..end]);
+            let started = Instant::now();
+            let reply = self.call(Operation::Write, DEVICE_WRITE, &args)?;
+            let accepted = XdrReader::new(&reply)
+                .u32()
+                .map_err(|e| self.io_error(e, Operation::Write, started))?;
+            // Retrying a write the instrument didn't take any of would never finish.
+            if accepted == 0 {
+                return Err(Error::FunctionFailure(Box::new(
+                    self.context(Operation::Write, "Instrument accepted none of the data")
+                        .with_elapsed(started.elapsed()),
+                )));
+            }
+            sent += (accepted as usize).min(end - sent);
+        }
+        Ok(())
+    }
+
+    fn read_bytes(&mut self) -> Result<Vec<u8>, Error> {
+        let term = self.read_term.clone().unwrap_or(TerminationBytes::None);
+        let mut response = self.read_until(&term, Instant::now())?;
+        let len = term.strip(&response).len();
+        response.truncate(len);
+        Ok(response)
+    }
+
+    /// Reads exactly the declared length of a definite block without the term char so
+    /// termination bytes inside the data don't end the read. An indefinite block ends with END.
+    fn read_binary_block_into(&mut self, buffer: &mut dyn BlockBuffer) -> Result<usize, Error> {
+        let started = Instant::now();
+        let ended_early = |this: &Vxi11Conn, received: usize, expected: usize| {
+            let message = format!("Binary block ended after {received} of {expected} bytes");
+            let context = this
+                .context(Operation::Read, message)
+                .with_elapsed(started.elapsed());
+            Error::FunctionFailure(Box::new(context))
+        };
+        let term = self.read_term.clone().unwrap_or(TerminationBytes::None);
+        let mut header = Vec::new();
+        let mut ended = false;
+        let len = loop {
+            match binary_block::parse_header(&header, &[])? {
+                Header::Incomplete(needed) => {
+                    if ended {
+                        Err(ended_early(self, header.len(), needed))?
+                    }
+                    let size = needed - header.len();
+                    let (reason, data) =
+                        self.device_read(size, None, self.io_timeout(), started)?;
+                    header.extend_from_slice(&data);
+                    ended = reason & REASON_END != 0;
+                }
+                Header::Definite { len, .. } => break len,
+                // The rest of the message up to END is the data.
+                Header::Indefinite { .. } => {
+                    let mut data = Vec::new();
+                    if !ended {
+                        data = self.read_until(&TerminationBytes::None, started)?;
+                    }
+                    let len = term.strip(&data).len();
+                    buffer.alloc(len).copy_from_slice(&data[..len]);
+                    return Ok(len);
+                }
+            }
+        };
+        if len > MAXIMUM_BUFFER_SIZE {
+            let message = format!(
+                "Binary block of {len} bytes exceeds the maximum size of {MAXIMUM_BUFFER_SIZE} bytes"
+            );
+            let context = self
+                .context(Operation::Read, message)
+                .with_elapsed(started.elapsed());
+            Err(Error::FunctionFailure(Box::new(context)))?
+        }
+        let data = buffer.alloc(len);
+        let mut filled = 0;
+        while filled < len {
+            if ended {
+                Err(ended_early(self, filled, len))?
+            }
+            let size = len - filled;
+            let (reason, received) = self.device_read(size, None, self.io_timeout(), started)?;
+            let count = received.len().min(size);
+            data[filled..filled + count].copy_from_slice(&received[..count]);
+            filled += count;
+            ended = reason & REASON_END != 0;
+        }
+        // The block is returned even if an instrument that doesn't send END never terminates it.
+        if !ended && !term.bytes().is_empty() {
+            let grace = millis(self.timeout.min(TERMINATOR_GRACE));
+            match self.device_read(term.bytes().len(), None, grace, started) {
+                Err(e) if !e.is_timeout() => Err(e)?,
+                _ => (),
+            }
+        }
+        Ok(len)
+    }
+}
+
+impl Drop for Vxi11Conn {
+    fn drop(&mut self) {
+        self.destroy_link();
+    }
+}
@@ -0,0 +1,59 @@
+use crate::address::InstAddr;
+use crate::connection::visa_conn::try_load_binary;
+use crate::err::Error;
+use visa::Binary;
+
+/// A resource reported by a VISA library.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveredResource {
+    pub address: InstAddr,
+    /// The resource name exactly as the VISA library reported it.
+    pub resource_name: String,
+    /// One of the `VI_INTF_*` constants such as [`visa::VI_INTF_GPIB`].
+    pub interface_type: u16,
+    pub board: u16,
+    /// The alias configured for the resource in the vendor's connection expert.
+    pub alias: Option<String>,
+}
+
+/// Lists every resource of the VISA library that matches a VISA resource expression such as
+/// `?*INSTR` for all instruments or `GPIB?*` for everything on the GPIB interfaces. Resources
+/// that can't be described as an [`InstAddr`] are returned as errors so the rest of the list
+/// is still available.
+/// ```no_run
+/// use instrument_communication::discover;
+/// for resource in discover(visa::Binary::Primary, "?*INSTR")? {
+///     match resource {
+///         Ok(resource) => println!("{} {:?}", resource.address, resource.alias),
+///         Err(e) => println!("{e}"),
+///     }
+/// }
+/// # Ok::<(), instrument_communication::err::Error>(())
+/// ```
+pub fn discover(
+    binary: Binary,
+    pattern: &str,
+) -> Result<impl Iterator<Item = Result<DiscoveredResource, Error>>, Error> {
+    let rm = try_load_binary(binary)?;
+    let found = rm.find(pattern).map_err(|e| {
+        Error::DiscoveryFailed(format!("Failed to find resources matching {pattern}. {e}").into())
+    })?;
+    Ok(found.map(move |name| {
+        let name = name.map_err(|e| {
+            Error::DiscoveryFailed(format!("Failed to read the next resource. {e}").into())
+        })?;
+        let parsed = rm.parse_resource(&name).map_err(|e| {
+            Error::DiscoveryFailed(format!("Failed to parse resource {name}. {e}").into())
+        })?;
+        let address = InstAddr::new(&parsed.expanded_name).map_err(|e| {
+            Error::ParseFailed(format!("Unsupported resource {name}. Error: {e}").into())
+        })?;
+        Ok(DiscoveredResource {
+            address,
+            resource_name: name,
+            interface_type: parsed.interface_type,
+            board: parsed.board,
+            alias: parsed.alias,
+        })
+    }))
+}
@@ -1,12 +1,251 @@
+use crate::address::error::AddressError;
+use crate::address::InstAddr;
 use std::borrow::Cow;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use visa::VisaStatus;
+
+/// Commands longer than this are shortened in error messages.
+const MAX_COMMAND_LENGTH: usize = 64;
+
 #[non_exhaustive]
 #[derive(Debug, Clone)]
 pub enum Error {
-    Timeout,
+    /// The instrument did not respond in time.
+    Timeout(Box<Context>),
     BinaryError(Cow<'static, str>),
     OpenSessionError(Cow<'static, str>),
     ParseFailed(Cow<'static, str>),
-    ConnectionFailed(Cow<'static, str>),
-    FunctionFailure(Cow<'static, str>),
+    /// An address could not be parsed.
+    InvalidAddress(AddressError),
+    /// The connection could not be opened or was closed by the instrument.
+    ConnectionFailed(Box<Context>),
+    /// An operation on an open connection failed.
+    FunctionFailure(Box<Context>),
     ConflictingSettings(Cow<'static, str>),
+    /// A VISA function failed with the status.
+    Visa(VisaStatus, Box<Context>),
+    /// Listing the resources of a VISA library failed.
+    DiscoveryFailed(Cow<'static, str>),
+}
+
+/// The operation that was running when an error occurred.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Connect,
+    Write,
+    Read,
+    SetAttribute,
+    Clear,
+    Trigger,
+    ReadStatusByte,
+    Lock,
+    Abort,
+    GpibControl,
+}
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Operation::Connect => "connect",
+            Operation::Write => "write",
+            Operation::Read => "read",
+            Operation::SetAttribute => "set attribute",
+            Operation::Clear => "clear",
+            Operation::Trigger => "trigger",
+            Operation::ReadStatusByte => "read status byte",
+            Operation::Lock => "lock",
+            Operation::Abort => "abort",
+            Operation::GpibControl => "GPIB control",
+        })
+    }
+}
+
+/// Describes where an error happened: the instrument, the operation, the last command that was
+/// sent and how long the operation ran before it failed.
+#[derive(Debug, Clone)]
+pub struct Context {
+    pub address: InstAddr,
+    pub operation: Operation,
+    pub command: Option<String>,
+    pub elapsed: Duration,
+    pub message: Cow<'static, str>,
+    io_error: Option<Arc<io::Error>>,
+    visa_status: Option<VisaStatus>,
+}
+
+impl Context {
+    pub fn new(
+        address: InstAddr,
+        operation: Operation,
+        message: impl Into<Cow<'static, str>>,
+    ) -> Context {
+        Context {
+            address,
+            operation,
+            command: None,
+            elapsed: Duration::ZERO,
+            message: message.into(),
+            io_error: None,
+            visa_status: None,
+        }
+    }
+
+    /// Attaches the command. Binary data is shown lossily and long commands are shortened.
+    pub fn with_command(mut self, command: Option<&[u8]>) -> Context {
+        self.command = command.map(|command| {
+            let text = String::from_utf8_lossy(command);
+            let text = text.trim_end();
+            match text.char_indices().nth(MAX_COMMAND_LENGTH) {
+                Some((end, _)) => format!("{}...", &text[..end]),
+                None => text.to_owned(),
+            }
+        });
+        self
+    }
+
+    pub fn with_elapsed(mut self, elapsed: Duration) -> Context {
+        self.elapsed = elapsed;
+        self
+    }
+
+    pub fn with_io_error(mut self, error: io::Error) -> Context {
+        self.io_error = Some(Arc::new(error));
+        self
+    }
+
+    /// The I/O error that caused the failure if there is one.
+    pub fn io_error(&self) -> Option<&io::Error> {
+        self.io_error.as_deref()
+    }
+
+    pub fn with_visa_status(mut self, status: VisaStatus) -> Context {
+        self.visa_status = Some(status);
+        self
+    }
+
+    /// The status of the VISA function that caused the failure if there is one.
+    pub fn visa_status(&self) -> Option<VisaStatus> {
+        self.visa_status
+    }
+}
+
+impl Display for Context {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} {} failed", self.operation, self.address)?;
+        if !self.elapsed.is_zero() {
+            write!(f, " after {:?}", self.elapsed)?;
+        }
+        if let Some(command) = &self.command {
+            write!(f, " (command: {command:?})")?;
+        }
+        write!(f, ": {}", self.message)?;
+        if let Some(e) = &self.io_error {
+            write!(f, ". {e}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Error {
+    /// True for timeouts of any connection type including `VI_ERROR_TMO` from VISA.
+    pub fn is_timeout(&self) -> bool {
+        matches!(
+            self,
+            Error::Timeout(_) | Error::Visa(VisaStatus::ERROR_TMO, _)
+        )
+    }
+
+    /// The context of errors raised by an operation on an instrument.
+    pub fn context(&self) -> Option<&Context> {
+        match self {
+            Error::Timeout(context)
+            | Error::ConnectionFailed(context)
+            | Error::FunctionFailure(context)
+            | Error::Visa(_, context) => Some(context),
+            _ => None,
+        }
+    }
+
+    /// The VISA status of a failed VISA function. Timeouts of a VISA connection keep
+    /// `VI_ERROR_TMO` in their context.
+    pub fn visa_status(&self) -> Option<VisaStatus> {
+        match self {
+            Error::Visa(status, _) => Some(*status),
+            _ => self.context().and_then(Context::visa_status),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Error::Timeout(context) => write!(f, "Timeout. {context}"),
+            Error::ConnectionFailed(context) => write!(f, "Connection failed. {context}"),
+            Error::FunctionFailure(context) => context.fmt(f),
+            Error::Visa(status, context) => write!(f, "{context}. {status}"),
+            Error::BinaryError(msg) => write!(f, "Failed to load VISA binary. {msg}"),
+            Error::OpenSessionError(msg) => write!(f, "Failed to open VISA session. {msg}"),
+            Error::ParseFailed(msg) => f.write_str(msg),
+            Error::InvalidAddress(e) => write!(f, "Failed to create address. Error: {e}"),
+            Error::ConflictingSettings(msg) => f.write_str(msg),
+            Error::DiscoveryFailed(msg) => write!(f, "Failed to discover instruments. {msg}"),
+        }
+    }
+}
+
+impl From<AddressError> for Error {
+    fn from(e: AddressError) -> Self {
+        Error::InvalidAddress(e)
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Visa(status, _) => Some(status),
+            Error::InvalidAddress(e) => Some(e),
+            _ => {
+                let context = self.context()?;
+                match (&context.visa_status, context.io_error()) {
+                    (Some(status), _) => Some(status),
+                    (None, io_error) => io_error.map(|e| e as _),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_names_instrument_command_and_source() {
+        let address: InstAddr = "GPIB0::7::INSTR".parse().unwrap();
+        let context = Context::new(address, Operation::Read, "Failed to read from instrument")
+            .with_command(Some(b"*IDN?\n"))
+            .with_elapsed(Duration::from_millis(1500));
+        let err = Error::Visa(VisaStatus::ERROR_TMO, Box::new(context));
+        assert_eq!(
+            err.to_string(),
+            "read gpib0::7::instr failed after 1.5s (command: \"*IDN?\"): Failed to read from instrument. VI_ERROR_TMO (0xBFFF0015): Timeout expired before operation completed."
+        );
+        let source = std::error::Error::source(&err).unwrap();
+        assert_eq!(
+            source.downcast_ref::<VisaStatus>(),
+            Some(&VisaStatus::ERROR_TMO)
+        );
+    }
+
+    #[test]
+    fn long_commands_are_shortened() {
+        let address: InstAddr = "GPIB0::7::INSTR".parse().unwrap();
+        let context =
+            Context::new(address, Operation::Write, "").with_command(Some(&[b'A'; 100][..]));
+        assert_eq!(context.command.unwrap().len(), MAX_COMMAND_LENGTH + 3);
+    }
 }
@@ -0,0 +1,295 @@
+//! How messages are delimited on byte stream connections such as raw sockets. Text instruments
+//! end their messages with termination bytes while binary fixture controllers often send fixed
+//! size frames or frames that start with their length.
+use crate::communication::MAXIMUM_BUFFER_SIZE;
+use crate::err::Error;
+use crate::termination_bytes::TerminationBytes;
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::Range;
+
+/// A complete frame at the start of the received bytes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    /// The bytes of the message inside the frame.
+    pub payload: Range<usize>,
+    /// The number of bytes the whole frame takes up.
+    pub len: usize,
+}
+
+impl Frame {
+    /// A frame that is only the message.
+    pub fn whole(len: usize) -> Frame {
+        Frame {
+            payload: 0..len,
+            len,
+        }
+    }
+}
+
+/// Splits the received bytes into messages and frames the messages that are written.
+pub trait Framing: Send {
+    /// Finds the first complete frame in the received bytes. Returns `None` if more bytes are
+    /// needed and an error if the received bytes can't start a valid frame. `searched` is the
+    /// number of bytes that were already passed to a previous call which found no frame, so a
+    /// scan can resume where it stopped.
+    fn decode(
+        &mut self,
+        received: &[u8],
+        searched: usize,
+    ) -> Result<Option<Frame>, Cow<'static, str>>;
+    /// Appends the framed message to `out`.
+    fn encode(&mut self, message: &[u8], out: &mut Vec<u8>) -> Result<(), Cow<'static, str>>;
+}
+
+/// Messages end with the termination bytes. Without termination bytes every read returns the
+/// bytes received so far.
+impl Framing for TerminationBytes {
+    fn decode(
+        &mut self,
+        received: &[u8],
+        searched: usize,
+    ) -> Result<Option<Frame>, Cow<'static, str>> {
+        let term_len = self.bytes().len();
+        if term_len == 0 {
+            return Ok((!received.is_empty()).then(|| Frame::whole(received.len())));
+        }
+        // The terminator may have been split by the previous read.
+        let end = self.find(received, searched.saturating_sub(term_len - 1));
+        Ok(end.map(|end| Frame {
+            payload: 0..end,
+            len: end + term_len,
+        }))
+    }
+
+    fn encode(&mut self, message: &[u8], out: &mut Vec<u8>) -> Result<(), Cow<'static, str>> {
+        out.extend_from_slice(message);
+        out.extend_from_slice(self.bytes());
+        Ok(())
+    }
+}
+
+/// Every message is exactly this many bytes long.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FixedSize(usize);
+
+impl FixedSize {
+    /// Frames of `size` bytes. Empty frames are rejected since they would never consume any of
+    /// the received bytes.
+    pub fn new(size: usize) -> Result<FixedSize, Error> {
+        if size == 0 {
+            Err(Error::ConflictingSettings(
+                "The frame size must be at least 1 byte".into(),
+            ))?
+        }
+        Ok(FixedSize(size))
+    }
+
+    pub fn size(self) -> usize {
+        self.0
+    }
+}
+
+impl Framing for FixedSize {
+    fn decode(
+        &mut self,
+        received: &[u8],
+        _searched: usize,
+    ) -> Result<Option<Frame>, Cow<'static, str>> {
+        Ok((received.len() >= self.0).then(|| Frame::whole(self.0)))
+    }
+
+    fn encode(&mut self, message: &[u8], out: &mut Vec<u8>) -> Result<(), Cow<'static, str>> {
+        if message.len() != self.0 {
+            return Err(format!(
+                "Message of {} bytes doesn't match the frame size of {} bytes",
+                message.len(),
+                self.0
+            )
+            .into());
+        }
+        out.extend_from_slice(message);
+        Ok(())
+    }
+}
+
+/// Every message starts with its length as a big endian number. Messages longer than 50 MB are
+/// rejected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LengthPrefixed {
+    U16,
+    U32,
+}
+
+impl LengthPrefixed {
+    fn width(self) -> usize {
+        match self {
+            LengthPrefixed::U16 => 2,
+            LengthPrefixed::U32 => 4,
+        }
+    }
+}
+
+impl Framing for LengthPrefixed {
+    fn decode(
+        &mut self,
+        received: &[u8],
+        _searched: usize,
+    ) -> Result<Option<Frame>, Cow<'static, str>> {
+        let width = self.width();
+        let Some(prefix) = received.get(..width) else {
+            return Ok(None);
+        };
+        let len = prefix
+            .iter()
+            .fold(0usize, |len, byte| len << 8 | usize::from(*byte));
+        if len > MAXIMUM_BUFFER_SIZE {
+            Err(format!(
+                "Frame of {len} bytes exceeds the maximum size of {MAXIMUM_BUFFER_SIZE} bytes"
+            ))?
+        }
+        Ok((received.len() >= width + len).then(|| Frame {
+            payload: width..width + len,
+            len: width + len,
+        }))
+    }
+
+    fn encode(&mut self, message: &[u8], out: &mut Vec<u8>) -> Result<(), Cow<'static, str>> {
+        if message.len() > MAXIMUM_BUFFER_SIZE {
+            Err(format!(
+                "Message of {} bytes exceeds the maximum size of {MAXIMUM_BUFFER_SIZE} bytes",
+                message.len()
+            ))?
+        }
+        let prefix = match self {
+            LengthPrefixed::U16 => {
+                u16::try_from(message.len()).map(|len| len.to_be_bytes().to_vec())
+            }
+            LengthPrefixed::U32 => {
+                u32::try_from(message.len()).map(|len| len.to_be_bytes().to_vec())
+            }
+        }
+        .map_err(|_| {
+            format!(
+                "Message of {} bytes is too long for a {} byte length prefix",
+                message.len(),
+                self.width()
+            )
+        })?;
+        out.extend_from_slice(&prefix);
+        out.extend_from_slice(message);
+        Ok(())
+    }
+}
+
+/// Frames found by a closure. Messages are written without any framing. Frames that don't fit
+/// in the received bytes are reported as errors by the connection.
+/// ```rust
+/// use instrument_communication::framing::{Custom, Frame, Framing};
+/// // Frames of an STX, the message and an ETX.
+/// let mut framing = Custom::new(|received: &[u8]| {
+///     let end = received.iter().position(|byte| *byte == 0x03)?;
+///     Some(Frame { payload: 1..end, len: end + 1 })
+/// });
+/// let frame = framing.decode(b"\x02OK\x03\x02", 0).unwrap().unwrap();
+/// assert_eq!(frame.payload, 1..3);
+/// ```
+pub struct Custom<F> {
+    decode: F,
+}
+
+impl<F> Custom<F>
+where
+    F: FnMut(&[u8]) -> Option<Frame> + Send,
+{
+    pub fn new(decode: F) -> Custom<F> {
+        Custom { decode }
+    }
+}
+
+impl<F> Framing for Custom<F>
+where
+    F: FnMut(&[u8]) -> Option<Frame> + Send,
+{
+    fn decode(
+        &mut self,
+        received: &[u8],
+        _searched: usize,
+    ) -> Result<Option<Frame>, Cow<'static, str>> {
+        Ok((self.decode)(received))
+    }
+
+    fn encode(&mut self, message: &[u8], out: &mut Vec<u8>) -> Result<(), Cow<'static, str>> {
+        out.extend_from_slice(message);
+        Ok(())
+    }
+}
+
+impl<F> fmt::Debug for Custom<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Custom")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(b"ab\r", 0, None)]
+    #[test_case(b"ab\r\ncd", 3, Some(Frame { payload: 0..2, len: 4 }))]
+    #[test_case(b"a\nb\r\n", 0, Some(Frame { payload: 0..3, len: 5 }))]
+    fn terminator_split_across_reads(received: &[u8], searched: usize, frame: Option<Frame>) {
+        assert_eq!(TerminationBytes::CRLF.decode(received, searched), Ok(frame));
+    }
+
+    #[test]
+    fn fixed_size_frames() {
+        let mut framing = FixedSize::new(4).unwrap();
+        assert_eq!(framing.decode(b"abc", 0), Ok(None));
+        assert_eq!(framing.decode(b"abcde", 0), Ok(Some(Frame::whole(4))));
+        let mut out = Vec::new();
+        assert!(framing.encode(b"abc", &mut out).is_err());
+        framing.encode(b"abcd", &mut out).unwrap();
+        assert_eq!(out, b"abcd");
+        assert!(FixedSize::new(0).is_err());
+    }
+
+    #[test_case(LengthPrefixed::U16, b"\x00\x03abc", b"abc")]
+    #[test_case(LengthPrefixed::U32, b"\x00\x00\x00\x02\n\r", b"\n\r")]
+    #[test_case(LengthPrefixed::U16, b"\x00\x00", b"")]
+    fn length_prefixed_frames_round_trip(
+        mut framing: LengthPrefixed,
+        frame: &[u8],
+        message: &[u8],
+    ) {
+        let mut out = Vec::new();
+        framing.encode(message, &mut out).unwrap();
+        assert_eq!(out, frame);
+        let mut received = frame.to_vec();
+        received.extend_from_slice(b"next");
+        let decoded = framing.decode(&received, 0).unwrap().unwrap();
+        assert_eq!(&received[decoded.payload], message);
+        assert_eq!(decoded.len, frame.len());
+        assert_eq!(framing.decode(&frame[..frame.len() - 1], 0), Ok(None));
+    }
+
+    #[test]
+    fn length_prefix_limits_the_message_size() {
+        let mut out = Vec::new();
+        let err = LengthPrefixed::U16
+            .encode(&[0; 65536], &mut out)
+            .unwrap_err();
+        assert_eq!(
+            err,
+            "Message of 65536 bytes is too long for a 2 byte length prefix"
+        );
+        let err = LengthPrefixed::U32
+            .decode(b"\x7F\xFF\xFF\xFF", 0)
+            .unwrap_err();
+        assert_eq!(
+            err,
+            "Frame of 2147483647 bytes exceeds the maximum size of 50000000 bytes"
+        );
+    }
+}
@@ -3,15 +3,19 @@ use communication::InstConnection;
 use err::Error;
 
 pub mod address;
+pub mod binary_block;
 pub mod communication;
 pub mod connection;
+pub mod discovery;
 pub mod err;
+pub mod framing;
+pub mod scpi;
+#[cfg(feature = "serde")]
+mod serde_impl;
 pub mod termination_bytes;
+pub use discovery::discover;
 /// Open a connection to an address provided as a simple string. This simplifies the process of creating
 /// an address object first then opening the connection. This is yet to mature as the API stabilizes.
 pub fn connect<T: AsRef<str>>(address: T) -> Result<Box<dyn InstConnection>, Error> {
-    let _address = InstAddr::new(address).map_err(|msg| {
-        Error::ParseFailed(format!("Failed to create address. Error: {msg}").into())
-    })?;
-    _address.connect()
+    InstAddr::new(address)?.connect()
 }
@@ -0,0 +1,68 @@
+//! Typed SCPI queries. [`ScpiQuery`] sends a query and parses its response with the parsers
+//! of [`parse`].
+use crate::communication::InstConnection;
+use crate::err::Error;
+use parse::{FromScpi, Mnemonic};
+
+pub mod parse;
+
+/// Queries that parse their response. It is implemented for every connection including
+/// `Box<dyn InstConnection>`.
+pub trait ScpiQuery {
+    /// Queries an NR1, NR2 or NR3 number. `9.91E37` is NaN and `9.9E37` is infinity.
+    fn query_f64(&mut self, message: &str) -> Result<f64, Error>;
+    /// Queries a whole number.
+    fn query_i64(&mut self, message: &str) -> Result<i64, Error>;
+    /// Queries a boolean sent as `1`, `0`, `ON` or `OFF`.
+    fn query_bool(&mut self, message: &str) -> Result<bool, Error>;
+    /// Queries comma separated values such as `1.5,2.5,3.5`.
+    fn query_list<T: FromScpi>(&mut self, message: &str) -> Result<Vec<T>, Error>;
+    /// Queries a string. Quotes are removed and doubled quotes inside are undoubled.
+    fn query_string(&mut self, message: &str) -> Result<String, Error>;
+    /// Queries character data and matches it against the short and long forms of `T`.
+    fn query_enum<T: Mnemonic>(&mut self, message: &str) -> Result<T, Error>;
+}
+
+/// Sends the query and parses its response. Parse errors name the query.
+fn query_parsed<C, T>(
+    conn: &mut C,
+    message: &str,
+    parse: impl FnOnce(&str) -> Result<T, Error>,
+) -> Result<T, Error>
+where
+    C: InstConnection + ?Sized,
+{
+    let response = conn.query(message)?;
+    parse(&response).map_err(|e| match e {
+        Error::ParseFailed(msg) => {
+            Error::ParseFailed(format!("{msg} in the response to {message}").into())
+        }
+        e => e,
+    })
+}
+
+impl<C: InstConnection + ?Sized> ScpiQuery for C {
+    fn query_f64(&mut self, message: &str) -> Result<f64, Error> {
+        query_parsed(self, message, parse::f64)
+    }
+
+    fn query_i64(&mut self, message: &str) -> Result<i64, Error> {
+        query_parsed(self, message, parse::i64)
+    }
+
+    fn query_bool(&mut self, message: &str) -> Result<bool, Error> {
+        query_parsed(self, message, parse::bool)
+    }
+
+    fn query_list<T: FromScpi>(&mut self, message: &str) -> Result<Vec<T>, Error> {
+        query_parsed(self, message, parse::list)
+    }
+
+    fn query_string(&mut self, message: &str) -> Result<String, Error> {
+        query_parsed(self, message, parse::string)
+    }
+
+    fn query_enum<T: Mnemonic>(&mut self, message: &str) -> Result<T, Error> {
+        query_parsed(self, message, parse::mnemonic)
+    }
+}
@@ -0,0 +1,351 @@
+//! Parsers of SCPI response data. Every function accepts the response as it was read with the
+//! termination already removed. Surrounding whitespace is ignored.
+use crate::err::Error;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+/// Instruments answer with this value when a measurement is not a number.
+pub const NOT_A_NUMBER: f64 = 9.91e37;
+/// Instruments answer with this value for positive infinity and its negation for negative
+/// infinity.
+pub const INFINITY: f64 = 9.9e37;
+
+lazy_static! {
+    /// NR1 `12`, NR2 `12.5` and NR3 `1.25E+01` followed by an optional suffix unit.
+    static ref NUMBER_REGEX: Regex =
+        Regex::new(r"^([+-]?(?:\d+\.?\d*|\.\d+)(?:[eE][+-]?\d+)?)\s*([A-Za-z%/]*)$").unwrap();
+    /// Hexadecimal `#H1F`, octal `#Q17` and binary `#B101` numbers.
+    static ref NON_DECIMAL_REGEX: Regex = Regex::new(r"^#([HhQqBb])([0-9A-Fa-f]+)$").unwrap();
+}
+
+fn invalid(expected: &str, text: &str) -> Error {
+    Error::ParseFailed(format!("Expected {expected} but got {text:?}").into())
+}
+
+/// Parses a number and returns it with its suffix unit, which is empty if there is none.
+/// `9.91E37` is NaN and `9.9E37` is infinity.
+/// ```rust
+/// use instrument_communication::scpi::parse;
+/// assert_eq!(parse::number_with_unit("+1.25E-03 V").unwrap(), (0.00125, "V"));
+/// assert!(parse::number_with_unit("9.91E37").unwrap().0.is_nan());
+/// ```
+pub fn number_with_unit(text: &str) -> Result<(f64, &str), Error> {
+    let text = text.trim();
+    match text.to_ascii_uppercase().as_str() {
+        "NAN" => return Ok((f64::NAN, "")),
+        "INF" => return Ok((f64::INFINITY, "")),
+        "NINF" => return Ok((f64::NEG_INFINITY, "")),
+        _ => (),
+    }
+    let captures = NUMBER_REGEX
+        .captures(text)
+        .ok_or_else(|| invalid("a number", text))?;
+    let number: f64 = captures[1].parse().map_err(|_| invalid("a number", text))?;
+    let unit = captures.get(2).map_or("", |unit| unit.as_str());
+    let number = if number == NOT_A_NUMBER {
+        f64::NAN
+    } else if number.abs() == INFINITY {
+        f64::INFINITY.copysign(number)
+    } else {
+        number
+    };
+    Ok((number, unit))
+}
+
+/// Parses an NR1, NR2 or NR3 number. A suffix unit is ignored.
+pub fn f64(text: &str) -> Result<f64, Error> {
+    number_with_unit(text).map(|(number, _)| number)
+}
+
+/// Parses a whole number. Instruments often send whole numbers in NR3 such as `+1.0E+01` which
+/// are accepted, as are `#H`, `#Q` and `#B` numbers.
+pub fn i64(text: &str) -> Result<i64, Error> {
+    let text = text.trim();
+    if let Ok(number) = text.parse() {
+        return Ok(number);
+    }
+    if let Some(captures) = NON_DECIMAL_REGEX.captures(text) {
+        let radix = match captures[1].to_ascii_uppercase().as_str() {
+            "H" => 16,
+            "Q" => 8,
+            _ => 2,
+        };
+        return i64::from_str_radix(&captures[2], radix)
+            .map_err(|_| invalid("a whole number", text));
+    }
+    let number = f64(text)?;
+    let in_range = (i64::MIN as f64..i64::MAX as f64).contains(&number);
+    if number.fract() == 0.0 && in_range {
+        Ok(number as i64)
+    } else {
+        Err(invalid("a whole number", text))
+    }
+}
+
+/// Parses `1`, `0`, `ON` or `OFF`.
+pub fn bool(text: &str) -> Result<bool, Error> {
+    let text = text.trim();
+    match text.to_ascii_uppercase().as_str() {
+        "1" | "+1" | "ON" => Ok(true),
+        "0" | "+0" | "OFF" => Ok(false),
+        _ => Err(invalid("1, 0, ON or OFF", text)),
+    }
+}
+
+/// Parses a string in double or single quotes where a quote inside is doubled, e.g.
+/// `"say ""hi"""`. Unquoted text is returned as is.
+pub fn string(text: &str) -> Result<String, Error> {
+    let text = text.trim();
+    let Some(quote) = text.chars().next().filter(|c| matches!(c, '"' | '\'')) else {
+        return Ok(text.to_owned());
+    };
+    let inner = text[1..]
+        .strip_suffix(quote)
+        .ok_or_else(|| invalid("a closing quote", text))?;
+    let mut string = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c == quote && chars.next() != Some(quote) {
+            return Err(invalid("quotes inside a string to be doubled", text));
+        }
+        string.push(c);
+    }
+    Ok(string)
+}
+
+/// Splits comma separated values. Commas inside quoted strings don't separate values.
+pub fn split(text: &str) -> Vec<&str> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Vec::new();
+    }
+    let mut values = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    for (index, c) in text.char_indices() {
+        match (quote, c) {
+            // A doubled quote closes then opens the string again.
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            (None, ',') => {
+                values.push(text[start..index].trim());
+                start = index + 1;
+            }
+            _ => (),
+        }
+    }
+    values.push(text[start..].trim());
+    values
+}
+
+/// Parses comma separated values.
+pub fn list<T: FromScpi>(text: &str) -> Result<Vec<T>, Error> {
+    split(text).into_iter().map(T::from_scpi).collect()
+}
+
+/// True if `text` is the short or the long form of a mnemonic such as `VOLTage` whose short
+/// form is its uppercase part.
+pub fn matches_mnemonic(text: &str, mnemonic: &str) -> bool {
+    let short: String = mnemonic
+        .chars()
+        .filter(|c| !c.is_ascii_lowercase())
+        .collect();
+    text.eq_ignore_ascii_case(&short) || text.eq_ignore_ascii_case(mnemonic)
+}
+
+/// Parses character data such as `VOLT` into the value whose mnemonic it matches.
+pub fn mnemonic<T: Mnemonic>(text: &str) -> Result<T, Error> {
+    let text = text.trim();
+    T::MNEMONICS
+        .iter()
+        .find(|(mnemonic, _)| matches_mnemonic(text, mnemonic))
+        .map(|(_, value)| *value)
+        .ok_or_else(|| {
+            let names: Vec<_> = T::MNEMONICS.iter().map(|(name, _)| *name).collect();
+            invalid(&format!("one of {}", names.join(", ")), text)
+        })
+}
+
+/// A value that can be parsed from a single SCPI response value.
+pub trait FromScpi: Sized {
+    fn from_scpi(text: &str) -> Result<Self, Error>;
+}
+
+impl FromScpi for f64 {
+    fn from_scpi(text: &str) -> Result<Self, Error> {
+        f64(text)
+    }
+}
+
+impl FromScpi for f32 {
+    fn from_scpi(text: &str) -> Result<Self, Error> {
+        f64(text).map(|number| number as f32)
+    }
+}
+
+impl FromScpi for bool {
+    fn from_scpi(text: &str) -> Result<Self, Error> {
+        bool(text)
+    }
+}
+
+impl FromScpi for String {
+    fn from_scpi(text: &str) -> Result<Self, Error> {
+        string(text)
+    }
+}
+
+macro_rules! from_scpi_int {
+    ($($int:ty),*) => {
+        $(impl FromScpi for $int {
+            fn from_scpi(text: &str) -> Result<Self, Error> {
+                <$int>::try_from(i64(text)?).map_err(|_| invalid(stringify!($int), text.trim()))
+            }
+        })*
+    };
+}
+
+from_scpi_int!(i8, u8, i16, u16, i32, u32, i64, u64, usize);
+
+/// An enum of character data. Each value has a mnemonic such as `VOLTage` whose uppercase
+/// part is its short form.
+/// ```rust
+/// use instrument_communication::scpi::parse::{self, Mnemonic};
+/// #[derive(Clone, Copy, Debug, PartialEq)]
+/// enum Function {
+///     Voltage,
+///     Current,
+/// }
+/// impl Mnemonic for Function {
+///     const MNEMONICS: &'static [(&'static str, Self)] =
+///         &[("VOLTage", Function::Voltage), ("CURRent", Function::Current)];
+/// }
+/// assert_eq!(parse::mnemonic::<Function>("CURR").unwrap(), Function::Current);
+/// ```
+pub trait Mnemonic: Copy + 'static {
+    const MNEMONICS: &'static [(&'static str, Self)];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("12", 12.0 ; "nr1")]
+    #[test_case("-12.5", -12.5 ; "nr2")]
+    #[test_case("+1.25E+01", 12.5 ; "nr3")]
+    #[test_case(" .5e-3 ", 0.0005 ; "leading point")]
+    #[test_case("5.", 5.0 ; "trailing point")]
+    #[test_case("1.5 V", 1.5 ; "unit")]
+    #[test_case("10HZ", 10.0 ; "unit without space")]
+    #[test_case("9.9E37", f64::INFINITY ; "infinity")]
+    #[test_case("-9.9E+37", f64::NEG_INFINITY ; "negative infinity")]
+    #[test_case("NINF", f64::NEG_INFINITY ; "ninf")]
+    fn numbers(text: &str, number: f64) {
+        assert_eq!(f64(text).unwrap(), number);
+    }
+
+    #[test_case("9.91E37")]
+    #[test_case("+9.910000E+37")]
+    #[test_case("nan")]
+    fn not_a_number(text: &str) {
+        assert!(f64(text).unwrap().is_nan());
+    }
+
+    #[test_case("" ; "empty")]
+    #[test_case("1.2.3" ; "two points")]
+    #[test_case("E5" ; "no mantissa")]
+    #[test_case("1 2" ; "two numbers")]
+    fn invalid_numbers(text: &str) {
+        assert!(f64(text).is_err());
+    }
+
+    #[test_case("+42", 42)]
+    #[test_case("-1.000E+01", -10)]
+    #[test_case("#H1F", 31)]
+    #[test_case("#q17", 15)]
+    #[test_case("#B101", 5)]
+    #[test_case("9223372036854775807", i64::MAX)]
+    fn whole_numbers(text: &str, number: i64) {
+        assert_eq!(i64(text).unwrap(), number);
+    }
+
+    #[test]
+    fn fractions_are_not_whole_numbers() {
+        assert_eq!(
+            i64("1.5").unwrap_err().to_string(),
+            "Expected a whole number but got \"1.5\""
+        );
+        assert!(u8::from_scpi("256").is_err());
+    }
+
+    #[test_case("1", true)]
+    #[test_case("on", true)]
+    #[test_case("OFF", false)]
+    #[test_case(" 0 ", false)]
+    fn booleans(text: &str, value: bool) {
+        assert_eq!(bool(text).unwrap(), value);
+    }
+
+    #[test_case(r#""say ""hi""""#, r#"say "hi""# ; "doubled double quotes")]
+    #[test_case("'it''s'", "it's" ; "doubled single quotes")]
+    #[test_case(r#"'a "b"'"#, r#"a "b""# ; "other quote")]
+    #[test_case("Cosmere,mock1000", "Cosmere,mock1000" ; "unquoted")]
+    #[test_case(r#""""#, "" ; "empty")]
+    fn strings(text: &str, string: &str) {
+        assert_eq!(super::string(text).unwrap(), string);
+    }
+
+    #[test_case(r#""abc"# ; "unterminated")]
+    #[test_case(r#""a"b""# ; "single quote inside")]
+    fn invalid_strings(text: &str) {
+        assert!(string(text).is_err());
+    }
+
+    #[test]
+    fn lists_split_outside_quotes() {
+        assert_eq!(
+            split(r#"1, "a,""b""", 'c,d' ,2"#),
+            ["1", r#""a,""b""""#, "'c,d'", "2"]
+        );
+        assert_eq!(
+            list::<f64>("+1.0E+00,-2.5,9.91E37").unwrap()[..2],
+            [1.0, -2.5]
+        );
+        assert!(list::<i32>(" ").unwrap().is_empty());
+        assert_eq!(
+            list::<String>(r#""a,b",c"#).unwrap(),
+            ["a,b".to_owned(), "c".to_owned()]
+        );
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Coupling {
+        Ac,
+        Dc,
+        Ground,
+    }
+
+    impl Mnemonic for Coupling {
+        const MNEMONICS: &'static [(&'static str, Self)] = &[
+            ("AC", Coupling::Ac),
+            ("DC", Coupling::Dc),
+            ("GROund", Coupling::Ground),
+        ];
+    }
+
+    #[test_case("GRO", Coupling::Ground)]
+    #[test_case("ground", Coupling::Ground)]
+    #[test_case("dc", Coupling::Dc)]
+    fn mnemonics_match_short_and_long_form(text: &str, value: Coupling) {
+        assert_eq!(mnemonic::<Coupling>(text).unwrap(), value);
+    }
+
+    #[test]
+    fn partial_mnemonics_are_rejected() {
+        assert_eq!(
+            mnemonic::<Coupling>("GROU").unwrap_err().to_string(),
+            "Expected one of AC, DC, GROund but got \"GROU\""
+        );
+    }
+}
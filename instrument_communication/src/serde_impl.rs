@@ -0,0 +1,91 @@
+//! (De)serialization of addresses and termination bytes through their canonical text form, so
+//! they can be stored in station configurations. Deserialization accepts every form the parser
+//! accepts, serialization writes the canonical one.
+use crate::address::socket::{NetworkAddr, Socket};
+use crate::address::{InstAddr, RawSocket, VisaAddress};
+use crate::termination_bytes::TerminationBytes;
+use serde::de::{Deserialize, Deserializer, Error};
+use serde::ser::{Serialize, Serializer};
+use std::borrow::Cow;
+
+macro_rules! serde_via_str {
+    ($($ty:ty),*) => {$(
+        impl Serialize for $ty {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $ty {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let text = <Cow<'de, str>>::deserialize(deserializer)?;
+                text.parse().map_err(D::Error::custom)
+            }
+        }
+    )*};
+}
+
+serde_via_str!(
+    InstAddr,
+    VisaAddress,
+    Socket,
+    RawSocket,
+    NetworkAddr,
+    TerminationBytes
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("GPIB0::7::3::INSTR", "\"gpib0::7::3::instr\"")]
+    #[test_case(
+        "USB0::0x2A8D::0x0101::MY123::INSTR",
+        "\"usb0::0x2a8d::0x0101::MY123::instr\""
+    )]
+    #[test_case(
+        "ASRL/dev/ttyS1::INSTR::115200,7E2",
+        "\"asrl/dev/ttyS1::instr::115200,7e2,none\""
+    )]
+    #[test_case(
+        "TCPIP::[fe80::1%3]::5025::SOCKET",
+        "\"tcpip0::[fe80::1%3]::5025::socket\""
+    )]
+    #[test_case("10.0.0.2:5025", "\"10.0.0.2:5025\"")]
+    #[test_case("[fe80::1%3]:5025", "\"[fe80::1%3]:5025\"")]
+    #[test_case("[fe80::1%eth7]:5025", "\"[fe80::1%eth7]:5025\"")]
+    #[test_case("scope-lab-3:5025", "\"scope-lab-3:5025\"")]
+    fn addresses_round_trip(address: &str, json: &str) {
+        let address: InstAddr = address.parse().unwrap();
+        assert_eq!(serde_json::to_string(&address).unwrap(), json);
+        assert_eq!(serde_json::from_str::<InstAddr>(json).unwrap(), address);
+    }
+
+    #[test]
+    fn typed_values_round_trip() {
+        let visa: VisaAddress = "TCPIP0::10.0.0.5::inst1::INSTR".parse().unwrap();
+        let json = serde_json::to_string(&visa).unwrap();
+        assert_eq!(serde_json::from_str::<VisaAddress>(&json).unwrap(), visa);
+        let raw: RawSocket = "scope-lab-3:5025".parse().unwrap();
+        let json = serde_json::to_string(&raw).unwrap();
+        assert_eq!(serde_json::from_str::<RawSocket>(&json).unwrap(), raw);
+        for term in [
+            TerminationBytes::CRLF,
+            TerminationBytes::None,
+            TerminationBytes::Custom(vec![0x03, 0x0d]),
+            TerminationBytes::Custom(vec![]),
+        ] {
+            let json = serde_json::to_string(&term).unwrap();
+            assert_eq!(
+                serde_json::from_str::<TerminationBytes>(&json).unwrap(),
+                term
+            );
+        }
+        assert_eq!(
+            serde_json::to_string(&visa::Binary::NiVisa).unwrap(),
+            "\"nivisa\""
+        );
+        assert!(serde_json::from_str::<InstAddr>("\"GPIB0::40::INSTR\"").is_err());
+    }
+}
@@ -1,3 +1,7 @@
+use crate::err::Error;
+use std::fmt;
+use std::str::FromStr;
+
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
 pub enum TerminationBytes {
     #[default]
@@ -18,4 +22,64 @@ impl TerminationBytes {
             TerminationBytes::Custom(bytes) => bytes.as_slice(),
         }
     }
+
+    /// Finds the first occurrence of the termination bytes in `data` starting at `start`.
+    /// Returns the index of the first termination byte. An empty termination never matches.
+    pub fn find(&self, data: &[u8], start: usize) -> Option<usize> {
+        let term = self.bytes();
+        if term.is_empty() || data.len() < term.len() {
+            return None;
+        }
+        let start = start.min(data.len());
+        data[start..]
+            .windows(term.len())
+            .position(|window| window == term)
+            .map(|pos| pos + start)
+    }
+
+    /// Removes the termination bytes from the end of the message if they are present.
+    pub fn strip<'a>(&self, message: &'a [u8]) -> &'a [u8] {
+        message.strip_suffix(self.bytes()).unwrap_or(message)
+    }
+}
+
+/// `lf`, `cr`, `crlf`, `none` or custom bytes in hex such as `0x03`. Empty custom bytes are `0x`.
+impl fmt::Display for TerminationBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TerminationBytes::LF => f.write_str("lf"),
+            TerminationBytes::CR => f.write_str("cr"),
+            TerminationBytes::CRLF => f.write_str("crlf"),
+            TerminationBytes::None => f.write_str("none"),
+            TerminationBytes::Custom(bytes) => {
+                f.write_str("0x")?;
+                bytes.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+            }
+        }
+    }
+}
+
+impl FromStr for TerminationBytes {
+    type Err = Error;
+    fn from_str(term: &str) -> Result<Self, Self::Err> {
+        let term = term.trim().to_ascii_lowercase();
+        let invalid = || Error::ParseFailed(format!("Invalid termination {term}").into());
+        match term.as_str() {
+            "lf" => Ok(TerminationBytes::LF),
+            "cr" => Ok(TerminationBytes::CR),
+            "crlf" => Ok(TerminationBytes::CRLF),
+            "none" => Ok(TerminationBytes::None),
+            _ => {
+                let hex = term.strip_prefix("0x").ok_or_else(invalid)?;
+                if hex.len() % 2 != 0 || !hex.is_ascii() {
+                    return Err(invalid());
+                }
+                (0..hex.len())
+                    .step_by(2)
+                    .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid()))
+                    .collect::<Result<_, _>>()
+                    .map(TerminationBytes::Custom)
+            }
+        }
+    }
 }
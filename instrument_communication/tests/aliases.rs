@@ -0,0 +1,35 @@
+//! Aliases are installed for the whole process so every check runs in one test.
+use instrument_communication::address::alias::Aliases;
+use instrument_communication::address::InstAddr;
+
+#[test]
+fn aliases_resolve_through_the_file_then_the_binary() {
+    let file = concat!(env!("CARGO_MANIFEST_DIR"), "/../aliases.txt");
+    Aliases::load(file)
+        .unwrap()
+        .with_binary(visa_mock::binary())
+        .install();
+
+    assert_eq!(
+        InstAddr::new("psu1").unwrap(),
+        "localhost:5025".parse().unwrap()
+    );
+    assert_eq!(
+        InstAddr::new(" SCOPE1 ").unwrap(),
+        "TCPIP0::localhost::inst0::INSTR".parse().unwrap()
+    );
+
+    // Not in the file but configured in the mock's connection expert.
+    Aliases::new().with_binary(visa_mock::binary()).install();
+    assert_eq!(
+        InstAddr::new("DMM1").unwrap(),
+        "GPIB0::7::INSTR".parse().unwrap()
+    );
+
+    let err = InstAddr::new("DMM2").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Unrecognized address format, expected host:port"
+    );
+    assert_eq!(err.span(), 0..4);
+}
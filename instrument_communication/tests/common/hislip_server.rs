@@ -0,0 +1,330 @@
+//! A HiSLIP stand-in instrument for the native client tests. It listens on an ephemeral port of
+//! the loopback interface and accepts any number of sessions.
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const IDENTITY: &str = "Cosmere,hislip-mock,0,1.0";
+/// The largest message the stand-in accepts including the header.
+pub const MAX_MESSAGE_SIZE: u64 = 1024;
+/// The length of the response to `DATA?`.
+pub const DATA_LENGTH: usize = 10000;
+/// The stand-in only speaks HiSLIP 1.1.
+const PROTOCOL_VERSION: u32 = 0x0101;
+const HEADER_SIZE: usize = 16;
+const FIRST_MESSAGE_ID: u32 = 0xFFFF_FF00;
+const MAV: u8 = 0x10;
+
+const INITIALIZE: u8 = 0;
+const INITIALIZE_RESPONSE: u8 = 1;
+const ERROR: u8 = 3;
+const ASYNC_LOCK: u8 = 4;
+const ASYNC_LOCK_RESPONSE: u8 = 5;
+const DATA: u8 = 6;
+const DATA_END: u8 = 7;
+const DEVICE_CLEAR_COMPLETE: u8 = 8;
+const DEVICE_CLEAR_ACKNOWLEDGE: u8 = 9;
+const TRIGGER: u8 = 12;
+const INTERRUPTED: u8 = 13;
+const ASYNC_INTERRUPTED: u8 = 14;
+const ASYNC_MAXIMUM_MESSAGE_SIZE: u8 = 15;
+const ASYNC_MAXIMUM_MESSAGE_SIZE_RESPONSE: u8 = 16;
+const ASYNC_INITIALIZE: u8 = 17;
+const ASYNC_INITIALIZE_RESPONSE: u8 = 18;
+const ASYNC_DEVICE_CLEAR: u8 = 19;
+const ASYNC_STATUS_QUERY: u8 = 21;
+const ASYNC_STATUS_RESPONSE: u8 = 22;
+const ASYNC_DEVICE_CLEAR_ACKNOWLEDGE: u8 = 23;
+
+struct Message {
+    kind: u8,
+    control: u8,
+    parameter: u32,
+    payload: Vec<u8>,
+}
+
+struct Session {
+    overlapped: bool,
+    input: Vec<u8>,
+    /// Responses that were sent but whose delivery the client didn't confirm yet.
+    unread: usize,
+    /// The ID of the last message that was handled on the synchronous channel.
+    processed: u32,
+    asynchronous: Option<TcpStream>,
+}
+
+impl Session {
+    fn new() -> Session {
+        Session {
+            overlapped: false,
+            input: Vec::new(),
+            unread: 0,
+            processed: FIRST_MESSAGE_ID.wrapping_sub(2),
+            asynchronous: None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Instrument {
+    next_session: u16,
+    sessions: HashMap<u16, Session>,
+    lock: Option<u16>,
+    triggers: u32,
+}
+
+impl Instrument {
+    fn execute(&mut self, input: &[u8]) -> Vec<String> {
+        let mut responses = Vec::new();
+        for command in String::from_utf8_lossy(input).lines() {
+            let command = command.trim();
+            if command == "*IDN?" {
+                responses.push(IDENTITY.to_owned());
+            } else if command == "DATA?" {
+                responses.push("0123456789".repeat(DATA_LENGTH / 10));
+            } else if command == "TRG:COUNT?" {
+                responses.push(self.triggers.to_string());
+            } else if let Some(text) = command.strip_prefix("ECHO ") {
+                responses.push(text.to_owned());
+            }
+        }
+        responses
+    }
+}
+
+/// A running stand-in. It keeps running until the test process exits.
+pub struct HislipServer {
+    pub port: u16,
+    instrument: Arc<Mutex<Instrument>>,
+}
+
+impl HislipServer {
+    pub fn start() -> HislipServer {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let instrument = Arc::new(Mutex::new(Instrument::default()));
+        let shared = instrument.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let instrument = shared.clone();
+                thread::spawn(move || serve(&instrument, stream));
+            }
+        });
+        HislipServer { port, instrument }
+    }
+
+    /// The resource string of the stand-in instrument.
+    pub fn resource(&self) -> String {
+        format!("TCPIP::127.0.0.1::hislip0,{}::INSTR", self.port)
+    }
+
+    pub fn sessions(&self) -> usize {
+        self.instrument.lock().unwrap().sessions.len()
+    }
+}
+
+fn send(
+    stream: &mut TcpStream,
+    kind: u8,
+    control: u8,
+    parameter: u32,
+    payload: &[u8],
+) -> io::Result<()> {
+    let mut message = b"HS".to_vec();
+    message.extend_from_slice(&[kind, control]);
+    message.extend_from_slice(&parameter.to_be_bytes());
+    message.extend_from_slice(&(payload.len() as u64).to_be_bytes());
+    message.extend_from_slice(payload);
+    stream.write_all(&message)
+}
+
+fn receive(stream: &mut TcpStream) -> io::Result<Message> {
+    let mut header = [0u8; HEADER_SIZE];
+    stream.read_exact(&mut header)?;
+    assert_eq!(&header[..2], b"HS");
+    let len = u64::from_be_bytes(header[8..].try_into().unwrap());
+    let mut payload = vec![0u8; len as usize];
+    stream.read_exact(&mut payload)?;
+    Ok(Message {
+        kind: header[2],
+        control: header[3],
+        parameter: u32::from_be_bytes(header[4..8].try_into().unwrap()),
+        payload,
+    })
+}
+
+fn serve(instrument: &Mutex<Instrument>, mut stream: TcpStream) -> io::Result<()> {
+    let first = receive(&mut stream)?;
+    match first.kind {
+        INITIALIZE => {
+            let session_id = {
+                let mut instrument = instrument.lock().unwrap();
+                instrument.next_session += 1;
+                let session_id = instrument.next_session;
+                instrument.sessions.insert(session_id, Session::new());
+                session_id
+            };
+            let parameter = PROTOCOL_VERSION << 16 | session_id as u32;
+            send(&mut stream, INITIALIZE_RESPONSE, 0, parameter, &[])?;
+            let result = serve_sync(instrument, session_id, &mut stream);
+            let mut instrument = instrument.lock().unwrap();
+            instrument.sessions.remove(&session_id);
+            if instrument.lock == Some(session_id) {
+                instrument.lock = None;
+            }
+            result
+        }
+        ASYNC_INITIALIZE => {
+            let session_id = first.parameter as u16;
+            if let Some(session) = instrument.lock().unwrap().sessions.get_mut(&session_id) {
+                session.asynchronous = Some(stream.try_clone()?);
+            }
+            send(&mut stream, ASYNC_INITIALIZE_RESPONSE, 0, 0, &[])?;
+            serve_async(instrument, session_id, &mut stream)
+        }
+        _ => Ok(()),
+    }
+}
+
+fn serve_sync(
+    instrument: &Mutex<Instrument>,
+    session_id: u16,
+    stream: &mut TcpStream,
+) -> io::Result<()> {
+    loop {
+        let message = receive(stream)?;
+        let mut guard = instrument.lock().unwrap();
+        let instrument = &mut *guard;
+        let session = instrument.sessions.get_mut(&session_id).unwrap();
+        if message.control & 1 != 0 && matches!(message.kind, DATA | DATA_END | TRIGGER) {
+            session.unread = 0;
+        }
+        match message.kind {
+            DATA | DATA_END => {
+                if message.payload.len() + HEADER_SIZE > MAX_MESSAGE_SIZE as usize {
+                    send(stream, ERROR, 1, 0, b"Message is too large")?;
+                    continue;
+                }
+                // In synchronized mode a new message discards the unread responses.
+                if !session.overlapped && session.unread > 0 && session.input.is_empty() {
+                    session.unread = 0;
+                    send(stream, INTERRUPTED, 0, message.parameter, &[])?;
+                    if let Some(asynchronous) = &mut session.asynchronous {
+                        send(asynchronous, ASYNC_INTERRUPTED, 0, message.parameter, &[])?;
+                    }
+                }
+                session.input.extend_from_slice(&message.payload);
+                if message.kind == DATA_END {
+                    let input = std::mem::take(&mut session.input);
+                    let responses = instrument.execute(&input);
+                    let session = instrument.sessions.get_mut(&session_id).unwrap();
+                    for response in responses {
+                        let response = format!("{response}\n");
+                        let mut chunks = response.as_bytes().chunks(1024).peekable();
+                        while let Some(chunk) = chunks.next() {
+                            let kind = if chunks.peek().is_none() {
+                                DATA_END
+                            } else {
+                                DATA
+                            };
+                            send(stream, kind, 0, message.parameter, chunk)?;
+                        }
+                        session.unread += 1;
+                    }
+                }
+                instrument.sessions.get_mut(&session_id).unwrap().processed = message.parameter;
+            }
+            TRIGGER => {
+                session.processed = message.parameter;
+                instrument.triggers += 1;
+            }
+            DEVICE_CLEAR_COMPLETE => {
+                session.overlapped = message.control & 1 != 0;
+                session.input.clear();
+                session.unread = 0;
+                session.processed = FIRST_MESSAGE_ID.wrapping_sub(2);
+                let control = session.overlapped as u8;
+                send(stream, DEVICE_CLEAR_ACKNOWLEDGE, control, 0, &[])?;
+            }
+            _ => (),
+        }
+    }
+}
+
+fn serve_async(
+    instrument: &Mutex<Instrument>,
+    session_id: u16,
+    stream: &mut TcpStream,
+) -> io::Result<()> {
+    loop {
+        let message = receive(stream)?;
+        match message.kind {
+            ASYNC_MAXIMUM_MESSAGE_SIZE => {
+                let size = MAX_MESSAGE_SIZE.to_be_bytes();
+                send(stream, ASYNC_MAXIMUM_MESSAGE_SIZE_RESPONSE, 0, 0, &size)?;
+            }
+            ASYNC_DEVICE_CLEAR => {
+                let overlapped = instrument.lock().unwrap().sessions[&session_id].overlapped;
+                send(
+                    stream,
+                    ASYNC_DEVICE_CLEAR_ACKNOWLEDGE,
+                    overlapped as u8,
+                    0,
+                    &[],
+                )?;
+            }
+            ASYNC_STATUS_QUERY => {
+                // The status includes every message up to the one named by the query.
+                let deadline = Instant::now() + Duration::from_secs(1);
+                let mut instrument = loop {
+                    let instrument = instrument.lock().unwrap();
+                    if instrument.sessions[&session_id].processed == message.parameter
+                        || Instant::now() >= deadline
+                    {
+                        break instrument;
+                    }
+                    drop(instrument);
+                    thread::sleep(Duration::from_millis(1));
+                };
+                let session = instrument.sessions.get_mut(&session_id).unwrap();
+                if message.control & 1 != 0 {
+                    session.unread = 0;
+                }
+                let stb = if session.unread > 0 { MAV } else { 0 };
+                drop(instrument);
+                send(stream, ASYNC_STATUS_RESPONSE, stb, 0, &[])?;
+            }
+            ASYNC_LOCK if message.control == 1 => {
+                let deadline = Instant::now() + Duration::from_millis(message.parameter as u64);
+                let granted = loop {
+                    let mut instrument = instrument.lock().unwrap();
+                    if instrument.lock.is_none() || instrument.lock == Some(session_id) {
+                        instrument.lock = Some(session_id);
+                        break true;
+                    }
+                    drop(instrument);
+                    if Instant::now() >= deadline {
+                        break false;
+                    }
+                    thread::sleep(Duration::from_millis(5));
+                };
+                send(stream, ASYNC_LOCK_RESPONSE, granted as u8, 0, &[])?;
+            }
+            ASYNC_LOCK => {
+                let mut instrument = instrument.lock().unwrap();
+                let control = if instrument.lock == Some(session_id) {
+                    instrument.lock = None;
+                    1
+                } else {
+                    3
+                };
+                drop(instrument);
+                send(stream, ASYNC_LOCK_RESPONSE, control, 0, &[])?;
+            }
+            _ => (),
+        }
+    }
+}
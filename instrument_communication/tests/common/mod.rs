@@ -0,0 +1,17 @@
+// Every test binary only uses the stand-in servers it needs.
+#![allow(dead_code)]
+
+pub mod hislip_server;
+pub mod vxi11_server;
+
+use instrument_communication::address::InstAddr;
+use instrument_communication::connection::visa_conn::VisaConn;
+use instrument_communication::err::Error;
+
+/// Opens a resource of the mock VISA library.
+pub fn open(address: &str) -> Result<VisaConn, Error> {
+    let InstAddr::Visa(address) = InstAddr::new(address).unwrap() else {
+        panic!("{address} should be a visa address");
+    };
+    VisaConn::connect(address, Some(visa_mock::binary()))
+}
@@ -0,0 +1,401 @@
+//! A VXI-11 stand-in instrument for the native client tests. It serves the portmapper, the core
+//! channel and the abort channel on ephemeral ports of the loopback interface.
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const IDENTITY: &str = "Cosmere,vxi11-mock,0,1.0";
+/// The instrument accepts at most this many bytes per device_write.
+pub const MAX_RECV_SIZE: u32 = 1024;
+/// The length of the response to `DATA?`.
+pub const DATA_LENGTH: usize = 10000;
+
+const DEVICE_CORE: u32 = 0x0607AF;
+const MAV: u32 = 0x10;
+
+const FLAG_WAITLOCK: u32 = 0x01;
+const FLAG_END: u32 = 0x08;
+const FLAG_TERMCHRSET: u32 = 0x80;
+const REASON_REQCNT: u32 = 0x01;
+const REASON_CHR: u32 = 0x02;
+const REASON_END: u32 = 0x04;
+
+const ERROR_INVALID_LINK: u32 = 4;
+const ERROR_DEVICE_LOCKED: u32 = 11;
+const ERROR_NO_LOCK_HELD: u32 = 12;
+const ERROR_IO_TIMEOUT: u32 = 15;
+
+#[derive(Default)]
+struct Link {
+    /// The device name the link was created for such as `inst0` or `gpib0,7`.
+    device: String,
+    input: Vec<u8>,
+    output: Vec<u8>,
+}
+
+#[derive(Default)]
+struct Instrument {
+    next_link: u32,
+    links: HashMap<u32, Link>,
+    lock: Option<u32>,
+    triggers: u32,
+    aborts: u32,
+    /// The input buffer is full so device_write accepts no data.
+    input_full: bool,
+}
+
+impl Instrument {
+    fn execute(&mut self, lid: u32) {
+        let input = std::mem::take(&mut self.links.get_mut(&lid).unwrap().input);
+        for command in String::from_utf8_lossy(&input).lines() {
+            let command = command.trim();
+            let response = if command == "*IDN?" {
+                IDENTITY.to_owned()
+            } else if command == "DATA?" {
+                "0123456789".repeat(DATA_LENGTH / 10)
+            } else if command == "CURV?" {
+                // A block with the term char in its data.
+                "#14a\nbc".to_owned()
+            } else if command == "TRG:COUNT?" {
+                self.triggers.to_string()
+            } else if let Some(text) = command.strip_prefix("ECHO ") {
+                text.to_owned()
+            } else {
+                continue;
+            };
+            let output = &mut self.links.get_mut(&lid).unwrap().output;
+            output.extend_from_slice(response.as_bytes());
+            output.push(b'\n');
+        }
+    }
+}
+
+/// A running stand-in. The servers keep running until the test process exits.
+pub struct Vxi11Server {
+    pub portmapper_port: u16,
+    instrument: Arc<Mutex<Instrument>>,
+}
+
+impl Vxi11Server {
+    pub fn start() -> Vxi11Server {
+        let instrument = Arc::new(Mutex::new(Instrument::default()));
+        let abort_port = listen({
+            let instrument = instrument.clone();
+            move |_procedure, args, reply| {
+                let lid = args.u32();
+                let mut instrument = instrument.lock().unwrap();
+                if instrument.links.contains_key(&lid) {
+                    instrument.aborts += 1;
+                    put(reply, 0);
+                } else {
+                    put(reply, ERROR_INVALID_LINK);
+                }
+            }
+        });
+        let core_port = listen({
+            let instrument = instrument.clone();
+            move |procedure, args, reply| core(&instrument, abort_port, procedure, args, reply)
+        });
+        let portmapper_port = listen(move |_procedure, args, reply| {
+            let program = args.u32();
+            put(
+                reply,
+                if program == DEVICE_CORE {
+                    core_port as u32
+                } else {
+                    0
+                },
+            );
+        });
+        Vxi11Server {
+            portmapper_port,
+            instrument,
+        }
+    }
+
+    /// The resource string of the stand-in instrument.
+    pub fn resource(&self) -> &'static str {
+        "TCPIP::127.0.0.1::INSTR"
+    }
+
+    /// The number of links that weren't destroyed.
+    pub fn links(&self) -> usize {
+        self.instrument.lock().unwrap().links.len()
+    }
+
+    /// The device names of the links that weren't destroyed.
+    pub fn devices(&self) -> Vec<String> {
+        let instrument = self.instrument.lock().unwrap();
+        instrument
+            .links
+            .values()
+            .map(|link| link.device.clone())
+            .collect()
+    }
+
+    pub fn aborts(&self) -> u32 {
+        self.instrument.lock().unwrap().aborts
+    }
+
+    /// Makes every following device_write succeed without accepting any data.
+    pub fn fill_input(&self) {
+        self.instrument.lock().unwrap().input_full = true;
+    }
+}
+
+/// Waits up to the lock timeout when the caller asked to wait for a lock held by another link.
+fn wait_for_lock(instrument: &Mutex<Instrument>, lid: u32, flags: u32, lock_timeout: u32) -> bool {
+    let deadline = Instant::now() + Duration::from_millis(lock_timeout as u64);
+    loop {
+        let lock = instrument.lock().unwrap().lock;
+        if lock.is_none() || lock == Some(lid) {
+            return true;
+        }
+        if flags & FLAG_WAITLOCK == 0 || Instant::now() >= deadline {
+            return false;
+        }
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+fn core(
+    instrument: &Mutex<Instrument>,
+    abort_port: u16,
+    procedure: u32,
+    args: &mut Args,
+    reply: &mut Vec<u8>,
+) {
+    match procedure {
+        // create_link
+        10 => {
+            // The client ID, lock_device and lock_timeout come before the device name.
+            args.u32();
+            args.u32();
+            args.u32();
+            let device = String::from_utf8(args.opaque()).unwrap();
+            let mut instrument = instrument.lock().unwrap();
+            instrument.next_link += 1;
+            let lid = instrument.next_link;
+            instrument.links.insert(
+                lid,
+                Link {
+                    device,
+                    ..Link::default()
+                },
+            );
+            for value in [0, lid, abort_port as u32, MAX_RECV_SIZE] {
+                put(reply, value);
+            }
+        }
+        // device_write
+        11 => {
+            let (lid, _io_timeout, lock_timeout, flags) =
+                (args.u32(), args.u32(), args.u32(), args.u32());
+            let data = args.opaque();
+            if !wait_for_lock(instrument, lid, flags, lock_timeout) {
+                put(reply, ERROR_DEVICE_LOCKED);
+                put(reply, 0);
+                return;
+            }
+            let mut instrument = instrument.lock().unwrap();
+            if instrument.input_full {
+                put(reply, 0);
+                put(reply, 0);
+                return;
+            }
+            instrument
+                .links
+                .get_mut(&lid)
+                .unwrap()
+                .input
+                .extend_from_slice(&data);
+            if flags & FLAG_END != 0 {
+                instrument.execute(lid);
+            }
+            put(reply, 0);
+            put(reply, data.len() as u32);
+        }
+        // device_read
+        12 => {
+            let (lid, request_size, io_timeout, lock_timeout, flags, term_char) = (
+                args.u32(),
+                args.u32() as usize,
+                args.u32(),
+                args.u32(),
+                args.u32(),
+                args.u32() as u8,
+            );
+            if !wait_for_lock(instrument, lid, flags, lock_timeout) {
+                put(reply, ERROR_DEVICE_LOCKED);
+                put(reply, 0);
+                put_opaque(reply, &[]);
+                return;
+            }
+            let deadline = Instant::now() + Duration::from_millis(io_timeout as u64);
+            loop {
+                let mut instrument = instrument.lock().unwrap();
+                let output = &mut instrument.links.get_mut(&lid).unwrap().output;
+                if !output.is_empty() {
+                    let mut len = output.len().min(request_size);
+                    let mut reason = REASON_REQCNT;
+                    if flags & FLAG_TERMCHRSET != 0 {
+                        if let Some(pos) = output[..len].iter().position(|b| *b == term_char) {
+                            len = pos + 1;
+                            reason = REASON_CHR;
+                        }
+                    }
+                    let data: Vec<u8> = output.drain(..len).collect();
+                    if output.is_empty() {
+                        reason |= REASON_END;
+                    }
+                    put(reply, 0);
+                    put(reply, reason);
+                    put_opaque(reply, &data);
+                    return;
+                }
+                drop(instrument);
+                if Instant::now() >= deadline {
+                    put(reply, ERROR_IO_TIMEOUT);
+                    put(reply, 0);
+                    put_opaque(reply, &[]);
+                    return;
+                }
+                thread::sleep(Duration::from_millis(5));
+            }
+        }
+        // device_readstb, device_trigger and device_clear
+        13..=15 => {
+            let (lid, flags, lock_timeout) = (args.u32(), args.u32(), args.u32());
+            if !wait_for_lock(instrument, lid, flags, lock_timeout) {
+                put(reply, ERROR_DEVICE_LOCKED);
+                if procedure == 13 {
+                    put(reply, 0);
+                }
+                return;
+            }
+            let mut instrument = instrument.lock().unwrap();
+            put(reply, 0);
+            match procedure {
+                13 => {
+                    let link = &instrument.links[&lid];
+                    put(reply, if link.output.is_empty() { 0 } else { MAV });
+                }
+                14 => instrument.triggers += 1,
+                _ => {
+                    let link = instrument.links.get_mut(&lid).unwrap();
+                    link.input.clear();
+                    link.output.clear();
+                }
+            }
+        }
+        // device_lock
+        18 => {
+            let (lid, flags, lock_timeout) = (args.u32(), args.u32(), args.u32());
+            if !wait_for_lock(instrument, lid, flags, lock_timeout) {
+                put(reply, ERROR_DEVICE_LOCKED);
+                return;
+            }
+            instrument.lock().unwrap().lock = Some(lid);
+            put(reply, 0);
+        }
+        // device_unlock
+        19 => {
+            let lid = args.u32();
+            let mut instrument = instrument.lock().unwrap();
+            if instrument.lock == Some(lid) {
+                instrument.lock = None;
+                put(reply, 0);
+            } else {
+                put(reply, ERROR_NO_LOCK_HELD);
+            }
+        }
+        // destroy_link
+        23 => {
+            let lid = args.u32();
+            let mut instrument = instrument.lock().unwrap();
+            if instrument.lock == Some(lid) {
+                instrument.lock = None;
+            }
+            let removed = instrument.links.remove(&lid).is_some();
+            put(reply, if removed { 0 } else { ERROR_INVALID_LINK });
+        }
+        _ => put(reply, 8),
+    }
+}
+
+/// The arguments of a call.
+struct Args<'a>(&'a [u8]);
+
+impl Args<'_> {
+    fn u32(&mut self) -> u32 {
+        let (value, rest) = self.0.split_at(4);
+        self.0 = rest;
+        u32::from_be_bytes(value.try_into().unwrap())
+    }
+
+    fn opaque(&mut self) -> Vec<u8> {
+        let len = self.u32() as usize;
+        let padded = len.div_ceil(4) * 4;
+        let (value, rest) = self.0.split_at(padded);
+        self.0 = rest;
+        value[..len].to_vec()
+    }
+}
+
+fn put(reply: &mut Vec<u8>, value: u32) {
+    reply.extend_from_slice(&value.to_be_bytes());
+}
+
+fn put_opaque(reply: &mut Vec<u8>, data: &[u8]) {
+    put(reply, data.len() as u32);
+    reply.extend_from_slice(data);
+    reply.resize(reply.len() + (4 - data.len() % 4) % 4, 0);
+}
+
+/// Serves an RPC program on an ephemeral port and returns the port. `handler` gets the
+/// procedure number and its arguments and writes the results.
+fn listen<F>(handler: F) -> u16
+where
+    F: Fn(u32, &mut Args, &mut Vec<u8>) + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let handler = Arc::new(handler);
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let handler = handler.clone();
+            thread::spawn(move || serve(stream, &*handler));
+        }
+    });
+    port
+}
+
+fn serve(mut stream: TcpStream, handler: &dyn Fn(u32, &mut Args, &mut Vec<u8>)) -> io::Result<()> {
+    loop {
+        let mut header = [0u8; 4];
+        stream.read_exact(&mut header)?;
+        let len = (u32::from_be_bytes(header) & 0x7FFF_FFFF) as usize;
+        let mut call = vec![0u8; len];
+        stream.read_exact(&mut call)?;
+        let mut args = Args(&call);
+        let xid = args.u32();
+        // msg_type, rpcvers, prog, vers and proc
+        let procedure = (0..5).map(|_| args.u32()).last().unwrap();
+        // The credential and the verifier
+        args.u32();
+        args.opaque();
+        args.u32();
+        args.opaque();
+        let mut reply = Vec::new();
+        for value in [xid, 1, 0, 0, 0, 0] {
+            put(&mut reply, value);
+        }
+        handler(procedure, &mut args, &mut reply);
+        let mut record = (0x8000_0000 | reply.len() as u32).to_be_bytes().to_vec();
+        record.extend_from_slice(&reply);
+        stream.write_all(&record)?;
+    }
+}
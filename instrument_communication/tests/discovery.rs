@@ -0,0 +1,31 @@
+use instrument_communication::address::InstAddr;
+use instrument_communication::discover;
+
+#[test]
+fn discover_gpib_instruments_with_alias() {
+    let found: Vec<_> = discover(visa_mock::binary(), "GPIB?*INSTR")
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(found.len(), 2);
+    assert_eq!(found[0].address, InstAddr::new("GPIB0::7::INSTR").unwrap());
+    assert_eq!(found[0].interface_type, visa::VI_INTF_GPIB as u16);
+    assert_eq!(found[0].board, 0);
+    assert_eq!(found[0].alias.as_deref(), Some("DMM1"));
+    assert_eq!(found[1].resource_name, "GPIB0::9::INSTR");
+    assert_eq!(found[1].alias, None);
+}
+
+#[test]
+fn discover_everything_lists_each_simulated_resource() {
+    let names: Vec<_> = discover(visa_mock::binary(), "?*")
+        .unwrap()
+        .map(|resource| resource.unwrap().resource_name)
+        .collect();
+    assert_eq!(names, visa_mock::SIMULATED_RESOURCES);
+}
+
+#[test]
+fn discover_without_match_is_empty() {
+    assert_eq!(discover(visa_mock::binary(), "PXI?*").unwrap().count(), 0);
+}
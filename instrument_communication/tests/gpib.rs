@@ -0,0 +1,59 @@
+mod common;
+
+use common::open;
+use instrument_communication::address::InstAddr;
+use instrument_communication::communication::InstConnection;
+use instrument_communication::connection::gpib_bus::{AtnMode, GpibBus};
+use instrument_communication::err::{Error, Operation};
+use visa::{attr, VisaStatus};
+
+#[test]
+fn secondary_address_is_opened() {
+    let mut conn = open("GPIB0::7::3::INSTR").unwrap();
+    assert_eq!(
+        conn.query("*IDN?").unwrap(),
+        "Cosmere,mock1000,GPIB0::7::3::INSTR,V0.01.00"
+    );
+}
+
+#[test]
+fn instrument_local_lockout_and_go_to_local() {
+    let conn = open("GPIB0::7::INSTR").unwrap();
+    conn.local_lockout().unwrap();
+    conn.go_to_local().unwrap();
+
+    let serial = open("ASRL1::INSTR").unwrap();
+    let err = serial.local_lockout().unwrap_err();
+    assert!(matches!(err, Error::Visa(VisaStatus::ERROR_NSUP_OPER, _)));
+    assert_eq!(err.context().unwrap().operation, Operation::GpibControl);
+}
+
+#[test]
+fn bus_control() {
+    let bus = GpibBus::open(0, Some(visa_mock::binary())).unwrap();
+    bus.interface_clear().unwrap();
+    assert!(bus.session().get::<attr::GpibCicState>().unwrap());
+
+    bus.local_lockout().unwrap();
+    assert_eq!(
+        bus.session().get::<attr::GpibRenState>().unwrap(),
+        visa::VI_STATE_ASSERTED as i16
+    );
+    bus.go_to_local().unwrap();
+    assert_eq!(
+        bus.session().get::<attr::GpibRenState>().unwrap(),
+        visa::VI_STATE_UNASSERTED as i16
+    );
+
+    bus.set_attention(AtnMode::Assert).unwrap();
+    // UNL and UNT
+    assert_eq!(bus.command(&[0x3F, 0x5F]).unwrap(), 2);
+    bus.set_attention(AtnMode::Deassert).unwrap();
+
+    let mainframe = "GPIB0::9::2::INSTR".parse::<InstAddr>().unwrap();
+    let InstAddr::Visa(mainframe) = mainframe else {
+        panic!("GPIB addresses are visa addresses");
+    };
+    bus.pass_control(&mainframe.as_gpib().unwrap()).unwrap();
+    assert!(!bus.session().get::<attr::GpibCicState>().unwrap());
+}
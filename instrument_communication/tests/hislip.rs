@@ -0,0 +1,101 @@
+mod common;
+
+use common::hislip_server::{HislipServer, DATA_LENGTH, IDENTITY, MAX_MESSAGE_SIZE};
+use instrument_communication::address::InstAddr;
+use instrument_communication::communication::InstConnection;
+use instrument_communication::connection::hislip_conn::{HislipConn, HislipMode};
+use instrument_communication::err::Operation;
+use std::time::Duration;
+
+fn open(server: &HislipServer) -> HislipConn {
+    let resource = server.resource();
+    let InstAddr::Visa(address) = InstAddr::new(&resource).unwrap() else {
+        panic!("{resource} should be a visa address");
+    };
+    HislipConn::connect(address).unwrap()
+}
+
+#[test]
+fn query_identity_and_negotiated_settings() {
+    let server = HislipServer::start();
+    let mut conn = open(&server);
+    assert_eq!(conn.query("*IDN?").unwrap(), IDENTITY);
+    assert_eq!(conn.protocol_version(), (1, 1));
+    assert_eq!(conn.max_message_size(), MAX_MESSAGE_SIZE);
+    assert_eq!(conn.mode(), HislipMode::Synchronized);
+    assert_eq!(server.sessions(), 1);
+}
+
+#[test]
+fn long_messages_span_several_messages() {
+    let server = HislipServer::start();
+    let mut conn = open(&server);
+    assert_eq!(conn.query("DATA?").unwrap().len(), DATA_LENGTH);
+    let text = "x".repeat(3 * MAX_MESSAGE_SIZE as usize);
+    assert_eq!(conn.query(&format!("ECHO {text}")).unwrap(), text);
+}
+
+#[test]
+fn synchronized_mode_discards_unread_responses() {
+    let server = HislipServer::start();
+    let mut conn = open(&server);
+    conn.write("ECHO first").unwrap();
+    conn.write("ECHO second").unwrap();
+    assert_eq!(conn.read().unwrap(), "second");
+}
+
+#[test]
+fn overlapped_mode_keeps_responses_in_order() {
+    let server = HislipServer::start();
+    let mut conn = open(&server);
+    assert_eq!(
+        conn.set_mode(HislipMode::Overlapped).unwrap(),
+        HislipMode::Overlapped
+    );
+    conn.write("ECHO first").unwrap();
+    conn.write("ECHO second").unwrap();
+    assert_eq!(conn.read().unwrap(), "first");
+    assert_eq!(conn.read().unwrap(), "second");
+}
+
+#[test]
+fn status_query_trigger_and_clear() {
+    let server = HislipServer::start();
+    let mut conn = open(&server);
+    conn.write("*IDN?").unwrap();
+    assert_eq!(conn.read_stb().unwrap() & 0x10, 0x10);
+    conn.read().unwrap();
+    assert_eq!(conn.read_stb().unwrap(), 0);
+    conn.trigger().unwrap();
+    conn.trigger().unwrap();
+    assert_eq!(conn.query("TRG:COUNT?").unwrap(), "2");
+    conn.write("*IDN?").unwrap();
+    conn.clear().unwrap();
+    assert_eq!(conn.read_stb().unwrap(), 0);
+    assert_eq!(conn.query("*IDN?").unwrap(), IDENTITY);
+}
+
+#[test]
+fn lock_blocks_other_sessions() {
+    let server = HislipServer::start();
+    let mut owner = open(&server);
+    let mut other = open(&server);
+    owner.lock().unwrap();
+    other.set_lock_timeout(Duration::from_millis(50)).unwrap();
+    let err = other.lock().unwrap_err();
+    assert_eq!(err.context().unwrap().operation, Operation::Lock);
+    owner.unlock().unwrap();
+    other.lock().unwrap();
+    assert!(owner.unlock().is_err());
+}
+
+#[test]
+fn read_without_response_times_out() {
+    let server = HislipServer::start();
+    let mut conn = open(&server);
+    conn.set_timeout(Duration::from_millis(50)).unwrap();
+    conn.write("*RST").unwrap();
+    let err = conn.read_bytes().unwrap_err();
+    assert!(err.is_timeout());
+    assert_eq!(err.context().unwrap().command.as_deref(), Some("*RST"));
+}
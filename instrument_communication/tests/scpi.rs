@@ -0,0 +1,23 @@
+mod common;
+
+use common::open;
+use instrument_communication::err::Error;
+use instrument_communication::scpi::ScpiQuery;
+
+#[test]
+fn identity_is_a_list_of_strings() {
+    let mut conn = open("GPIB0::7::INSTR").unwrap();
+    let identity: Vec<String> = conn.query_list("*IDN?").unwrap();
+    assert_eq!(
+        identity,
+        ["Cosmere", "mock1000", "GPIB0::7::INSTR", "V0.01.00"]
+    );
+}
+
+#[test]
+fn parse_errors_name_the_query() {
+    let mut conn = open("GPIB0::7::INSTR").unwrap();
+    let err = conn.query_f64("*IDN?").unwrap_err();
+    assert!(matches!(err, Error::ParseFailed(_)));
+    assert!(err.to_string().ends_with("in the response to *IDN?"));
+}
@@ -0,0 +1,131 @@
+//! Tests of the native serial connection against a stand-in instrument on a pseudo-terminal.
+#![cfg(target_os = "linux")]
+use instrument_communication::address::InstAddr;
+use instrument_communication::communication::InstConnection;
+use instrument_communication::connection::serial_conn::SerialConn;
+use instrument_communication::err::{Error, Operation};
+use std::ffi::CStr;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::unix::io::FromRawFd;
+use std::thread;
+use std::time::Duration;
+
+/// Connects to the slave side of a new pseudo-terminal and returns the connection, the master
+/// side and the slave side.
+fn pseudo_terminal() -> (SerialConn, File, File) {
+    let (mut master, mut slave) = (0, 0);
+    let result = unsafe {
+        libc::openpty(
+            &mut master,
+            &mut slave,
+            std::ptr::null_mut(),
+            std::ptr::null(),
+            std::ptr::null(),
+        )
+    };
+    assert_eq!(result, 0);
+    let path = unsafe { CStr::from_ptr(libc::ttyname(slave)) }
+        .to_str()
+        .unwrap()
+        .to_owned();
+    let master = unsafe { File::from_raw_fd(master) };
+    // The slave stays open so the terminal outlives reconnects of the connection.
+    let slave = unsafe { File::from_raw_fd(slave) };
+    let InstAddr::Visa(address) = InstAddr::new(format!("ASRL{path}::INSTR::115200,8N1")).unwrap()
+    else {
+        panic!("serial addresses are visa addresses");
+    };
+    (SerialConn::connect(address).unwrap(), master, slave)
+}
+
+/// A stand-in instrument on the master side of a pseudo-terminal. It answers `*IDN?` and
+/// `ECHO x`, echoes the received characters back if `echo` is set and writes `prompt` after
+/// every response.
+fn start(echo: bool, prompt: &'static str) -> (SerialConn, File) {
+    let (conn, mut master, slave) = pseudo_terminal();
+    thread::spawn(move || {
+        let mut line = Vec::new();
+        let mut byte = [0u8; 1];
+        while let Ok(1) = master.read(&mut byte) {
+            if echo {
+                master.write_all(&byte).unwrap();
+            }
+            if byte[0] != b'\n' {
+                line.push(byte[0]);
+                continue;
+            }
+            let command = String::from_utf8_lossy(&line).trim().to_owned();
+            line.clear();
+            let response = if command == "*IDN?" {
+                "Cosmere,serial-mock,0,1.0".to_owned()
+            } else if let Some(text) = command.strip_prefix("ECHO ") {
+                text.to_owned()
+            } else {
+                continue;
+            };
+            master
+                .write_all(format!("{response}\n{prompt}").as_bytes())
+                .unwrap();
+        }
+    });
+    (conn, slave)
+}
+
+#[test]
+fn query_through_pseudo_terminal() {
+    let (mut conn, _slave) = start(false, "");
+    assert_eq!(conn.query("*IDN?").unwrap(), "Cosmere,serial-mock,0,1.0");
+    assert_eq!(conn.settings().baud_rate, 115200);
+}
+
+#[test]
+fn echoed_commands_are_discarded() {
+    let (mut conn, _slave) = start(true, "");
+    conn.set_echo(true);
+    assert_eq!(conn.query("*IDN?").unwrap(), "Cosmere,serial-mock,0,1.0");
+    conn.write("ECHO first").unwrap();
+    conn.write("ECHO second").unwrap();
+    assert_eq!(conn.read().unwrap(), "first");
+    assert_eq!(conn.read().unwrap(), "second");
+}
+
+#[test]
+fn prompts_are_stripped() {
+    let (mut conn, _slave) = start(true, "> ");
+    conn.set_echo(true);
+    conn.set_prompt(Some(b"> ".to_vec()));
+    assert_eq!(conn.query("ECHO one").unwrap(), "one");
+    assert_eq!(conn.query("ECHO two").unwrap(), "two");
+}
+
+#[test]
+fn read_without_response_times_out() {
+    let (mut conn, _slave) = start(false, "");
+    conn.set_timeout(Duration::from_millis(100)).unwrap();
+    conn.write("*RST").unwrap();
+    let err = conn.read_bytes().unwrap_err();
+    assert!(matches!(err, Error::Timeout(_)));
+    assert_eq!(err.context().unwrap().command.as_deref(), Some("*RST"));
+}
+
+#[test]
+fn write_that_is_never_read_times_out() {
+    let (mut conn, _master, _slave) = pseudo_terminal();
+    conn.set_timeout(Duration::from_millis(100)).unwrap();
+    let err = conn.write_bytes(&[b'x'; 1 << 20]).unwrap_err();
+    assert!(matches!(err, Error::Timeout(_)));
+    assert_eq!(err.context().unwrap().operation, Operation::Write);
+}
+
+#[test]
+fn unsupported_settings_are_rejected() {
+    let InstAddr::Visa(address) = InstAddr::new("ASRL/dev/null::INSTR::9600,8N1,DTRDSR").unwrap()
+    else {
+        panic!("serial addresses are visa addresses");
+    };
+    assert!(matches!(
+        SerialConn::connect(address),
+        Err(Error::ConflictingSettings(_))
+    ));
+}
@@ -0,0 +1,12 @@
+//! The default binary is process wide so it is only changed in this test binary.
+use instrument_communication::connection::visa_conn::VisaConn;
+
+#[test]
+fn connect_helper_opens_usb_instrument() {
+    VisaConn::set_default_binary(visa_mock::binary());
+    let mut conn = instrument_communication::connect("USB::10893::257::MY12345678::0").unwrap();
+    assert_eq!(
+        conn.query("*IDN?").unwrap(),
+        "Cosmere,mock1000,USB0::0x2A8D::0x0101::MY12345678::0::INSTR,V0.01.00"
+    );
+}
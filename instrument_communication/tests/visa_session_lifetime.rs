@@ -0,0 +1,53 @@
+//! The mock VISA library counts open sessions for the whole process so every check lives in a
+//! single test.
+mod common;
+
+use common::open;
+use instrument_communication::communication::InstConnection;
+use std::time::Duration;
+
+#[test]
+fn sessions_and_resource_manager_are_closed_on_drop() {
+    let first = open("GPIB0::7::INSTR").unwrap();
+    assert_eq!(
+        visa_mock::open_session_count(),
+        2,
+        "A resource manager and one instrument session are open."
+    );
+    let mut second = open("GPIB0::9::INSTR").unwrap();
+    assert_eq!(
+        visa_mock::open_session_count(),
+        3,
+        "Connections through the same binary share a resource manager."
+    );
+
+    second.set_timeout(Duration::from_millis(500)).unwrap();
+    second.reconnect().unwrap();
+    assert_eq!(
+        visa_mock::open_session_count(),
+        3,
+        "Reconnecting closes the old session."
+    );
+    assert!(second
+        .query("*IDN?")
+        .unwrap()
+        .starts_with("Cosmere,mock1000"));
+
+    drop(first);
+    assert_eq!(visa_mock::open_session_count(), 2);
+    drop(second);
+    assert_eq!(
+        visa_mock::open_session_count(),
+        0,
+        "The resource manager closes with its last session."
+    );
+
+    let third = open("GPIB0::7::INSTR").unwrap();
+    assert_eq!(
+        visa_mock::open_session_count(),
+        2,
+        "A new resource manager opens once the previous one was closed."
+    );
+    drop(third);
+    assert_eq!(visa_mock::open_session_count(), 0);
+}
@@ -0,0 +1,32 @@
+mod common;
+
+use common::open;
+use instrument_communication::communication::InstConnection;
+use instrument_communication::err::{Error, Operation};
+use std::time::Duration;
+use visa::VisaStatus;
+
+#[test]
+fn missing_resource_reports_status() {
+    let result = open("GPIB0::29::INSTR");
+    assert!(matches!(
+        result,
+        Err(Error::Visa(VisaStatus::ERROR_RSRC_NFOUND, _))
+    ));
+}
+
+#[test]
+fn read_without_response_times_out_with_the_visa_status() {
+    let mut conn = open("GPIB0::7::INSTR").unwrap();
+    conn.set_timeout(Duration::from_millis(50)).unwrap();
+    let err = conn.read_bytes().unwrap_err();
+    assert!(matches!(err, Error::Timeout(_)));
+    assert!(err.is_timeout());
+    assert_eq!(err.visa_status(), Some(VisaStatus::ERROR_TMO));
+    let source = std::error::Error::source(&err).unwrap();
+    assert_eq!(
+        source.downcast_ref::<VisaStatus>(),
+        Some(&VisaStatus::ERROR_TMO)
+    );
+    assert_eq!(err.context().unwrap().operation, Operation::Read);
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use common::vxi11_server::{Vxi11Server, DATA_LENGTH, IDENTITY, MAX_RECV_SIZE};
+use instrument_communication::address::InstAddr;
+use instrument_communication::communication::InstConnection;
+use instrument_communication::connection::vxi11_conn::Vxi11Conn;
+use instrument_communication::err::{Error, Operation};
+use std::time::Duration;
+
+fn open(server: &Vxi11Server) -> Vxi11Conn {
+    let InstAddr::Visa(address) = InstAddr::new(server.resource()).unwrap() else {
+        panic!("{} should be a visa address", server.resource());
+    };
+    Vxi11Conn::connect_with_portmapper(address, server.portmapper_port).unwrap()
+}
+
+#[test]
+fn query_identity() {
+    let server = Vxi11Server::start();
+    let mut conn = open(&server);
+    assert_eq!(conn.query("*IDN?").unwrap(), IDENTITY);
+}
+
+#[test]
+fn long_messages_span_several_calls() {
+    let server = Vxi11Server::start();
+    let mut conn = open(&server);
+    let data = conn.query("DATA?").unwrap();
+    assert_eq!(data.len(), DATA_LENGTH);
+    let text = "x".repeat(3 * MAX_RECV_SIZE as usize);
+    assert_eq!(conn.query(&format!("ECHO {text}")).unwrap(), text);
+}
+
+#[test]
+fn binary_block_is_read_past_the_term_char() {
+    let server = Vxi11Server::start();
+    let mut conn = open(&server);
+    assert_eq!(conn.query_binary_block("CURV?").unwrap(), b"a\nbc");
+    assert_eq!(conn.query("*IDN?").unwrap(), IDENTITY);
+}
+
+#[test]
+fn status_byte_trigger_and_clear() {
+    let server = Vxi11Server::start();
+    let mut conn = open(&server);
+    conn.write("*IDN?").unwrap();
+    assert_eq!(conn.read_stb().unwrap() & 0x10, 0x10);
+    conn.clear().unwrap();
+    assert_eq!(conn.read_stb().unwrap(), 0);
+    conn.trigger().unwrap();
+    conn.trigger().unwrap();
+    assert_eq!(conn.query("TRG:COUNT?").unwrap(), "2");
+}
+
+#[test]
+fn lock_blocks_other_links() {
+    let server = Vxi11Server::start();
+    let mut owner = open(&server);
+    let mut other = open(&server);
+    owner.lock().unwrap();
+    let err = other.write("*IDN?").unwrap_err();
+    assert!(matches!(err, Error::FunctionFailure(_)));
+    assert_eq!(err.context().unwrap().operation, Operation::Write);
+    owner.unlock().unwrap();
+    assert_eq!(other.query("*IDN?").unwrap(), IDENTITY);
+}
+
+#[test]
+fn read_without_response_times_out() {
+    let server = Vxi11Server::start();
+    let mut conn = open(&server);
+    conn.set_timeout(Duration::from_millis(50)).unwrap();
+    conn.write("*RST").unwrap();
+    let err = conn.read_bytes().unwrap_err();
+    assert!(err.is_timeout());
+    assert_eq!(err.context().unwrap().command.as_deref(), Some("*RST"));
+}
+
+#[test]
+fn write_that_is_not_accepted_fails() {
+    let server = Vxi11Server::start();
+    let mut conn = open(&server);
+    server.fill_input();
+    let err = conn.write("*RST").unwrap_err();
+    assert!(matches!(err, Error::FunctionFailure(_)));
+    assert_eq!(err.context().unwrap().operation, Operation::Write);
+}
+
+#[test]
+fn abort_channel_and_link_lifetime() {
+    let server = Vxi11Server::start();
+    let conn = open(&server);
+    conn.abort_handle().unwrap().abort().unwrap();
+    assert_eq!(server.aborts(), 1);
+    assert_eq!(server.links(), 1);
+    drop(conn);
+    assert_eq!(server.links(), 0);
+}
+
+#[test]
+fn device_name_is_sent_when_creating_the_link() {
+    let server = Vxi11Server::start();
+    let InstAddr::Visa(address) = InstAddr::new("TCPIP::127.0.0.1::gpib0,7,2::INSTR").unwrap()
+    else {
+        panic!("VXI-11 addresses are visa addresses");
+    };
+    let mut conn = Vxi11Conn::connect_with_portmapper(address, server.portmapper_port).unwrap();
+    assert_eq!(server.devices(), ["gpib0,7,2"]);
+    conn.reconnect().unwrap();
+    assert_eq!(server.devices(), ["gpib0,7,2"]);
+    drop(conn);
+
+    let _conn = open(&server);
+    assert_eq!(server.devices(), ["inst0"]);
+}
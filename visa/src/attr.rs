@@ -0,0 +1,313 @@
+//! Typed VISA attributes. Every attribute is a marker type that knows its id, the type of its
+//! value and whether it can be written, so values are read with the width the driver expects.
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use visa::{attr, Binary, ResourceManager};
+//!
+//! let rm = Arc::new(ResourceManager::load(&Binary::Primary)?);
+//! let session = rm.open("ASRL1::INSTR")?;
+//! session.set::<attr::AsrlBaud>(115200)?;
+//! let timeout: u32 = session.get::<attr::TmoValue>()?;
+//! let name: String = session.get::<attr::RsrcName>()?;
+//! # Ok::<(), visa::err::Error>(())
+//! ```
+use crate::safe::{buffer_to_string, check, Result, Session};
+use crate::*;
+use std::ffi::c_void;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// A VISA attribute with a known value type.
+pub trait Attribute {
+    /// The `VI_ATTR_*` id.
+    const ID: ViAttr;
+    /// The name of the `VI_ATTR_*` constant.
+    const NAME: &'static str;
+    const READ_ONLY: bool;
+    type Value: AttrValue;
+}
+
+/// Implemented by the attributes that can be set.
+pub trait Writable: Attribute {}
+
+/// The kinds of values attributes hold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValueKind {
+    U8,
+    U16,
+    U32,
+    U64,
+    I16,
+    Bool,
+    String,
+}
+
+/// A value of any attribute. It is returned by [`Session::attributes`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    I16(i16),
+    Bool(bool),
+    String(String),
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Value::U8(v) => v.fmt(f),
+            Value::U16(v) => v.fmt(f),
+            Value::U32(v) => v.fmt(f),
+            Value::U64(v) => v.fmt(f),
+            Value::I16(v) => v.fmt(f),
+            Value::Bool(v) => v.fmt(f),
+            Value::String(v) => v.fmt(f),
+        }
+    }
+}
+
+/// A type an attribute value can be read into and written from.
+pub trait AttrValue: Sized + Into<Value> {
+    const KIND: ValueKind;
+    /// Reads the attribute. The buffer passed to the driver has the size VISA defines for the
+    /// value type.
+    fn read(session: &Session, id: ViAttr) -> Result<Self>;
+    /// The raw value passed to `viSetAttribute`.
+    fn to_state(&self) -> ViAttrState;
+}
+
+fn get_raw(session: &Session, id: ViAttr, value: *mut c_void) -> Result<()> {
+    check(session.visa().viGetAttribute(session.raw(), id, value)).map(|_| ())
+}
+
+macro_rules! numeric_values {
+    ($($ty:ty => $kind:ident;)*) => {
+        $(
+            impl AttrValue for $ty {
+                const KIND: ValueKind = ValueKind::$kind;
+
+                fn read(session: &Session, id: ViAttr) -> Result<Self> {
+                    let mut value: $ty = 0;
+                    get_raw(session, id, &mut value as *mut $ty as *mut c_void)?;
+                    Ok(value)
+                }
+
+                fn to_state(&self) -> ViAttrState {
+                    *self as ViAttrState
+                }
+            }
+
+            impl From<$ty> for Value {
+                fn from(value: $ty) -> Self {
+                    Value::$kind(value)
+                }
+            }
+        )*
+    };
+}
+
+numeric_values! {
+    u8 => U8;
+    u16 => U16;
+    u32 => U32;
+    u64 => U64;
+    i16 => I16;
+}
+
+impl AttrValue for bool {
+    const KIND: ValueKind = ValueKind::Bool;
+
+    fn read(session: &Session, id: ViAttr) -> Result<Self> {
+        let mut value: ViBoolean = 0;
+        get_raw(session, id, &mut value as *mut ViBoolean as *mut c_void)?;
+        Ok(value != 0)
+    }
+
+    fn to_state(&self) -> ViAttrState {
+        *self as ViAttrState
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Bool(value)
+    }
+}
+
+impl AttrValue for String {
+    const KIND: ValueKind = ValueKind::String;
+
+    /// VISA guarantees that string attributes fit in `VI_FIND_BUFLEN` bytes including the NUL.
+    fn read(session: &Session, id: ViAttr) -> Result<Self> {
+        let mut buffer = [0u8; VI_FIND_BUFLEN as usize];
+        get_raw(session, id, buffer.as_mut_ptr() as *mut c_void)?;
+        Ok(buffer_to_string(&buffer))
+    }
+
+    /// String attributes are read-only so this is never passed to the driver.
+    fn to_state(&self) -> ViAttrState {
+        0
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::String(value)
+    }
+}
+
+macro_rules! attributes {
+    ($($(#[$doc:meta])* $name:ident = $id:ident: $ty:ty, $access:ident;)*) => {
+        $(
+            $(#[$doc])*
+            pub struct $name;
+
+            impl Attribute for $name {
+                const ID: ViAttr = $id;
+                const NAME: &'static str = stringify!($id);
+                const READ_ONLY: bool = attributes!(@read_only $access);
+                type Value = $ty;
+            }
+
+            attributes!(@writable $name $access);
+        )*
+
+        /// The value kind and access of a known attribute id.
+        pub fn lookup(id: ViAttr) -> Option<(&'static str, ValueKind, bool)> {
+            match id {
+                $($id => Some((
+                    stringify!($id),
+                    <$ty as AttrValue>::KIND,
+                    attributes!(@read_only $access),
+                )),)*
+                _ => None,
+            }
+        }
+
+        pub(crate) fn dump(session: &Session) -> Vec<(&'static str, Result<Value>)> {
+            vec![$((stringify!($id), session.get::<$name>().map(Value::from)),)*]
+        }
+    };
+    (@read_only ro) => { true };
+    (@read_only rw) => { false };
+    (@writable $name:ident ro) => {};
+    (@writable $name:ident rw) => { impl Writable for $name {} };
+}
+
+attributes! {
+    RsrcClass = VI_ATTR_RSRC_CLASS: String, ro;
+    RsrcName = VI_ATTR_RSRC_NAME: String, ro;
+    RsrcImplVersion = VI_ATTR_RSRC_IMPL_VERSION: u32, ro;
+    RsrcLockState = VI_ATTR_RSRC_LOCK_STATE: u32, ro;
+    RsrcSpecVersion = VI_ATTR_RSRC_SPEC_VERSION: u32, ro;
+    RsrcManfName = VI_ATTR_RSRC_MANF_NAME: String, ro;
+    RsrcManfId = VI_ATTR_RSRC_MANF_ID: u16, ro;
+    MaxQueueLength = VI_ATTR_MAX_QUEUE_LENGTH: u32, rw;
+    UserData = VI_ATTR_USER_DATA_64: u64, rw;
+    /// Timeout of I/O operations in milliseconds.
+    TmoValue = VI_ATTR_TMO_VALUE: u32, rw;
+    SendEndEn = VI_ATTR_SEND_END_EN: bool, rw;
+    SuppressEndEn = VI_ATTR_SUPPRESS_END_EN: bool, rw;
+    Termchar = VI_ATTR_TERMCHAR: u8, rw;
+    TermcharEn = VI_ATTR_TERMCHAR_EN: bool, rw;
+    IoProt = VI_ATTR_IO_PROT: u16, rw;
+    DmaAllowEn = VI_ATTR_DMA_ALLOW_EN: bool, rw;
+    FileAppendEn = VI_ATTR_FILE_APPEND_EN: bool, rw;
+    RdBufOperMode = VI_ATTR_RD_BUF_OPER_MODE: u16, rw;
+    RdBufSize = VI_ATTR_RD_BUF_SIZE: u32, ro;
+    WrBufOperMode = VI_ATTR_WR_BUF_OPER_MODE: u16, rw;
+    WrBufSize = VI_ATTR_WR_BUF_SIZE: u32, ro;
+    TrigId = VI_ATTR_TRIG_ID: i16, rw;
+    DevStatusByte = VI_ATTR_DEV_STATUS_BYTE: u8, rw;
+    Is4882Compliant = VI_ATTR_4882_COMPLIANT: bool, ro;
+    ManfName = VI_ATTR_MANF_NAME: String, ro;
+    ManfId = VI_ATTR_MANF_ID: u16, ro;
+    ModelName = VI_ATTR_MODEL_NAME: String, ro;
+    ModelCode = VI_ATTR_MODEL_CODE: u16, ro;
+    IntfType = VI_ATTR_INTF_TYPE: u16, ro;
+    IntfNum = VI_ATTR_INTF_NUM: u16, ro;
+    IntfInstName = VI_ATTR_INTF_INST_NAME: String, ro;
+    IntfParentNum = VI_ATTR_INTF_PARENT_NUM: u16, ro;
+    ImmediateServ = VI_ATTR_IMMEDIATE_SERV: bool, ro;
+    GpibPrimaryAddr = VI_ATTR_GPIB_PRIMARY_ADDR: u16, ro;
+    GpibSecondaryAddr = VI_ATTR_GPIB_SECONDARY_ADDR: u16, ro;
+    GpibReaddrEn = VI_ATTR_GPIB_READDR_EN: bool, rw;
+    GpibUnaddrEn = VI_ATTR_GPIB_UNADDR_EN: bool, rw;
+    GpibRenState = VI_ATTR_GPIB_REN_STATE: i16, ro;
+    GpibAtnState = VI_ATTR_GPIB_ATN_STATE: i16, ro;
+    GpibAddrState = VI_ATTR_GPIB_ADDR_STATE: i16, ro;
+    GpibCicState = VI_ATTR_GPIB_CIC_STATE: bool, ro;
+    GpibNdacState = VI_ATTR_GPIB_NDAC_STATE: i16, ro;
+    GpibSrqState = VI_ATTR_GPIB_SRQ_STATE: i16, ro;
+    GpibSysCntrlState = VI_ATTR_GPIB_SYS_CNTRL_STATE: bool, rw;
+    GpibHs488CblLen = VI_ATTR_GPIB_HS488_CBL_LEN: i16, rw;
+    /// Baud rate of a serial port.
+    AsrlBaud = VI_ATTR_ASRL_BAUD: u32, rw;
+    AsrlDataBits = VI_ATTR_ASRL_DATA_BITS: u16, rw;
+    AsrlParity = VI_ATTR_ASRL_PARITY: u16, rw;
+    AsrlStopBits = VI_ATTR_ASRL_STOP_BITS: u16, rw;
+    AsrlFlowCntrl = VI_ATTR_ASRL_FLOW_CNTRL: u16, rw;
+    AsrlEndIn = VI_ATTR_ASRL_END_IN: u16, rw;
+    AsrlEndOut = VI_ATTR_ASRL_END_OUT: u16, rw;
+    AsrlAvailNum = VI_ATTR_ASRL_AVAIL_NUM: u32, ro;
+    AsrlReplaceChar = VI_ATTR_ASRL_REPLACE_CHAR: u8, rw;
+    AsrlXonChar = VI_ATTR_ASRL_XON_CHAR: u8, rw;
+    AsrlXoffChar = VI_ATTR_ASRL_XOFF_CHAR: u8, rw;
+    AsrlCtsState = VI_ATTR_ASRL_CTS_STATE: i16, ro;
+    AsrlDcdState = VI_ATTR_ASRL_DCD_STATE: i16, ro;
+    AsrlDsrState = VI_ATTR_ASRL_DSR_STATE: i16, ro;
+    AsrlRiState = VI_ATTR_ASRL_RI_STATE: i16, ro;
+    AsrlDtrState = VI_ATTR_ASRL_DTR_STATE: i16, rw;
+    AsrlRtsState = VI_ATTR_ASRL_RTS_STATE: i16, rw;
+    TcpipAddr = VI_ATTR_TCPIP_ADDR: String, ro;
+    TcpipHostname = VI_ATTR_TCPIP_HOSTNAME: String, ro;
+    TcpipPort = VI_ATTR_TCPIP_PORT: u16, ro;
+    TcpipDeviceName = VI_ATTR_TCPIP_DEVICE_NAME: String, ro;
+    TcpipNodelay = VI_ATTR_TCPIP_NODELAY: bool, rw;
+    TcpipKeepalive = VI_ATTR_TCPIP_KEEPALIVE: bool, rw;
+    TcpipIsHislip = VI_ATTR_TCPIP_IS_HISLIP: bool, ro;
+    TcpipHislipVersion = VI_ATTR_TCPIP_HISLIP_VERSION: u32, ro;
+    TcpipHislipOverlapEn = VI_ATTR_TCPIP_HISLIP_OVERLAP_EN: bool, rw;
+    TcpipHislipMaxMessageKb = VI_ATTR_TCPIP_HISLIP_MAX_MESSAGE_KB: u32, rw;
+    UsbSerialNum = VI_ATTR_USB_SERIAL_NUM: String, ro;
+    UsbIntfcNum = VI_ATTR_USB_INTFC_NUM: i16, ro;
+    UsbProtocol = VI_ATTR_USB_PROTOCOL: i16, ro;
+    UsbMaxIntrSize = VI_ATTR_USB_MAX_INTR_SIZE: u16, rw;
+    VxiLa = VI_ATTR_VXI_LA: i16, ro;
+    VxiDevClass = VI_ATTR_VXI_DEV_CLASS: u16, ro;
+    CmdrLa = VI_ATTR_CMDR_LA: i16, ro;
+    MainframeLa = VI_ATTR_MAINFRAME_LA: i16, ro;
+    Slot = VI_ATTR_SLOT: i16, ro;
+    PxiBusNum = VI_ATTR_PXI_BUS_NUM: u16, ro;
+    PxiDevNum = VI_ATTR_PXI_DEV_NUM: u16, ro;
+    PxiFuncNum = VI_ATTR_PXI_FUNC_NUM: u16, ro;
+    PxiChassis = VI_ATTR_PXI_CHASSIS: i16, ro;
+    PxiSlotpath = VI_ATTR_PXI_SLOTPATH: String, ro;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(VI_ATTR_TMO_VALUE, "VI_ATTR_TMO_VALUE", ValueKind::U32, false)]
+    #[test_case(VI_ATTR_RSRC_NAME, "VI_ATTR_RSRC_NAME", ValueKind::String, true)]
+    #[test_case(VI_ATTR_TERMCHAR_EN, "VI_ATTR_TERMCHAR_EN", ValueKind::Bool, false)]
+    #[test_case(VI_ATTR_GPIB_REN_STATE, "VI_ATTR_GPIB_REN_STATE", ValueKind::I16, true)]
+    fn lookup_known_attribute(id: ViAttr, name: &str, kind: ValueKind, read_only: bool) {
+        assert_eq!(lookup(id), Some((name, kind, read_only)));
+    }
+
+    #[test]
+    fn marker_types_match_lookup() {
+        assert_eq!(RsrcName::NAME, "VI_ATTR_RSRC_NAME");
+        assert_eq!((ModelName::READ_ONLY, AsrlBaud::READ_ONLY), (true, false));
+        assert_eq!(
+            lookup(VI_ATTR_OPER_NAME),
+            None,
+            "Event attributes are not listed."
+        );
+    }
+}
@@ -1,3 +1,4 @@
+#![allow(clippy::too_many_arguments)]
 use dlopen::wrapper::WrapperApi;
 use dlopen_derive::WrapperApi;
 
@@ -1,3 +1,4 @@
+use crate::status::VisaStatus;
 use std::convert::From;
 use std::error::Error as ErrorTrait;
 use std::fmt::{Display, Formatter, Result as FmtResult};
@@ -18,6 +19,8 @@ pub enum Error {
     NullCharacter,
     ///Unsupported Target
     UnsupportedPlatform,
+    ///A VISA function returned an error status.
+    Status(VisaStatus),
 }
 
 impl Display for Error {
@@ -38,8 +41,9 @@ impl Display for Error {
                 f.write_str(": Path does not lead to a library.")?;
                 msg.fmt(f)
             }
-            NullCharacter => f.write_str(": The path contains a null character."),
+            NullCharacter => f.write_str(": The string contains a null character."),
             UnsupportedPlatform => f.write_str(": The target system is not supported by visa."),
+            Status(status) => write!(f, ": {status}"),
         }
     }
 }
@@ -66,6 +70,7 @@ impl ErrorTrait for Error {
             PathNotMatchingLibrary(_) => "Address does not match any dynamic link library",
             NullCharacter => "Uncategorized",
             UnsupportedPlatform => "The target system is not supported by visa",
+            Status(_) => "The VISA function returned an error status",
         }
     }
 
@@ -77,7 +82,8 @@ impl ErrorTrait for Error {
             | &NullSymbol
             | &PathNotMatchingLibrary(_)
             | &NullCharacter
-            | &UnsupportedPlatform => None,
+            | &UnsupportedPlatform
+            | &Status(_) => None,
         }
     }
 }
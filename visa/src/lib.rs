@@ -2,12 +2,17 @@
 #![allow(non_camel_case_types)]
 #![allow(non_snake_case)]
 
+pub mod attr;
 mod bindings;
 pub mod err;
+pub mod safe;
+pub mod status;
 
 use crate::err::Error;
 pub use bindings::*;
 use dlopen::wrapper::Container;
+pub use safe::{FindList, ParsedResource, ResourceManager, Session};
+pub use status::{Severity, VisaStatus};
 use std::borrow::Cow;
 // use visa::Visa;
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
@@ -24,7 +29,8 @@ pub enum Binary {
 }
 
 impl Binary {
-    fn binary_name(&self) -> Result<Cow<str>, Error> {
+    /// The file name or path of the library that is loaded for this binary on the current platform.
+    pub fn file_name(&self) -> Result<Cow<'_, str>, Error> {
         Ok(match self {
             Binary::Keysight => {
                 if cfg!(target_family = "windows") {
@@ -62,11 +68,55 @@ impl Binary {
     }
 }
 
-impl ToString for Binary {
-    fn to_string(&self) -> String {
-        self.binary_name()
-            .unwrap_or_else(|e| e.to_string().into())
-            .into()
+/// Marks the path of a custom binary in the text form when the path alone would read as one of
+/// the named binaries, e.g. `custom:primary`.
+const CUSTOM_PREFIX: &str = "custom:";
+
+/// The stable text form of the binary: `keysight`, `nivisa`, `primary` or the path of a custom
+/// binary. It is the same on every platform, see [`Binary::file_name`] for the library file.
+impl std::fmt::Display for Binary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Binary::Keysight => f.write_str("keysight"),
+            Binary::NiVisa => f.write_str("nivisa"),
+            Binary::Primary => f.write_str("primary"),
+            Binary::Custom(path) if path.parse() == Ok(Binary::Custom(path.clone())) => {
+                f.write_str(path)
+            }
+            Binary::Custom(path) => write!(f, "{CUSTOM_PREFIX}{path}"),
+        }
+    }
+}
+
+/// Parses the text form of [`Display`](std::fmt::Display). The names are not case sensitive.
+/// Anything else is the path of a custom binary, with the `custom:` prefix removed.
+impl std::str::FromStr for Binary {
+    type Err = std::convert::Infallible;
+    fn from_str(binary: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = binary.strip_prefix(CUSTOM_PREFIX) {
+            return Ok(Binary::Custom(path.to_owned()));
+        }
+        Ok(match binary.to_ascii_lowercase().as_str() {
+            "keysight" => Binary::Keysight,
+            "nivisa" => Binary::NiVisa,
+            "primary" => Binary::Primary,
+            _ => Binary::Custom(binary.to_owned()),
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Binary {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Binary {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let binary = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        Ok(binary.parse().unwrap_or_else(|never| match never {}))
     }
 }
 ///This factory method loads a visa dynamically linked library .dll or .so etc.
@@ -77,17 +127,41 @@ impl ToString for Binary {
 /// .or_else(|_| visa::create(&visa::Binary::Custom("visa.so".into())));
 ///```
 pub fn create(bin: &Binary) -> Result<Container<VisaFuncs>, Error> {
-    unsafe { Container::load(bin.binary_name()?.as_ref()).map_err(Error::from) }
+    unsafe { Container::load(bin.file_name()?.as_ref()).map_err(Error::from) }
 }
 
 #[cfg(test)]
 mod tests {
     use super::Binary;
 
+    #[test]
+    fn text_form_round_trips() {
+        for binary in [
+            Binary::Keysight,
+            Binary::NiVisa,
+            Binary::Primary,
+            Binary::Custom("/opt/visa/libvisa.so".into()),
+            Binary::Custom("primary".into()),
+            Binary::Custom("Keysight".into()),
+            Binary::Custom("custom:libvisa.so".into()),
+        ] {
+            assert_eq!(binary.to_string().parse::<Binary>(), Ok(binary));
+        }
+        assert_eq!("NiVisa".parse::<Binary>(), Ok(Binary::NiVisa));
+        assert_eq!(
+            Binary::Custom("primary".into()).to_string(),
+            "custom:primary"
+        );
+        assert_eq!(
+            Binary::Custom("/opt/visa/libvisa.so".into()).to_string(),
+            "/opt/visa/libvisa.so"
+        );
+    }
+
     #[test]
     fn failed_to_find_dll_file() {
         let binary = Binary::Custom("DummyLibraryThatDoesntExist".into());
         let visa = super::create(&binary);
-        assert!(matches!(visa, Err(_)));
+        assert!(visa.is_err());
     }
 }
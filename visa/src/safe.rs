@@ -0,0 +1,384 @@
+//! Safe wrappers around the raw VISA functions. Sessions are closed when they are dropped,
+//! strings and buffers are passed as Rust types and every status code is turned into a
+//! [`Result`]. The raw functions stay available through [`ResourceManager::visa`] and
+//! [`Session::raw`] for anything that isn't wrapped yet.
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use visa::{Binary, ResourceManager};
+//!
+//! let rm = Arc::new(ResourceManager::load(&Binary::Primary)?);
+//! let session = rm.open("TCPIP0::192.168.0.2::5025::SOCKET")?;
+//! session.write_all(b"*IDN?\n")?;
+//! let mut response = [0u8; 256];
+//! let (count, _) = session.read(&mut response)?;
+//! println!("{}", String::from_utf8_lossy(&response[..count]));
+//! # Ok::<(), visa::err::Error>(())
+//! ```
+use crate::attr::{self, AttrValue, Attribute, Value, Writable};
+use crate::err::Error;
+use crate::status::VisaStatus;
+use crate::*;
+use dlopen::wrapper::Container;
+use std::ffi::CString;
+use std::sync::Arc;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Size of the buffer `viStatusDesc` writes into as required by the VISA specification.
+const STATUS_DESC_BUFFER_SIZE: usize = 256;
+
+/// Converts a VISA status into a result. Warnings and completion codes are successful and are
+/// returned so that callers can tell, for example, why a read ended.
+pub fn check(status: ViStatus) -> Result<VisaStatus> {
+    let status = VisaStatus(status);
+    if status.is_error() {
+        Err(Error::Status(status))
+    } else {
+        Ok(status)
+    }
+}
+
+fn to_c_string(text: &str) -> Result<CString> {
+    CString::new(text).map_err(|_| Error::NullCharacter)
+}
+
+/// Converts a NUL terminated buffer filled by the driver into a string.
+pub(crate) fn buffer_to_string(buffer: &[u8]) -> String {
+    let len = buffer.iter().position(|b| *b == 0).unwrap_or(buffer.len());
+    String::from_utf8_lossy(&buffer[..len]).into_owned()
+}
+
+/// A default resource manager session. Sessions opened through it keep it alive and it is
+/// closed with `viClose` once it and all of its sessions are dropped.
+pub struct ResourceManager {
+    visa: Arc<Container<VisaFuncs>>,
+    session: ViSession,
+}
+
+impl ResourceManager {
+    /// Opens the default resource manager of an already loaded binary.
+    pub fn open_default(visa: Arc<Container<VisaFuncs>>) -> Result<ResourceManager> {
+        let mut session: ViSession = 0;
+        check(visa.viOpenDefaultRM(&mut session))?;
+        Ok(ResourceManager { visa, session })
+    }
+
+    /// Loads the binary then opens its default resource manager.
+    pub fn load(binary: &Binary) -> Result<ResourceManager> {
+        ResourceManager::open_default(Arc::new(create(binary)?))
+    }
+
+    /// The raw functions of the loaded binary.
+    pub fn visa(&self) -> &Arc<Container<VisaFuncs>> {
+        &self.visa
+    }
+
+    /// The raw resource manager session.
+    pub fn raw(&self) -> ViSession {
+        self.session
+    }
+
+    /// Opens a session to a resource such as `GPIB0::12::INSTR`.
+    pub fn open(self: &Arc<Self>, resource: &str) -> Result<Session> {
+        self.open_with(resource, VI_NO_LOCK, VI_TMO_IMMEDIATE)
+    }
+
+    /// Opens a session to a resource with an access mode such as `VI_EXCLUSIVE_LOCK` and the
+    /// time in milliseconds to wait for the lock.
+    pub fn open_with(
+        self: &Arc<Self>,
+        resource: &str,
+        access_mode: ViAccessMode,
+        open_timeout: u32,
+    ) -> Result<Session> {
+        let resource = to_c_string(resource)?;
+        let mut vi: ViSession = 0;
+        check(self.visa.viOpen(
+            self.session,
+            resource.as_ptr(),
+            access_mode,
+            open_timeout,
+            &mut vi,
+        ))?;
+        Ok(Session {
+            rm: self.clone(),
+            vi,
+        })
+    }
+
+    /// Finds the resources matching a VISA expression such as `?*INSTR` or `GPIB?*`. The list
+    /// is empty when nothing matches.
+    pub fn find(self: &Arc<Self>, expression: &str) -> Result<FindList> {
+        let expression = to_c_string(expression)?;
+        let mut vi: ViFindList = 0;
+        let mut count = 0u32;
+        let mut desc = [0u8; VI_FIND_BUFLEN as usize];
+        let status = self.visa.viFindRsrc(
+            self.session,
+            expression.as_ptr(),
+            &mut vi,
+            &mut count,
+            desc.as_mut_ptr() as *mut ViChar,
+        );
+        let first = match check(status) {
+            Ok(_) => Some(buffer_to_string(&desc)),
+            Err(Error::Status(VisaStatus::ERROR_RSRC_NFOUND)) => None,
+            Err(e) => Err(e)?,
+        };
+        Ok(FindList {
+            rm: self.clone(),
+            vi,
+            first,
+            remaining: count.saturating_sub(1),
+        })
+    }
+
+    /// Parses a resource name or alias with `viParseRsrcEx`.
+    pub fn parse_resource(&self, resource: &str) -> Result<ParsedResource> {
+        let resource = to_c_string(resource)?;
+        let mut interface_type = 0u16;
+        let mut board = 0u16;
+        let mut class = [0u8; VI_FIND_BUFLEN as usize];
+        let mut expanded = [0u8; VI_FIND_BUFLEN as usize];
+        let mut alias = [0u8; VI_FIND_BUFLEN as usize];
+        check(self.visa.viParseRsrcEx(
+            self.session,
+            resource.as_ptr(),
+            &mut interface_type,
+            &mut board,
+            class.as_mut_ptr() as *mut ViChar,
+            expanded.as_mut_ptr() as *mut ViChar,
+            alias.as_mut_ptr() as *mut ViChar,
+        ))?;
+        let alias = buffer_to_string(&alias);
+        Ok(ParsedResource {
+            interface_type,
+            board,
+            class: buffer_to_string(&class),
+            expanded_name: buffer_to_string(&expanded),
+            alias: (!alias.is_empty()).then_some(alias),
+        })
+    }
+
+    /// Description of a status code as provided by the loaded binary. Prefer the `Display` of
+    /// [`VisaStatus`] unless vendor specific codes are expected.
+    pub fn status_description(&self, status: VisaStatus) -> Option<String> {
+        status_description(&self.visa, self.session, status)
+    }
+}
+
+impl Drop for ResourceManager {
+    fn drop(&mut self) {
+        self.visa.viClose(self.session);
+    }
+}
+
+fn status_description(visa: &VisaFuncs, vi: ViObject, status: VisaStatus) -> Option<String> {
+    let mut desc = [0u8; STATUS_DESC_BUFFER_SIZE];
+    check(visa.viStatusDesc(vi, status.code(), desc.as_mut_ptr())).ok()?;
+    Some(buffer_to_string(&desc).trim_end().to_owned())
+}
+
+/// Resource names returned by [`ResourceManager::find`]. The find list is closed when it is
+/// dropped.
+pub struct FindList {
+    rm: Arc<ResourceManager>,
+    vi: ViFindList,
+    first: Option<String>,
+    remaining: u32,
+}
+
+impl Iterator for FindList {
+    type Item = Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(first) = self.first.take() {
+            return Some(Ok(first));
+        }
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let mut desc = [0u8; VI_FIND_BUFLEN as usize];
+        let status = self
+            .rm
+            .visa
+            .viFindNext(self.vi, desc.as_mut_ptr() as *mut ViChar);
+        Some(check(status).map(|_| buffer_to_string(&desc)))
+    }
+}
+
+impl Drop for FindList {
+    fn drop(&mut self) {
+        if self.vi != VI_NULL {
+            self.rm.visa.viClose(self.vi);
+        }
+    }
+}
+
+/// A resource name split into its parts by the VISA library.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedResource {
+    /// One of the `VI_INTF_*` constants such as `VI_INTF_GPIB`.
+    pub interface_type: u16,
+    pub board: u16,
+    /// The resource class such as `INSTR` or `SOCKET`.
+    pub class: String,
+    /// The full resource name with aliases resolved.
+    pub expanded_name: String,
+    pub alias: Option<String>,
+}
+
+/// An open session to an instrument. The session is closed when it is dropped.
+pub struct Session {
+    rm: Arc<ResourceManager>,
+    vi: ViSession,
+}
+
+impl Session {
+    /// The raw session to use with the functions returned by [`Session::visa`].
+    pub fn raw(&self) -> ViSession {
+        self.vi
+    }
+
+    /// The raw functions of the binary this session was opened with.
+    pub fn visa(&self) -> &Arc<Container<VisaFuncs>> {
+        &self.rm.visa
+    }
+
+    /// The resource manager that opened this session.
+    pub fn resource_manager(&self) -> &Arc<ResourceManager> {
+        &self.rm
+    }
+
+    /// Writes as much of the data as the driver accepts in one call and returns the count.
+    pub fn write(&self, data: &[u8]) -> Result<usize> {
+        let count = u32::try_from(data.len()).unwrap_or(u32::MAX);
+        let mut ret_cnt = 0u32;
+        check(
+            self.rm
+                .visa
+                .viWrite(self.vi, data.as_ptr(), count, &mut ret_cnt),
+        )?;
+        Ok(ret_cnt as usize)
+    }
+
+    /// Writes all of the data. A driver that accepts none of the data without an error status
+    /// would otherwise be called forever, so that is reported as [`VisaStatus::ERROR_IO`].
+    pub fn write_all(&self, mut data: &[u8]) -> Result<()> {
+        while !data.is_empty() {
+            let written = self.write(data)?;
+            if written == 0 {
+                return Err(Error::Status(VisaStatus::ERROR_IO));
+            }
+            data = &data[written..];
+        }
+        Ok(())
+    }
+
+    /// Writes a text message as is. Termination characters are not added.
+    pub fn write_str(&self, message: &str) -> Result<()> {
+        self.write_all(message.as_bytes())
+    }
+
+    /// Reads into the buffer. Returns the number of bytes read and the completion status which
+    /// tells why the read ended, for example [`VisaStatus::SUCCESS_TERM_CHAR`] or
+    /// [`VisaStatus::SUCCESS_MAX_CNT`].
+    pub fn read(&self, buf: &mut [u8]) -> Result<(usize, VisaStatus)> {
+        let count = u32::try_from(buf.len()).unwrap_or(u32::MAX);
+        let mut ret_cnt = 0u32;
+        let status = check(
+            self.rm
+                .visa
+                .viRead(self.vi, buf.as_mut_ptr(), count, &mut ret_cnt),
+        )?;
+        Ok((ret_cnt as usize, status))
+    }
+
+    /// Clears the device.
+    pub fn clear(&self) -> Result<()> {
+        check(self.rm.visa.viClear(self.vi)).map(|_| ())
+    }
+
+    /// Sends a trigger using the given protocol such as `VI_TRIG_PROT_DEFAULT`.
+    pub fn assert_trigger(&self, protocol: u16) -> Result<()> {
+        check(self.rm.visa.viAssertTrigger(self.vi, protocol)).map(|_| ())
+    }
+
+    /// Reads the status byte of the device.
+    pub fn read_stb(&self) -> Result<u16> {
+        let mut stb = 0u16;
+        check(self.rm.visa.viReadSTB(self.vi, &mut stb))?;
+        Ok(stb)
+    }
+
+    /// Controls the REN line with a mode such as `VI_GPIB_REN_ASSERT_LLO`.
+    pub fn gpib_control_ren(&self, mode: u16) -> Result<()> {
+        check(self.rm.visa.viGpibControlREN(self.vi, mode)).map(|_| ())
+    }
+
+    /// Controls the ATN line of an interface with a mode such as `VI_GPIB_ATN_ASSERT`.
+    pub fn gpib_control_atn(&self, mode: u16) -> Result<()> {
+        check(self.rm.visa.viGpibControlATN(self.vi, mode)).map(|_| ())
+    }
+
+    /// Pulses the interface clear line of an interface.
+    pub fn gpib_send_ifc(&self) -> Result<()> {
+        check(self.rm.visa.viGpibSendIFC(self.vi)).map(|_| ())
+    }
+
+    /// Sends command bytes with ATN asserted and returns the number of bytes sent.
+    pub fn gpib_command(&self, commands: &[u8]) -> Result<usize> {
+        let count = u32::try_from(commands.len()).unwrap_or(u32::MAX);
+        let mut ret_cnt = 0u32;
+        check(
+            self.rm
+                .visa
+                .viGpibCommand(self.vi, commands.as_ptr(), count, &mut ret_cnt),
+        )?;
+        Ok(ret_cnt as usize)
+    }
+
+    /// Passes controller in charge to a device. Use `VI_NO_SEC_ADDR` as the secondary address
+    /// of a device without one.
+    pub fn gpib_pass_control(&self, primary: u16, secondary: u16) -> Result<()> {
+        check(self.rm.visa.viGpibPassControl(self.vi, primary, secondary)).map(|_| ())
+    }
+
+    /// Reads a typed attribute, for example `session.get::<attr::TmoValue>()`.
+    pub fn get<A: Attribute>(&self) -> Result<A::Value> {
+        A::Value::read(self, A::ID)
+    }
+
+    /// Sets a typed attribute, for example `session.set::<attr::AsrlBaud>(115200)`.
+    pub fn set<A: Writable>(&self, value: A::Value) -> Result<()> {
+        self.set_attribute(A::ID, value.to_state())
+    }
+
+    /// Reads every known attribute for diagnostics. Attributes the resource doesn't support
+    /// are left out.
+    pub fn attributes(&self) -> Vec<(&'static str, Result<Value>)> {
+        let mut attributes = attr::dump(self);
+        attributes
+            .retain(|(_, value)| !matches!(value, Err(Error::Status(VisaStatus::ERROR_NSUP_ATTR))));
+        attributes
+    }
+
+    /// Sets an attribute such as `VI_ATTR_TMO_VALUE` to a raw value.
+    pub fn set_attribute(&self, attribute: ViAttr, value: ViAttrState) -> Result<()> {
+        check(self.rm.visa.viSetAttribute(self.vi, attribute, value)).map(|_| ())
+    }
+
+    /// Description of a status code as provided by the loaded binary. Prefer the `Display` of
+    /// [`VisaStatus`] unless vendor specific codes are expected.
+    pub fn status_description(&self, status: VisaStatus) -> Option<String> {
+        status_description(&self.rm.visa, self.vi, status)
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.rm.visa.viClose(self.vi);
+    }
+}
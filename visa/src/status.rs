@@ -0,0 +1,235 @@
+//! Typed VISA status codes. A [`VisaStatus`] knows its name, severity and the description
+//! from the VISA specification so it can be displayed without asking a loaded binary.
+use crate::*;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// Whether a status reports a success, a success with a warning or a failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Severity {
+    Success,
+    Warning,
+    Error,
+}
+
+/// A status code returned by a VISA function. Every `VI_SUCCESS_*`, `VI_WARN_*` and
+/// `VI_ERROR_*` constant is available without its `VI_` prefix, for example
+/// [`VisaStatus::ERROR_TMO`], so that statuses can be used in patterns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VisaStatus(pub ViStatus);
+
+macro_rules! statuses {
+    ($($name:ident = $code:expr, $severity:ident, $desc:literal;)*) => {
+        impl VisaStatus {
+            $(pub const $name: VisaStatus = VisaStatus($code as ViStatus);)*
+        }
+
+        /// Every known status with its name, severity and description.
+        const STATUSES: &[(VisaStatus, &str, Severity, &str)] = &[
+            $((VisaStatus::$name, concat!("VI_", stringify!($name)), Severity::$severity, $desc),)*
+        ];
+    };
+}
+
+statuses! {
+    SUCCESS = VI_SUCCESS, Success, "Operation completed successfully.";
+    SUCCESS_EVENT_EN = VI_SUCCESS_EVENT_EN, Success, "Specified event is already enabled for at least one of the specified mechanisms.";
+    SUCCESS_EVENT_DIS = VI_SUCCESS_EVENT_DIS, Success, "Specified event is already disabled for at least one of the specified mechanisms.";
+    SUCCESS_QUEUE_EMPTY = VI_SUCCESS_QUEUE_EMPTY, Success, "Operation completed successfully, but queue was already empty.";
+    SUCCESS_TERM_CHAR = VI_SUCCESS_TERM_CHAR, Success, "The specified termination character was read.";
+    SUCCESS_MAX_CNT = VI_SUCCESS_MAX_CNT, Success, "The number of bytes read is equal to the input count.";
+    SUCCESS_DEV_NPRESENT = VI_SUCCESS_DEV_NPRESENT, Success, "Session opened successfully, but the device at the specified address is not responding.";
+    SUCCESS_TRIG_MAPPED = VI_SUCCESS_TRIG_MAPPED, Success, "The path from the trigger source line to the destination line is already mapped.";
+    SUCCESS_QUEUE_NEMPTY = VI_SUCCESS_QUEUE_NEMPTY, Success, "Wait terminated successfully on receipt of an event notification. There is still at least one more event occurrence of the requested type(s) available for this session.";
+    SUCCESS_NCHAIN = VI_SUCCESS_NCHAIN, Success, "Event handled successfully. Do not invoke any other handlers on this session for this event.";
+    SUCCESS_NESTED_SHARED = VI_SUCCESS_NESTED_SHARED, Success, "Operation completed successfully, and this session has nested shared locks.";
+    SUCCESS_NESTED_EXCLUSIVE = VI_SUCCESS_NESTED_EXCLUSIVE, Success, "Operation completed successfully, and this session has nested exclusive locks.";
+    SUCCESS_SYNC = VI_SUCCESS_SYNC, Success, "Asynchronous operation request was actually performed synchronously.";
+    WARN_QUEUE_OVERFLOW = VI_WARN_QUEUE_OVERFLOW, Warning, "VISA received more event information of the specified type than the configured queue size could hold.";
+    WARN_CONFIG_NLOADED = VI_WARN_CONFIG_NLOADED, Warning, "The specified configuration either does not exist or could not be loaded. VISA-specified defaults will be used.";
+    WARN_NULL_OBJECT = VI_WARN_NULL_OBJECT, Warning, "The specified object reference is uninitialized.";
+    WARN_NSUP_ATTR_STATE = VI_WARN_NSUP_ATTR_STATE, Warning, "Although the specified state of the attribute is valid, it is not supported by this implementation.";
+    WARN_UNKNOWN_STATUS = VI_WARN_UNKNOWN_STATUS, Warning, "The status code passed to the operation could not be interpreted.";
+    WARN_NSUP_BUF = VI_WARN_NSUP_BUF, Warning, "The specified I/O buffer type is not supported.";
+    WARN_EXT_FUNC_NIMPL = VI_WARN_EXT_FUNC_NIMPL, Warning, "The operation succeeded, but a lower level driver did not implement the extended functionality.";
+    WARN_SERVER_CERT_UNTRUSTED = VI_WARN_SERVER_CERT_UNTRUSTED, Warning, "The server certificate is not trusted.";
+    ERROR_SYSTEM_ERROR = VI_ERROR_SYSTEM_ERROR, Error, "Unknown system error (miscellaneous error).";
+    ERROR_INV_OBJECT = VI_ERROR_INV_OBJECT, Error, "The given session or object reference is invalid.";
+    ERROR_RSRC_LOCKED = VI_ERROR_RSRC_LOCKED, Error, "Specified type of lock cannot be obtained or specified operation cannot be performed, because the resource is locked.";
+    ERROR_INV_EXPR = VI_ERROR_INV_EXPR, Error, "Invalid expression specified for search.";
+    ERROR_RSRC_NFOUND = VI_ERROR_RSRC_NFOUND, Error, "Insufficient location information or the device or resource is not present in the system.";
+    ERROR_INV_RSRC_NAME = VI_ERROR_INV_RSRC_NAME, Error, "Invalid resource reference specified. Parsing error.";
+    ERROR_INV_ACC_MODE = VI_ERROR_INV_ACC_MODE, Error, "Invalid access mode.";
+    ERROR_TMO = VI_ERROR_TMO, Error, "Timeout expired before operation completed.";
+    ERROR_CLOSING_FAILED = VI_ERROR_CLOSING_FAILED, Error, "Unable to deallocate the previously allocated data structures corresponding to this session or object reference.";
+    ERROR_INV_DEGREE = VI_ERROR_INV_DEGREE, Error, "Specified degree is invalid.";
+    ERROR_INV_JOB_ID = VI_ERROR_INV_JOB_ID, Error, "Specified job identifier is invalid.";
+    ERROR_NSUP_ATTR = VI_ERROR_NSUP_ATTR, Error, "The specified attribute is not defined or supported by the referenced session, event, or find list.";
+    ERROR_NSUP_ATTR_STATE = VI_ERROR_NSUP_ATTR_STATE, Error, "The specified state of the attribute is not valid, or is not supported as defined by the session, event, or find list.";
+    ERROR_ATTR_READONLY = VI_ERROR_ATTR_READONLY, Error, "The specified attribute is Read Only.";
+    ERROR_INV_LOCK_TYPE = VI_ERROR_INV_LOCK_TYPE, Error, "The specified type of lock is not supported by this resource.";
+    ERROR_INV_ACCESS_KEY = VI_ERROR_INV_ACCESS_KEY, Error, "The access key to the resource associated with this session is invalid.";
+    ERROR_INV_EVENT = VI_ERROR_INV_EVENT, Error, "Specified event type is not supported by the resource.";
+    ERROR_INV_MECH = VI_ERROR_INV_MECH, Error, "Invalid mechanism specified.";
+    ERROR_HNDLR_NINSTALLED = VI_ERROR_HNDLR_NINSTALLED, Error, "A handler is not currently installed for the specified event.";
+    ERROR_INV_HNDLR_REF = VI_ERROR_INV_HNDLR_REF, Error, "The given handler reference is invalid.";
+    ERROR_INV_CONTEXT = VI_ERROR_INV_CONTEXT, Error, "Specified event context is invalid.";
+    ERROR_NENABLED = VI_ERROR_NENABLED, Error, "The session must be enabled for events of the specified type in order to receive them.";
+    ERROR_ABORT = VI_ERROR_ABORT, Error, "User abort occurred during transfer.";
+    ERROR_RAW_WR_PROT_VIOL = VI_ERROR_RAW_WR_PROT_VIOL, Error, "Violation of raw write protocol occurred during transfer.";
+    ERROR_RAW_RD_PROT_VIOL = VI_ERROR_RAW_RD_PROT_VIOL, Error, "Violation of raw read protocol occurred during transfer.";
+    ERROR_OUTP_PROT_VIOL = VI_ERROR_OUTP_PROT_VIOL, Error, "Device reported an output protocol error during transfer.";
+    ERROR_INP_PROT_VIOL = VI_ERROR_INP_PROT_VIOL, Error, "Device reported an input protocol error during transfer.";
+    ERROR_BERR = VI_ERROR_BERR, Error, "Bus error occurred during transfer.";
+    ERROR_IN_PROGRESS = VI_ERROR_IN_PROGRESS, Error, "Unable to queue the asynchronous operation because there is already an operation in progress.";
+    ERROR_INV_SETUP = VI_ERROR_INV_SETUP, Error, "Unable to start operation because setup is invalid (due to attributes being set to an inconsistent state).";
+    ERROR_QUEUE_ERROR = VI_ERROR_QUEUE_ERROR, Error, "Unable to queue asynchronous operation.";
+    ERROR_ALLOC = VI_ERROR_ALLOC, Error, "Insufficient system resources to perform necessary memory allocation.";
+    ERROR_INV_MASK = VI_ERROR_INV_MASK, Error, "Invalid buffer mask specified.";
+    ERROR_IO = VI_ERROR_IO, Error, "Could not perform operation because of I/O error.";
+    ERROR_INV_FMT = VI_ERROR_INV_FMT, Error, "A format specifier in the format string is invalid.";
+    ERROR_NSUP_FMT = VI_ERROR_NSUP_FMT, Error, "A format specifier in the format string is not supported.";
+    ERROR_LINE_IN_USE = VI_ERROR_LINE_IN_USE, Error, "The specified trigger line is currently in use.";
+    ERROR_LINE_NRESERVED = VI_ERROR_LINE_NRESERVED, Error, "An attempt was made to use a trigger line that is not reserved.";
+    ERROR_NSUP_MODE = VI_ERROR_NSUP_MODE, Error, "The specified mode is not supported by this VISA implementation.";
+    ERROR_SRQ_NOCCURRED = VI_ERROR_SRQ_NOCCURRED, Error, "Service request has not been received for the session.";
+    ERROR_INV_SPACE = VI_ERROR_INV_SPACE, Error, "Invalid address space specified.";
+    ERROR_INV_OFFSET = VI_ERROR_INV_OFFSET, Error, "Invalid offset specified.";
+    ERROR_INV_WIDTH = VI_ERROR_INV_WIDTH, Error, "Invalid access width specified.";
+    ERROR_NSUP_OFFSET = VI_ERROR_NSUP_OFFSET, Error, "Specified offset is not accessible from this hardware.";
+    ERROR_NSUP_VAR_WIDTH = VI_ERROR_NSUP_VAR_WIDTH, Error, "Cannot support source and destination widths that are different.";
+    ERROR_WINDOW_NMAPPED = VI_ERROR_WINDOW_NMAPPED, Error, "The specified session is not currently mapped.";
+    ERROR_RESP_PENDING = VI_ERROR_RESP_PENDING, Error, "A previous response is still pending, causing a multiple query error.";
+    ERROR_NLISTENERS = VI_ERROR_NLISTENERS, Error, "No listeners condition is detected (both NRFD and NDAC are deasserted).";
+    ERROR_NCIC = VI_ERROR_NCIC, Error, "The interface associated with this session is not currently the controller in charge.";
+    ERROR_NSYS_CNTLR = VI_ERROR_NSYS_CNTLR, Error, "The interface associated with this session is not the system controller.";
+    ERROR_NSUP_OPER = VI_ERROR_NSUP_OPER, Error, "The given session or object reference does not support this operation.";
+    ERROR_INTR_PENDING = VI_ERROR_INTR_PENDING, Error, "An interrupt is still pending from a previous call.";
+    ERROR_ASRL_PARITY = VI_ERROR_ASRL_PARITY, Error, "A parity error occurred during transfer.";
+    ERROR_ASRL_FRAMING = VI_ERROR_ASRL_FRAMING, Error, "A framing error occurred during transfer.";
+    ERROR_ASRL_OVERRUN = VI_ERROR_ASRL_OVERRUN, Error, "An overrun error occurred during transfer. A character was not read from the hardware before the next character arrived.";
+    ERROR_TRIG_NMAPPED = VI_ERROR_TRIG_NMAPPED, Error, "The path from the trigger source line to the destination line is not currently mapped.";
+    ERROR_NSUP_ALIGN_OFFSET = VI_ERROR_NSUP_ALIGN_OFFSET, Error, "The specified offset is not properly aligned for the access width of the operation.";
+    ERROR_USER_BUF = VI_ERROR_USER_BUF, Error, "A specified user buffer is not valid or cannot be accessed for the required size.";
+    ERROR_RSRC_BUSY = VI_ERROR_RSRC_BUSY, Error, "The resource is valid, but VISA cannot currently access it.";
+    ERROR_NSUP_WIDTH = VI_ERROR_NSUP_WIDTH, Error, "Specified width is not supported by this hardware.";
+    ERROR_INV_PARAMETER = VI_ERROR_INV_PARAMETER, Error, "The value of some parameter is invalid.";
+    ERROR_INV_PROT = VI_ERROR_INV_PROT, Error, "The protocol specified is invalid.";
+    ERROR_INV_SIZE = VI_ERROR_INV_SIZE, Error, "Invalid size of window specified.";
+    ERROR_WINDOW_MAPPED = VI_ERROR_WINDOW_MAPPED, Error, "The specified session currently contains a mapped window.";
+    ERROR_NIMPL_OPER = VI_ERROR_NIMPL_OPER, Error, "The given operation is not implemented.";
+    ERROR_INV_LENGTH = VI_ERROR_INV_LENGTH, Error, "Invalid length specified.";
+    ERROR_INV_MODE = VI_ERROR_INV_MODE, Error, "The specified mode is invalid.";
+    ERROR_SESN_NLOCKED = VI_ERROR_SESN_NLOCKED, Error, "The current session did not have any lock on the resource.";
+    ERROR_MEM_NSHARED = VI_ERROR_MEM_NSHARED, Error, "The device does not export any memory.";
+    ERROR_LIBRARY_NFOUND = VI_ERROR_LIBRARY_NFOUND, Error, "A code library required by VISA could not be located or loaded.";
+    ERROR_NSUP_INTR = VI_ERROR_NSUP_INTR, Error, "The interface cannot generate an interrupt on the requested level or with the requested statusID value.";
+    ERROR_INV_LINE = VI_ERROR_INV_LINE, Error, "The value specified by the line parameter is invalid.";
+    ERROR_FILE_ACCESS = VI_ERROR_FILE_ACCESS, Error, "An error occurred while trying to open the specified file. Possible reasons include an invalid path or lack of access rights.";
+    ERROR_FILE_IO = VI_ERROR_FILE_IO, Error, "An error occurred while performing I/O on the specified file.";
+    ERROR_NSUP_LINE = VI_ERROR_NSUP_LINE, Error, "One of the specified lines is not supported by this VISA implementation.";
+    ERROR_NSUP_MECH = VI_ERROR_NSUP_MECH, Error, "The specified mechanism is not supported for the given event type.";
+    ERROR_INTF_NUM_NCONFIG = VI_ERROR_INTF_NUM_NCONFIG, Error, "The interface type is valid but the specified interface number is not configured.";
+    ERROR_CONN_LOST = VI_ERROR_CONN_LOST, Error, "The connection for the given session has been lost.";
+    ERROR_NPERMISSION = VI_ERROR_NPERMISSION, Error, "Access to the resource or remote machine is denied.";
+    ERROR_SERVER_CERT = VI_ERROR_SERVER_CERT, Error, "The server certificate is not valid.";
+}
+
+impl VisaStatus {
+    /// Alias of [`VisaStatus::ERROR_INV_OBJECT`] which shares its code.
+    pub const ERROR_INV_SESSION: VisaStatus = VisaStatus::ERROR_INV_OBJECT;
+
+    /// The raw status code.
+    pub fn code(self) -> ViStatus {
+        self.0
+    }
+
+    fn lookup(self) -> Option<&'static (VisaStatus, &'static str, Severity, &'static str)> {
+        STATUSES.iter().find(|(status, ..)| *status == self)
+    }
+
+    /// The name of the constant such as `VI_ERROR_TMO` or `None` for vendor specific codes.
+    pub fn name(self) -> Option<&'static str> {
+        self.lookup().map(|(_, name, ..)| *name)
+    }
+
+    /// The description from the VISA specification or `None` for vendor specific codes.
+    pub fn description(self) -> Option<&'static str> {
+        self.lookup().map(|(.., desc)| *desc)
+    }
+
+    /// Negative codes are errors. Positive codes are successes unless they are one of the
+    /// known warnings.
+    pub fn severity(self) -> Severity {
+        match self.lookup() {
+            Some((_, _, severity, _)) => *severity,
+            None if self.0 < 0 => Severity::Error,
+            None => Severity::Success,
+        }
+    }
+
+    pub fn is_error(self) -> bool {
+        self.0 < 0
+    }
+
+    pub fn is_warning(self) -> bool {
+        self.severity() == Severity::Warning
+    }
+}
+
+impl From<ViStatus> for VisaStatus {
+    fn from(value: ViStatus) -> Self {
+        VisaStatus(value)
+    }
+}
+
+impl From<VisaStatus> for ViStatus {
+    fn from(value: VisaStatus) -> Self {
+        value.0
+    }
+}
+
+impl Display for VisaStatus {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self.lookup() {
+            Some((_, name, _, desc)) => write!(f, "{name} (0x{:08X}): {desc}", self.0),
+            None => write!(f, "Unknown VISA status 0x{:08X}", self.0),
+        }
+    }
+}
+
+impl std::error::Error for VisaStatus {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(VisaStatus(VI_ERROR_TMO), "VI_ERROR_TMO", Severity::Error)]
+    #[test_case(
+        VisaStatus(VI_ERROR_INV_SESSION),
+        "VI_ERROR_INV_OBJECT",
+        Severity::Error
+    )]
+    #[test_case(VisaStatus(VI_SUCCESS_TERM_CHAR as ViStatus), "VI_SUCCESS_TERM_CHAR", Severity::Success)]
+    #[test_case(VisaStatus(VI_WARN_NSUP_BUF as ViStatus), "VI_WARN_NSUP_BUF", Severity::Warning)]
+    fn known_status(status: VisaStatus, name: &str, severity: Severity) {
+        assert_eq!(status.name(), Some(name));
+        assert_eq!(status.severity(), severity);
+    }
+
+    #[test]
+    fn display_does_not_need_a_library() {
+        assert_eq!(
+            VisaStatus::ERROR_TMO.to_string(),
+            "VI_ERROR_TMO (0xBFFF0015): Timeout expired before operation completed."
+        );
+        assert_eq!(VisaStatus(-1).to_string(), "Unknown VISA status 0xFFFFFFFF");
+        assert_eq!(VisaStatus(-1).severity(), Severity::Error);
+    }
+
+    #[test]
+    fn every_constant_is_described() {
+        assert_eq!(STATUSES.len(), 101);
+        assert!(STATUSES.iter().all(|(_, _, _, desc)| !desc.is_empty()));
+    }
+}
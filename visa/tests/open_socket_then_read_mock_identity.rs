@@ -35,7 +35,7 @@ fn open_socket_then_read_mock_identity() -> Result<(), Box<dyn Error>> {
         0,
         "Failed to write to visa connection."
     );
-    let resp = vec![0u8; 50];
+    let resp = [0u8; 50];
     let status = visa.viRead(vi, resp.as_ptr() as *mut _, 50, &mut ret_cnt);
     let response = std::str::from_utf8(&resp[0..ret_cnt as usize])?;
     println!("Response : {}", response);
@@ -0,0 +1,85 @@
+use std::sync::Arc;
+use visa::{attr, err::Error, ResourceManager, VisaStatus};
+
+#[test]
+fn safe_session_queries_simulated_instrument() -> Result<(), Error> {
+    let rm = Arc::new(ResourceManager::load(&visa_mock::binary())?);
+    let session = rm.open("GPIB0::7::INSTR")?;
+    session.set_attribute(visa::VI_ATTR_TERMCHAR, b'\n' as visa::ViAttrState)?;
+    session.set_attribute(visa::VI_ATTR_TERMCHAR_EN, 1)?;
+    session.write_str("*IDN?\n")?;
+    let mut response = [0u8; 128];
+    let (count, status) = session.read(&mut response)?;
+    assert_eq!(status, VisaStatus::SUCCESS_TERM_CHAR);
+    assert_eq!(
+        &response[..count],
+        b"Cosmere,mock1000,GPIB0::7::INSTR,V0.01.00\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn failed_open_returns_status() {
+    let rm = Arc::new(ResourceManager::load(&visa_mock::binary()).unwrap());
+    let result = rm.open("GPIB0::29::INSTR");
+    assert!(matches!(
+        result,
+        Err(Error::Status(VisaStatus::ERROR_RSRC_NFOUND))
+    ));
+    assert!(matches!(
+        rm.open("GPIB0::7\0::INSTR"),
+        Err(Error::NullCharacter)
+    ));
+}
+
+#[test]
+fn typed_attributes_use_their_value_type() -> Result<(), Error> {
+    let rm = Arc::new(ResourceManager::load(&visa_mock::binary())?);
+    let session = rm.open("ASRL1::INSTR")?;
+    session.set::<attr::AsrlBaud>(115200)?;
+    assert_eq!(session.get::<attr::AsrlBaud>()?, 115200);
+    session.set::<attr::TermcharEn>(true)?;
+    assert!(session.get::<attr::TermcharEn>()?);
+    assert_eq!(session.get::<attr::TmoValue>()?, 2000);
+    assert_eq!(session.get::<attr::RsrcName>()?, "ASRL1::INSTR");
+    assert_eq!(session.get::<attr::ModelName>()?, "mock1000");
+    assert!(matches!(
+        session.set_attribute(visa::VI_ATTR_RSRC_CLASS, 0),
+        Err(Error::Status(VisaStatus::ERROR_ATTR_READONLY))
+    ));
+
+    let attributes = session.attributes();
+    assert!(attributes
+        .iter()
+        .any(|(name, value)| *name == "VI_ATTR_ASRL_BAUD"
+            && matches!(value, Ok(attr::Value::U32(115200)))));
+    assert!(attributes
+        .iter()
+        .all(|(name, _)| *name != "VI_ATTR_GPIB_REN_STATE"));
+    Ok(())
+}
+
+#[test]
+fn gpib_bus_control_updates_line_states() -> Result<(), Error> {
+    let rm = Arc::new(ResourceManager::load(&visa_mock::binary())?);
+    let interface = rm.open("GPIB0::INTFC")?;
+    interface.gpib_send_ifc()?;
+    assert!(interface.get::<attr::GpibCicState>()?);
+    interface.gpib_control_ren(visa::VI_GPIB_REN_ASSERT_LLO as u16)?;
+    assert_eq!(
+        interface.get::<attr::GpibRenState>()?,
+        visa::VI_STATE_ASSERTED as i16
+    );
+    interface.gpib_control_atn(visa::VI_GPIB_ATN_ASSERT as u16)?;
+    assert_eq!(interface.gpib_command(&[0x3F, 0x5F])?, 2);
+    interface.gpib_pass_control(7, visa::VI_NO_SEC_ADDR as u16)?;
+    assert!(!interface.get::<attr::GpibCicState>()?);
+
+    let instrument = rm.open("GPIB0::7::INSTR")?;
+    instrument.gpib_control_ren(visa::VI_GPIB_REN_ADDRESS_GTL as u16)?;
+    assert!(matches!(
+        instrument.gpib_send_ifc(),
+        Err(Error::Status(VisaStatus::ERROR_NSUP_OPER))
+    ));
+    Ok(())
+}
@@ -0,0 +1,833 @@
+//! A stand-in VISA library. The crate is built as a dynamic library that exports the same
+//! symbols as a vendor VISA binary so it can be loaded with `visa::Binary::Custom`. It allows
+//! the workspace to test VISA code paths on machines where no vendor VISA is installed.
+//!
+//! Two kinds of resources can be opened:
+//! - `TCPIP[n]::host::port::SOCKET` opens a real TCP connection.
+//! - Any resource in [`SIMULATED_RESOURCES`] opens a simulated message based instrument
+//!   which answers `*IDN?` with `Cosmere,mock1000,<resource name>,V0.01.00`. A simulated GPIB
+//!   instrument also answers at any secondary address of its primary address.
+//! - `GPIB[n]::INTFC` opens a GPIB interface. Bus control only updates the line states that
+//!   are reported through the `VI_ATTR_GPIB_*` attributes.
+//!
+//! `viFindRsrc` lists the simulated resources and [`ALIASES`] can be used in their place.
+//! Every other operation returns `VI_ERROR_NSUP_OPER`.
+#![allow(non_snake_case)]
+// The exported functions mirror the VISA C API so their safety contract is the VISA specification.
+#![allow(clippy::missing_safety_doc)]
+
+use dlopen::raw::Library;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::{HashMap, VecDeque};
+use std::ffi::{c_char, c_void, CStr};
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use visa::*;
+
+/// Resources that open a simulated instrument instead of a real connection.
+pub const SIMULATED_RESOURCES: &[&str] = &[
+    "GPIB0::7::INSTR",
+    "GPIB0::9::INSTR",
+    "TCPIP0::127.0.0.1::inst0::INSTR",
+    "USB0::0x2A8D::0x0101::MY12345678::0::INSTR",
+    "ASRL1::INSTR",
+];
+
+/// Aliases of simulated resources as they would be configured in a vendor connection expert.
+pub const ALIASES: &[(&str, &str)] = &[("DMM1", "GPIB0::7::INSTR")];
+
+const DEFAULT_TIMEOUT_MS: ViAttrState = 2000;
+const DEFAULT_TERMCHAR: ViAttrState = 0x0A;
+
+enum Kind {
+    ResourceManager,
+    /// Resources that `viFindNext` has not returned yet.
+    FindList(VecDeque<String>),
+    Simulated(Arc<Mutex<Simulated>>),
+    Socket(Arc<Mutex<Socket>>),
+    GpibInterface,
+}
+
+struct Session {
+    /// Resource manager that opened this session. Resource managers own themselves.
+    rm: ViSession,
+    resource: String,
+    attributes: HashMap<ViAttr, ViAttrState>,
+    kind: Kind,
+}
+
+impl Session {
+    /// Attributes that were set or have a default. Any other attribute is not supported.
+    fn attribute(&self, attr: ViAttr) -> Option<ViAttrState> {
+        match (self.attributes.get(&attr), attr) {
+            (Some(value), _) => Some(*value),
+            (None, VI_ATTR_TMO_VALUE) => Some(DEFAULT_TIMEOUT_MS),
+            (None, VI_ATTR_TERMCHAR) => Some(DEFAULT_TERMCHAR),
+            (None, VI_ATTR_TERMCHAR_EN) => Some(0),
+            (None, VI_ATTR_SEND_END_EN) => Some(1),
+            (None, _) => None,
+        }
+    }
+
+    fn string_attribute(&self, attr: ViAttr) -> Option<&str> {
+        match attr {
+            VI_ATTR_RSRC_NAME => Some(&self.resource),
+            VI_ATTR_RSRC_CLASS => self.resource.rsplit("::").next(),
+            VI_ATTR_MANF_NAME if matches!(self.kind, Kind::Simulated(_)) => Some("Cosmere"),
+            VI_ATTR_MODEL_NAME if matches!(self.kind, Kind::Simulated(_)) => Some("mock1000"),
+            _ => None,
+        }
+    }
+
+    fn read_settings(&self) -> ReadSettings {
+        ReadSettings {
+            term_char: self
+                .attribute(VI_ATTR_TERMCHAR_EN)
+                .is_some_and(|enabled| enabled != 0)
+                .then_some(self.attribute(VI_ATTR_TERMCHAR).unwrap_or(DEFAULT_TERMCHAR) as u8),
+            timeout: Duration::from_millis(
+                self.attribute(VI_ATTR_TMO_VALUE)
+                    .unwrap_or(DEFAULT_TIMEOUT_MS),
+            ),
+        }
+    }
+}
+
+struct ReadSettings {
+    term_char: Option<u8>,
+    timeout: Duration,
+}
+
+#[derive(Default)]
+struct Simulated {
+    resource: String,
+    input: Vec<u8>,
+    /// Complete messages waiting to be read. The end of each message is signaled with END.
+    output: VecDeque<Vec<u8>>,
+}
+
+impl Simulated {
+    fn receive(&mut self, data: &[u8]) {
+        self.input.extend_from_slice(data);
+        while let Some(end) = self.input.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.input.drain(..=end).collect();
+            let command = String::from_utf8_lossy(&line).trim().to_ascii_uppercase();
+            if command == "*IDN?" {
+                self.output.push_back(
+                    format!("Cosmere,mock1000,{},V0.01.00\n", self.resource).into_bytes(),
+                );
+            }
+        }
+    }
+
+    fn read(&mut self, buf: &mut [u8], settings: &ReadSettings) -> (usize, ViStatus) {
+        let Some(message) = self.output.front_mut() else {
+            return (0, VI_ERROR_TMO);
+        };
+        let (count, status) = take_message_part(message, buf.len(), settings.term_char, true);
+        buf[..count].copy_from_slice(&message[..count]);
+        message.drain(..count);
+        if message.is_empty() {
+            self.output.pop_front();
+        }
+        (count, status)
+    }
+}
+
+struct Socket {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+}
+
+impl Socket {
+    fn read(&mut self, buf: &mut [u8], settings: &ReadSettings) -> (usize, ViStatus) {
+        let mut chunk = [0u8; 4096];
+        let _ = self
+            .stream
+            .set_read_timeout(Some(settings.timeout.max(Duration::from_millis(1))));
+        loop {
+            let (count, status) =
+                take_message_part(&self.buffer, buf.len(), settings.term_char, false);
+            if status != VI_ERROR_TMO {
+                buf[..count].copy_from_slice(&self.buffer[..count]);
+                self.buffer.drain(..count);
+                return (count, status);
+            }
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return (0, VI_ERROR_CONN_LOST),
+                Ok(n) => self.buffer.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(_) => {
+                    let count = self.buffer.len().min(buf.len());
+                    buf[..count].copy_from_slice(&self.buffer[..count]);
+                    self.buffer.drain(..count);
+                    return (count, VI_ERROR_TMO);
+                }
+            }
+        }
+    }
+}
+
+/// Decides how many bytes of `data` a read of `max` bytes returns and with which status.
+/// `complete` is true when `data` is a whole message that ends with END.
+fn take_message_part(
+    data: &[u8],
+    max: usize,
+    term_char: Option<u8>,
+    complete: bool,
+) -> (usize, ViStatus) {
+    let window = &data[..data.len().min(max)];
+    if let Some(pos) = term_char.and_then(|tc| window.iter().position(|b| *b == tc)) {
+        (pos + 1, VI_SUCCESS_TERM_CHAR as ViStatus)
+    } else if complete && data.len() <= max {
+        (data.len(), VI_SUCCESS as ViStatus)
+    } else if window.len() == max {
+        (max, VI_SUCCESS_MAX_CNT as ViStatus)
+    } else {
+        (0, VI_ERROR_TMO)
+    }
+}
+
+struct State {
+    next_handle: ViSession,
+    sessions: HashMap<ViSession, Session>,
+}
+
+impl State {
+    fn insert(&mut self, rm: Option<ViSession>, resource: String, kind: Kind) -> ViSession {
+        self.next_handle += 1;
+        let handle = self.next_handle;
+        self.sessions.insert(
+            handle,
+            Session {
+                rm: rm.unwrap_or(handle),
+                resource,
+                attributes: HashMap::new(),
+                kind,
+            },
+        );
+        handle
+    }
+}
+
+lazy_static! {
+    static ref STATE: Mutex<State> = Mutex::new(State {
+        next_handle: 0,
+        sessions: HashMap::new(),
+    });
+    static ref GPIB_SECONDARY_REGEX: Regex =
+        Regex::new(r"(?i)^(GPIB\d*::\d+)::(\d+)::INSTR$").unwrap();
+    static ref GPIB_INTERFACE_REGEX: Regex = Regex::new(r"(?i)^GPIB\d*::INTFC$").unwrap();
+}
+
+/// A panic must never unwind into the caller of an exported function so a poisoned lock is
+/// simply reused.
+fn state() -> MutexGuard<'static, State> {
+    STATE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn lock<T>(item: &Mutex<T>) -> MutexGuard<'_, T> {
+    item.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Copies `text` into a C string buffer of at least `VI_FIND_BUFLEN` bytes.
+unsafe fn write_c_string(text: &str, dest: *mut c_char) {
+    if dest.is_null() {
+        return;
+    }
+    let len = text.len().min(VI_FIND_BUFLEN as usize - 1);
+    std::ptr::copy_nonoverlapping(text.as_ptr() as *const c_char, dest, len);
+    *dest.add(len) = 0;
+}
+
+/// The name of the simulated instrument that a resource opens if there is one.
+fn simulated_resource(resource: &str) -> Option<String> {
+    let find = |name: &str| {
+        SIMULATED_RESOURCES
+            .iter()
+            .find(|known| known.eq_ignore_ascii_case(name))
+    };
+    if let Some(known) = find(resource) {
+        return Some(known.to_string());
+    }
+    let captures = GPIB_SECONDARY_REGEX.captures(resource)?;
+    let primary = find(&format!("{}::INSTR", &captures[1]))?;
+    let secondary: u16 = captures[2]
+        .parse()
+        .ok()
+        .filter(|secondary| *secondary <= 30)?;
+    Some(primary.replace("::INSTR", &format!("::{secondary}::INSTR")))
+}
+
+fn resolve_alias(name: &str) -> Option<&'static str> {
+    ALIASES
+        .iter()
+        .find(|(alias, _)| alias.eq_ignore_ascii_case(name))
+        .map(|(_, resource)| *resource)
+}
+
+/// Converts a VISA resource expression into an anchored case insensitive regex. `?` matches
+/// any character while `*`, `+`, `[list]`, `|` and `(...)` keep their regex meaning.
+fn expression_to_regex(expression: &str) -> Option<Regex> {
+    let mut pattern = String::from("(?i)^(?:");
+    for c in expression.chars() {
+        match c {
+            '?' => pattern.push('.'),
+            '*' | '+' | '[' | ']' | '^' | '|' | '(' | ')' => pattern.push(c),
+            _ => pattern.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    pattern.push_str(")$");
+    Regex::new(&pattern).ok()
+}
+
+struct ParsedResource {
+    interface_type: ViUInt16,
+    board: ViUInt16,
+    class: String,
+    expanded: String,
+    alias: &'static str,
+}
+
+fn parse_resource(name: &str) -> Option<ParsedResource> {
+    let (expanded, alias) = match resolve_alias(name) {
+        Some(resource) => (resource.to_owned(), name),
+        None => (name.to_owned(), ""),
+    };
+    let alias = ALIASES
+        .iter()
+        .find(|(alias_name, resource)| {
+            alias_name.eq_ignore_ascii_case(alias) || resource.eq_ignore_ascii_case(&expanded)
+        })
+        .map_or("", |(alias, _)| *alias);
+    let parts: Vec<String> = expanded.split("::").map(str::to_ascii_uppercase).collect();
+    let prefix = parts.first()?;
+    let (interface, interface_type) = [
+        ("GPIB-VXI", VI_INTF_GPIB_VXI),
+        ("GPIB", VI_INTF_GPIB),
+        ("VXI", VI_INTF_VXI),
+        ("ASRL", VI_INTF_ASRL),
+        ("PXI", VI_INTF_PXI),
+        ("TCPIP", VI_INTF_TCPIP),
+        ("USB", VI_INTF_USB),
+    ]
+    .into_iter()
+    .find(|(interface, _)| prefix.starts_with(interface))?;
+    let board = match &prefix[interface.len()..] {
+        "" => 0,
+        number => number.parse().ok()?,
+    };
+    let class = match parts.last().map(String::as_str) {
+        Some(
+            class @ ("INSTR" | "SOCKET" | "INTFC" | "BACKPLANE" | "MEMACC" | "SERVANT" | "RAW"),
+        ) => class.to_owned(),
+        _ => "INSTR".to_owned(),
+    };
+    Some(ParsedResource {
+        interface_type: interface_type as ViUInt16,
+        board,
+        class,
+        expanded,
+        alias,
+    })
+}
+
+fn open_socket(resource: &str) -> Option<TcpStream> {
+    let parts: Vec<&str> = resource.split("::").collect();
+    match parts.as_slice() {
+        [board, host, port, class]
+            if board.to_ascii_uppercase().starts_with("TCPIP")
+                && class.eq_ignore_ascii_case("SOCKET") =>
+        {
+            TcpStream::connect(format!("{host}:{port}")).ok()
+        }
+        _ => None,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn viOpenDefaultRM(vi: ViPSession) -> ViStatus {
+    *vi = state().insert(None, String::new(), Kind::ResourceManager);
+    VI_SUCCESS as ViStatus
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn viOpen(
+    sesn: ViSession,
+    name: ViConstRsrc,
+    _mode: ViAccessMode,
+    _timeout: ViUInt32,
+    vi: ViPSession,
+) -> ViStatus {
+    *vi = 0;
+    let resource = CStr::from_ptr(name).to_string_lossy().into_owned();
+    let resource = match resolve_alias(&resource) {
+        Some(resolved) => resolved.to_owned(),
+        None => resource,
+    };
+    if !matches!(
+        state().sessions.get(&sesn).map(|s| &s.kind),
+        Some(Kind::ResourceManager)
+    ) {
+        return VI_ERROR_INV_OBJECT;
+    }
+    let kind = if let Some(known) = simulated_resource(&resource) {
+        Kind::Simulated(Arc::new(Mutex::new(Simulated {
+            resource: known,
+            ..Default::default()
+        })))
+    } else if GPIB_INTERFACE_REGEX.is_match(&resource) {
+        Kind::GpibInterface
+    } else if let Some(stream) = open_socket(&resource) {
+        Kind::Socket(Arc::new(Mutex::new(Socket {
+            stream,
+            buffer: Vec::new(),
+        })))
+    } else {
+        return VI_ERROR_RSRC_NFOUND;
+    };
+    *vi = state().insert(Some(sesn), resource, kind);
+    VI_SUCCESS as ViStatus
+}
+
+#[no_mangle]
+pub extern "C" fn viClose(vi: ViObject) -> ViStatus {
+    let mut state = state();
+    match state.sessions.remove(&vi) {
+        Some(Session {
+            kind: Kind::ResourceManager,
+            ..
+        }) => {
+            // Closing a resource manager closes every session it opened.
+            state.sessions.retain(|_, session| session.rm != vi);
+            VI_SUCCESS as ViStatus
+        }
+        Some(_) => VI_SUCCESS as ViStatus,
+        None => VI_ERROR_INV_OBJECT,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn viClear(vi: ViSession) -> ViStatus {
+    match state().sessions.get(&vi).map(|s| &s.kind) {
+        Some(Kind::Simulated(sim)) => {
+            let mut sim = lock(sim);
+            sim.input.clear();
+            sim.output.clear();
+        }
+        Some(Kind::Socket(socket)) => lock(socket).buffer.clear(),
+        Some(Kind::ResourceManager | Kind::FindList(_) | Kind::GpibInterface) => {
+            return VI_ERROR_NSUP_OPER
+        }
+        None => return VI_ERROR_INV_OBJECT,
+    }
+    VI_SUCCESS as ViStatus
+}
+
+#[no_mangle]
+pub extern "C" fn viSetAttribute(
+    vi: ViObject,
+    attr_name: ViAttr,
+    attr_value: ViAttrState,
+) -> ViStatus {
+    let mut state = state();
+    let Some(session) = state.sessions.get_mut(&vi) else {
+        return VI_ERROR_INV_OBJECT;
+    };
+    match attr::lookup(attr_name) {
+        Some((_, _, true)) => VI_ERROR_ATTR_READONLY,
+        Some(_) => {
+            session.attributes.insert(attr_name, attr_value);
+            VI_SUCCESS as ViStatus
+        }
+        None => VI_ERROR_NSUP_ATTR,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn viFindRsrc(
+    sesn: ViSession,
+    expr: ViConstString,
+    vi: ViPFindList,
+    ret_cnt: ViPUInt32,
+    desc: *mut ViChar,
+) -> ViStatus {
+    if !vi.is_null() {
+        *vi = 0;
+    }
+    if !matches!(
+        state().sessions.get(&sesn).map(|s| &s.kind),
+        Some(Kind::ResourceManager)
+    ) {
+        return VI_ERROR_INV_OBJECT;
+    }
+    let expression = CStr::from_ptr(expr).to_string_lossy().into_owned();
+    let Some(regex) = expression_to_regex(&expression) else {
+        return VI_ERROR_INV_EXPR;
+    };
+    let mut found: VecDeque<String> = SIMULATED_RESOURCES
+        .iter()
+        .filter(|resource| regex.is_match(resource))
+        .map(|resource| resource.to_string())
+        .collect();
+    let Some(first) = found.pop_front() else {
+        return VI_ERROR_RSRC_NFOUND;
+    };
+    if !ret_cnt.is_null() {
+        *ret_cnt = found.len() as ViUInt32 + 1;
+    }
+    write_c_string(&first, desc);
+    // The find list is only created when the caller asks for it.
+    if !vi.is_null() {
+        *vi = state().insert(Some(sesn), expression, Kind::FindList(found));
+    }
+    VI_SUCCESS as ViStatus
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn viFindNext(vi: ViFindList, desc: *mut ViChar) -> ViStatus {
+    let next = match state().sessions.get_mut(&vi).map(|s| &mut s.kind) {
+        Some(Kind::FindList(found)) => found.pop_front(),
+        _ => return VI_ERROR_INV_OBJECT,
+    };
+    match next {
+        Some(resource) => {
+            write_c_string(&resource, desc);
+            VI_SUCCESS as ViStatus
+        }
+        None => VI_ERROR_RSRC_NFOUND,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn viParseRsrc(
+    rm_sesn: ViSession,
+    rsrc_name: ViConstRsrc,
+    intf_type: ViPUInt16,
+    intf_num: ViPUInt16,
+) -> ViStatus {
+    viParseRsrcEx(
+        rm_sesn,
+        rsrc_name,
+        intf_type,
+        intf_num,
+        std::ptr::null_mut(),
+        std::ptr::null_mut(),
+        std::ptr::null_mut(),
+    )
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn viParseRsrcEx(
+    rm_sesn: ViSession,
+    rsrc_name: ViConstRsrc,
+    intf_type: ViPUInt16,
+    intf_num: ViPUInt16,
+    rsrc_class: *mut ViChar,
+    expanded_unaliased_name: *mut ViChar,
+    alias_if_exists: *mut ViChar,
+) -> ViStatus {
+    if !matches!(
+        state().sessions.get(&rm_sesn).map(|s| &s.kind),
+        Some(Kind::ResourceManager)
+    ) {
+        return VI_ERROR_INV_OBJECT;
+    }
+    let name = CStr::from_ptr(rsrc_name).to_string_lossy();
+    let Some(parsed) = parse_resource(&name) else {
+        return VI_ERROR_INV_RSRC_NAME;
+    };
+    if !intf_type.is_null() {
+        *intf_type = parsed.interface_type;
+    }
+    if !intf_num.is_null() {
+        *intf_num = parsed.board;
+    }
+    write_c_string(&parsed.class, rsrc_class);
+    write_c_string(&parsed.expanded, expanded_unaliased_name);
+    write_c_string(parsed.alias, alias_if_exists);
+    VI_SUCCESS as ViStatus
+}
+
+/// Values are written with the width VISA defines for the attribute, like a vendor binary does.
+#[no_mangle]
+pub unsafe extern "C" fn viGetAttribute(
+    vi: ViObject,
+    attr_name: ViAttr,
+    attr_value: *mut c_void,
+) -> ViStatus {
+    let state = state();
+    let Some(session) = state.sessions.get(&vi) else {
+        return VI_ERROR_INV_OBJECT;
+    };
+    let Some((_, kind, _)) = attr::lookup(attr_name) else {
+        return VI_ERROR_NSUP_ATTR;
+    };
+    match (kind, session.attribute(attr_name)) {
+        (attr::ValueKind::String, _) => match session.string_attribute(attr_name) {
+            Some(text) => write_c_string(text, attr_value as *mut c_char),
+            None => return VI_ERROR_NSUP_ATTR,
+        },
+        (_, None) => return VI_ERROR_NSUP_ATTR,
+        (attr::ValueKind::U8, Some(value)) => *(attr_value as *mut ViUInt8) = value as ViUInt8,
+        (attr::ValueKind::U16, Some(value)) => *(attr_value as *mut ViUInt16) = value as ViUInt16,
+        (attr::ValueKind::U32, Some(value)) => *(attr_value as *mut ViUInt32) = value as ViUInt32,
+        (attr::ValueKind::U64, Some(value)) => *(attr_value as *mut ViUInt64) = value as ViUInt64,
+        (attr::ValueKind::I16, Some(value)) => *(attr_value as *mut ViInt16) = value as ViInt16,
+        (attr::ValueKind::Bool, Some(value)) => {
+            *(attr_value as *mut ViBoolean) = (value != 0) as ViBoolean
+        }
+    }
+    VI_SUCCESS as ViStatus
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn viStatusDesc(
+    _vi: ViObject,
+    status: ViStatus,
+    desc: *mut ViByte,
+) -> ViStatus {
+    write_c_string(&format!("Mock VISA status {status}"), desc as *mut c_char);
+    VI_SUCCESS as ViStatus
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn viWrite(
+    vi: ViSession,
+    buf: ViConstBuf,
+    cnt: ViUInt32,
+    ret_cnt: ViPUInt32,
+) -> ViStatus {
+    let data = std::slice::from_raw_parts(buf, cnt as usize);
+    let kind = match state().sessions.get(&vi).map(|s| &s.kind) {
+        Some(Kind::Simulated(sim)) => Kind::Simulated(sim.clone()),
+        Some(Kind::Socket(socket)) => Kind::Socket(socket.clone()),
+        Some(Kind::ResourceManager | Kind::FindList(_) | Kind::GpibInterface) => {
+            return VI_ERROR_NSUP_OPER
+        }
+        None => return VI_ERROR_INV_OBJECT,
+    };
+    match kind {
+        Kind::Simulated(sim) => lock(&sim).receive(data),
+        Kind::Socket(socket) => {
+            if lock(&socket).stream.write_all(data).is_err() {
+                return VI_ERROR_IO;
+            }
+        }
+        Kind::ResourceManager | Kind::FindList(_) | Kind::GpibInterface => unreachable!(),
+    }
+    if !ret_cnt.is_null() {
+        *ret_cnt = cnt;
+    }
+    VI_SUCCESS as ViStatus
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn viRead(
+    vi: ViSession,
+    buf: ViPBuf,
+    cnt: ViUInt32,
+    ret_cnt: ViPUInt32,
+) -> ViStatus {
+    let buf = std::slice::from_raw_parts_mut(buf, cnt as usize);
+    let (settings, kind) = match state().sessions.get(&vi) {
+        Some(session) => match &session.kind {
+            Kind::Simulated(sim) => (session.read_settings(), Kind::Simulated(sim.clone())),
+            Kind::Socket(socket) => (session.read_settings(), Kind::Socket(socket.clone())),
+            Kind::ResourceManager | Kind::FindList(_) | Kind::GpibInterface => {
+                return VI_ERROR_NSUP_OPER
+            }
+        },
+        None => return VI_ERROR_INV_OBJECT,
+    };
+    // The global lock is released so a slow read doesn't block the other sessions.
+    let (count, status) = match kind {
+        Kind::Simulated(sim) => lock(&sim).read(buf, &settings),
+        Kind::Socket(socket) => lock(&socket).read(buf, &settings),
+        Kind::ResourceManager | Kind::FindList(_) | Kind::GpibInterface => unreachable!(),
+    };
+    if !ret_cnt.is_null() {
+        *ret_cnt = count as ViUInt32;
+    }
+    status
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn viReadSTB(vi: ViSession, status: ViPUInt16) -> ViStatus {
+    match state().sessions.get(&vi) {
+        Some(_) => {
+            *status = 0;
+            VI_SUCCESS as ViStatus
+        }
+        None => VI_ERROR_INV_OBJECT,
+    }
+}
+
+/// Runs a GPIB bus control operation on the session. Only GPIB interfaces support every
+/// operation while GPIB instruments only support `viGpibControlREN`.
+fn gpib_control(
+    vi: ViSession,
+    instruments: bool,
+    operation: impl FnOnce(&mut HashMap<ViAttr, ViAttrState>) -> ViStatus,
+) -> ViStatus {
+    let mut state = state();
+    let Some(session) = state.sessions.get_mut(&vi) else {
+        return VI_ERROR_INV_OBJECT;
+    };
+    let supported = match &session.kind {
+        Kind::GpibInterface => true,
+        Kind::Simulated(_) => {
+            instruments && session.resource.to_ascii_uppercase().starts_with("GPIB")
+        }
+        _ => false,
+    };
+    if supported {
+        operation(&mut session.attributes)
+    } else {
+        VI_ERROR_NSUP_OPER
+    }
+}
+
+fn line_state(asserted: bool) -> ViAttrState {
+    if asserted {
+        VI_STATE_ASSERTED as ViAttrState
+    } else {
+        VI_STATE_UNASSERTED as ViAttrState
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn viGpibControlREN(vi: ViSession, mode: ViUInt16) -> ViStatus {
+    gpib_control(vi, true, |attributes| {
+        let asserted = match mode as u32 {
+            VI_GPIB_REN_DEASSERT | VI_GPIB_REN_DEASSERT_GTL => false,
+            VI_GPIB_REN_ASSERT..=VI_GPIB_REN_ADDRESS_GTL => true,
+            _ => return VI_ERROR_INV_MODE,
+        };
+        attributes.insert(VI_ATTR_GPIB_REN_STATE, line_state(asserted));
+        VI_SUCCESS as ViStatus
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn viGpibControlATN(vi: ViSession, mode: ViUInt16) -> ViStatus {
+    gpib_control(vi, false, |attributes| {
+        let asserted = match mode as u32 {
+            VI_GPIB_ATN_DEASSERT | VI_GPIB_ATN_DEASSERT_HANDSHAKE => false,
+            VI_GPIB_ATN_ASSERT | VI_GPIB_ATN_ASSERT_IMMEDIATE => true,
+            _ => return VI_ERROR_INV_MODE,
+        };
+        attributes.insert(VI_ATTR_GPIB_ATN_STATE, line_state(asserted));
+        VI_SUCCESS as ViStatus
+    })
+}
+
+/// Sending IFC makes the interface the controller in charge.
+#[no_mangle]
+pub extern "C" fn viGpibSendIFC(vi: ViSession) -> ViStatus {
+    gpib_control(vi, false, |attributes| {
+        attributes.insert(VI_ATTR_GPIB_CIC_STATE, 1);
+        VI_SUCCESS as ViStatus
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn viGpibCommand(
+    vi: ViSession,
+    _cmd: ViConstBuf,
+    cnt: ViUInt32,
+    ret_cnt: ViPUInt32,
+) -> ViStatus {
+    gpib_control(vi, false, |_| {
+        if !ret_cnt.is_null() {
+            *ret_cnt = cnt;
+        }
+        VI_SUCCESS as ViStatus
+    })
+}
+
+/// Passing control to another device makes the interface give up being the controller in
+/// charge.
+#[no_mangle]
+pub extern "C" fn viGpibPassControl(
+    vi: ViSession,
+    prim_addr: ViUInt16,
+    sec_addr: ViUInt16,
+) -> ViStatus {
+    gpib_control(vi, false, |attributes| {
+        if prim_addr > 30 || (sec_addr > 30 && sec_addr as u32 != VI_NO_SEC_ADDR) {
+            return VI_ERROR_INV_PARAMETER;
+        }
+        attributes.insert(VI_ATTR_GPIB_CIC_STATE, 0);
+        VI_SUCCESS as ViStatus
+    })
+}
+
+/// Number of sessions, including resource managers, that are currently open.
+#[no_mangle]
+pub extern "C" fn mockOpenSessionCount() -> ViUInt32 {
+    state().sessions.len() as ViUInt32
+}
+
+/// Exports operations that the mock doesn't support. They are only here so that the library
+/// contains every symbol that `visa::VisaFuncs` loads.
+macro_rules! unsupported {
+    ($($name:ident)*) => {
+        $(
+            #[no_mangle]
+            pub extern "C" fn $name() -> ViStatus {
+                VI_ERROR_NSUP_OPER
+            }
+        )*
+    };
+}
+
+unsupported! {
+    viTerminate viLock viUnlock viEnableEvent
+    viDisableEvent viDiscardEvents viWaitOnEvent viInstallHandler viUninstallHandler viReadAsync
+    viReadToFile viWriteAsync viWriteFromFile viAssertTrigger viSetBuf viFlush viBufWrite
+    viBufRead viVPrintf viVSPrintf viVScanf viVSScanf viVQueryf viIn8 viOut8 viIn16 viOut16
+    viIn32 viOut32 viIn64 viOut64 viIn8Ex viOut8Ex viIn16Ex viOut16Ex viIn32Ex viOut32Ex viIn64Ex
+    viOut64Ex viMoveIn8 viMoveOut8 viMoveIn16 viMoveOut16 viMoveIn32 viMoveOut32 viMoveIn64
+    viMoveOut64 viMoveIn8Ex viMoveOut8Ex viMoveIn16Ex viMoveOut16Ex viMoveIn32Ex viMoveOut32Ex
+    viMoveIn64Ex viMoveOut64Ex viMove viMoveAsync viMoveEx viMoveAsyncEx viMapAddress
+    viUnmapAddress viMapAddressEx viPeek8 viPoke8 viPeek16 viPoke16 viPeek32 viPoke32 viPeek64
+    viPoke64 viMemAlloc viMemFree viMemAllocEx viMemFreeEx viVxiCommandQuery viAssertUtilSignal
+    viAssertIntrSignal viMapTrigger viUnmapTrigger viUsbControlOut viUsbControlIn
+    viPxiReserveTriggers
+}
+
+/// Path of the mock dynamic library. Cargo places it next to the test executables that
+/// depend on this crate.
+pub fn library_path() -> PathBuf {
+    let name = format!(
+        "{}visa_mock{}",
+        std::env::consts::DLL_PREFIX,
+        std::env::consts::DLL_SUFFIX
+    );
+    let exe = std::env::current_exe().unwrap_or_default();
+    exe.ancestors()
+        .skip(1)
+        .take(2)
+        .map(|dir| dir.join(&name))
+        .find(|path| path.exists())
+        .unwrap_or_else(|| PathBuf::from(name))
+}
+
+/// The binary to pass to `visa::create` or to a connection to use the mock library.
+pub fn binary() -> Binary {
+    Binary::Custom(library_path().to_string_lossy().into_owned())
+}
+
+/// Number of sessions currently open in the loaded mock library. The count is read from the
+/// dynamic library since that is the copy of the mock that VISA callers talk to.
+pub fn open_session_count() -> u32 {
+    let lib = Library::open(library_path()).expect("mock VISA library should be built");
+    let count: extern "C" fn() -> ViUInt32 =
+        unsafe { lib.symbol("mockOpenSessionCount") }.expect("mock symbol should exist");
+    count()
+}